@@ -6,7 +6,7 @@ use crate::{
     bgp_afi2family, host_addr, host_addr__bindgen_ty_1, prefix, prefix__bindgen_ty_1,
     DefaultZeroed, AFI_IP, AFI_IP6,
 };
-use ipnet::{Ipv4Net, Ipv6Net};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
 
 impl From<&Ipv4Addr> for crate::in_addr {
     fn from(value: &Ipv4Addr) -> Self {
@@ -72,6 +72,28 @@ impl From<&Ipv6Net> for prefix {
     }
 }
 
+/// Fails with the address family of the prefix if it is neither IPv4 nor IPv6
+impl TryFrom<&prefix> for IpNet {
+    type Error = u8;
+
+    fn try_from(value: &prefix) -> Result<Self, Self::Error> {
+        let family = c_int::from(value.family);
+
+        let net = if family == unsafe { bgp_afi2family(AFI_IP as c_int) } {
+            Ipv4Net::new(Ipv4Addr::from(unsafe { &value.u.prefix4 }), value.prefixlen)
+                .map(IpNet::V4)
+        } else if family == unsafe { bgp_afi2family(AFI_IP6 as c_int) } {
+            Ipv6Net::new(Ipv6Addr::from(unsafe { &value.u.prefix6 }), value.prefixlen)
+                .map(IpNet::V6)
+        } else {
+            return Err(value.family);
+        };
+
+        // An invalid prefix length is an invalid prefix as a whole
+        net.map(|net| net.trunc()).map_err(|_| value.family)
+    }
+}
+
 impl From<&Ipv6Addr> for host_addr {
     fn from(value: &Ipv6Addr) -> Self {
         host_addr {
@@ -99,6 +121,23 @@ impl From<&IpAddr> for host_addr {
     }
 }
 
+/// Fails with the address family of the address if it is neither IPv4 nor IPv6
+impl TryFrom<&host_addr> for IpAddr {
+    type Error = u8;
+
+    fn try_from(value: &host_addr) -> Result<Self, Self::Error> {
+        let family = c_int::from(value.family);
+
+        if family == unsafe { bgp_afi2family(AFI_IP as c_int) } {
+            Ok(IpAddr::V4(Ipv4Addr::from(unsafe { &value.address.ipv4 })))
+        } else if family == unsafe { bgp_afi2family(AFI_IP6 as c_int) } {
+            Ok(IpAddr::V6(Ipv6Addr::from(unsafe { &value.address.ipv6 })))
+        } else {
+            Err(value.family)
+        }
+    }
+}
+
 impl host_addr {
     pub fn default_ipv4() -> Self {
         Self {
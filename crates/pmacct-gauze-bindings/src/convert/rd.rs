@@ -40,32 +40,34 @@ impl From<RouteDistinguisher> for rd_t {
     }
 }
 
-impl From<rd_t> for RouteDistinguisher {
-    fn from(value: rd_t) -> Self {
+/// Fails with the type of the RD, without its origin, if it is not an AS, IP or AS4 RD
+impl TryFrom<rd_t> for RouteDistinguisher {
+    type Error = u16;
+
+    fn try_from(value: rd_t) -> Result<Self, Self::Error> {
         let rd_type = unsafe { bgp_rd_type_get(value.type_) } as u32;
 
         if rd_type == RD_TYPE_AS {
-            RouteDistinguisher::As2Administrator {
+            Ok(RouteDistinguisher::As2Administrator {
                 asn2: value.as_,
                 number: value.val,
-            }
+            })
         } else if rd_type == RD_TYPE_IP {
             let rd_ip: rd_ip = unsafe { transmute(value) };
 
-            RouteDistinguisher::Ipv4Administrator {
+            Ok(RouteDistinguisher::Ipv4Administrator {
                 ip: Ipv4Addr::from(rd_ip.ip),
                 number: rd_ip.val,
-            }
+            })
         } else if rd_type == RD_TYPE_AS4 {
             let rd_as4: rd_as4 = unsafe { transmute(value) };
 
-            RouteDistinguisher::As4Administrator {
+            Ok(RouteDistinguisher::As4Administrator {
                 asn4: rd_as4.as_,
                 number: rd_as4.val,
-            }
+            })
         } else {
-            unreachable!()
-            // TODO make error for this, impl TryFrom instead
+            Err(rd_type as u16)
         }
     }
 }
@@ -14,7 +14,10 @@ use crate::opaque::Opaque;
 pub mod notification;
 pub mod open;
 pub mod parse;
+pub mod rib;
+pub mod session;
 pub mod update;
+pub mod write;
 
 /// Print a BGP Message
 /// # Safety
@@ -1,8 +1,21 @@
+use std::ffi::{c_char, CStr, CString};
+use std::{ptr, slice};
+
+use netgauze_bgp_pkt::iana::{BgpErrorNotificationCode, CeaseErrorSubCode};
+use netgauze_bgp_pkt::notification::{
+    BgpNotificationMessage, CeaseError, OpenMessageError, UpdateMessageError,
+};
 use netgauze_bgp_pkt::BgpMessage;
 
+use crate::capi::bgp::write::{write_bgp_message, BgpMessageWriteError, BgpMessageWriteResult};
 use crate::capi::bgp::WrongBgpMessageTypeError;
 use crate::cresult::CResult;
-use crate::extensions::bgp_notification::ExtendBgpNotificationMessage;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::extensions::bgp_notification::{
+    decode_shutdown_communication, encode_shutdown_communication, BgpNotificationBuildError,
+    ExtendBgpNotificationMessage,
+};
+use crate::free_cslice_t;
 use crate::opaque::Opaque;
 
 #[repr(C)]
@@ -40,3 +53,485 @@ pub unsafe extern "C" fn netgauze_bgp_notification(
         value: value.as_ptr(),
     })
 }
+
+/// A capability TLV listed in an Unsupported Capability notification.
+/// The `value` pointer is borrowed from the notification message.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BgpNotificationCapability {
+    code: u8,
+    value_len: u8,
+    value: *const u8,
+}
+
+free_cslice_t!(BgpNotificationCapability);
+
+/// Decoded data of a [BgpNotificationDetails]
+///
+/// Pointers to the message data are borrowed from the notification message.
+/// `ShutdownCommunication::message` and `UnsupportedCapabilities` are owned
+/// and freed with [netgauze_bgp_notification_details_free]
+#[repr(C)]
+#[derive(Debug)]
+pub enum BgpNotificationData {
+    Empty,
+    /// Data of a Cease Administrative Shutdown or Reset (RFC 9003), as a UTF-8 string
+    ShutdownCommunication {
+        message: *mut c_char,
+    },
+    /// Data of an OPEN Unsupported Capability error
+    UnsupportedCapabilities(OwnedSlice<BgpNotificationCapability>),
+    /// Data of an UPDATE error carrying the erroneous attribute
+    MalformedAttribute {
+        attr_flags: u8,
+        attr_type: u8,
+        attr_value_len: usize,
+        attr_value: *const u8,
+    },
+    /// Data of an UPDATE Missing Well-known Attribute error
+    MissingAttribute {
+        attr_type: u8,
+    },
+    /// Data that has no specific structure or could not be decoded
+    Raw {
+        value_len: usize,
+        value: *const u8,
+    },
+}
+
+/// Decoded BGP Notification. `code_name` and `subcode_name` are static strings.
+///
+/// This structure must be freed using [netgauze_bgp_notification_details_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BgpNotificationDetails {
+    pub code: u8,
+    pub subcode: u8,
+    pub code_name: *const c_char,
+    pub subcode_name: *const c_char,
+    pub data: BgpNotificationData,
+}
+
+pub type BgpNotificationDetailsResult = CResult<BgpNotificationDetails, WrongBgpMessageTypeError>;
+
+/// Get the decoded [BgpNotificationDetails] from a BGP Message
+///
+/// # Safety
+/// `bgp_message` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_message` pointer.
+/// The result borrows from `bgp_message` and must not outlive it.
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_notification_details(
+    bgp_message: *const Opaque<BgpMessage>,
+) -> BgpNotificationDetailsResult {
+    let bgp_message = unsafe { bgp_message.as_ref().unwrap().as_ref() };
+
+    let bgp_notification = match bgp_message {
+        BgpMessage::Notification(notif) => notif,
+        _ => return WrongBgpMessageTypeError(bgp_message.get_type().into()).into(),
+    };
+
+    CResult::Ok(BgpNotificationDetails::from(bgp_notification))
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_notification_details_free(value: BgpNotificationDetails) {
+    value.rust_free();
+}
+
+impl From<&BgpNotificationMessage> for BgpNotificationDetails {
+    fn from(notification: &BgpNotificationMessage) -> Self {
+        Self {
+            code: notification.code() as u8,
+            subcode: notification.raw_subcode(),
+            code_name: notification.code_name().as_ptr(),
+            subcode_name: notification.subcode_name().as_ptr(),
+            data: BgpNotificationData::from(notification),
+        }
+    }
+}
+
+impl RustFree for BgpNotificationDetails {
+    fn rust_free(self) {
+        match self.data {
+            BgpNotificationData::ShutdownCommunication { message } => unsafe {
+                drop(CString::from_raw(message));
+            },
+            BgpNotificationData::UnsupportedCapabilities(capabilities) => {
+                capabilities.rust_free();
+            }
+            BgpNotificationData::Empty
+            | BgpNotificationData::MalformedAttribute { .. }
+            | BgpNotificationData::MissingAttribute { .. }
+            | BgpNotificationData::Raw { .. } => {}
+        }
+    }
+}
+
+impl From<&BgpNotificationMessage> for BgpNotificationData {
+    fn from(notification: &BgpNotificationMessage) -> Self {
+        let value = notification.value_ptr();
+        let raw = BgpNotificationData::Raw {
+            value_len: value.len(),
+            value: value.as_ptr(),
+        };
+
+        if value.is_empty() {
+            return BgpNotificationData::Empty;
+        }
+
+        match notification {
+            BgpNotificationMessage::CeaseError(
+                CeaseError::AdministrativeShutdown { value }
+                | CeaseError::AdministrativeReset { value },
+            ) => decode_shutdown_communication(value)
+                .and_then(|message| CString::new(message).ok())
+                .map(|message| BgpNotificationData::ShutdownCommunication {
+                    message: message.into_raw(),
+                })
+                .unwrap_or(raw),
+            BgpNotificationMessage::OpenMessageError(OpenMessageError::UnsupportedCapability {
+                value,
+            }) => decode_capabilities(value)
+                .map(|capabilities| {
+                    BgpNotificationData::UnsupportedCapabilities(OwnedSlice::from_vec(capabilities))
+                })
+                .unwrap_or(raw),
+            BgpNotificationMessage::UpdateMessageError(
+                UpdateMessageError::MissingWellKnownAttribute { value },
+            ) => BgpNotificationData::MissingAttribute {
+                attr_type: value[0],
+            },
+            BgpNotificationMessage::UpdateMessageError(
+                UpdateMessageError::UnrecognizedWellKnownAttribute { value }
+                | UpdateMessageError::AttributeFlagsError { value }
+                | UpdateMessageError::AttributeLengthError { value }
+                | UpdateMessageError::InvalidOriginAttribute { value }
+                | UpdateMessageError::InvalidNextHopAttribute { value }
+                | UpdateMessageError::OptionalAttributeError { value },
+            ) => decode_attribute(value).unwrap_or(raw),
+            _ => raw,
+        }
+    }
+}
+
+/// Split the data of an Unsupported Capability error into capability TLVs
+fn decode_capabilities(mut value: &[u8]) -> Option<Vec<BgpNotificationCapability>> {
+    let mut capabilities = Vec::new();
+
+    while !value.is_empty() {
+        let (code, value_len) = (*value.first()?, *value.get(1)?);
+        let data = value.get(2..2 + value_len as usize)?;
+
+        capabilities.push(BgpNotificationCapability {
+            code,
+            value_len,
+            value: if data.is_empty() {
+                ptr::null()
+            } else {
+                data.as_ptr()
+            },
+        });
+
+        value = &value[2 + value_len as usize..];
+    }
+
+    Some(capabilities)
+}
+
+/// Split the erroneous attribute (flags, type, length, value) in the data of an UPDATE error
+fn decode_attribute(value: &[u8]) -> Option<BgpNotificationData> {
+    const EXTENDED_LENGTH_FLAG: u8 = 0x10;
+
+    let (attr_flags, attr_type) = (*value.first()?, *value.get(1)?);
+    let (attr_value_len, offset) = if attr_flags & EXTENDED_LENGTH_FLAG != 0 {
+        (
+            u16::from_be_bytes([*value.get(2)?, *value.get(3)?]) as usize,
+            4,
+        )
+    } else {
+        (*value.get(2)? as usize, 3)
+    };
+
+    // Routers may truncate the attribute, only give what is really there
+    let attr_value = &value[offset..];
+    let attr_value_len = attr_value_len.min(attr_value.len());
+
+    Some(BgpNotificationData::MalformedAttribute {
+        attr_flags,
+        attr_type,
+        attr_value_len,
+        attr_value: attr_value.as_ptr(),
+    })
+}
+
+impl From<BgpNotificationBuildError> for BgpMessageWriteError {
+    fn from(value: BgpNotificationBuildError) -> Self {
+        match value {
+            BgpNotificationBuildError::UndefinedCode(code) => Self::UndefinedNotificationCode(code),
+            BgpNotificationBuildError::UndefinedSubCode { code, subcode } => {
+                Self::UndefinedNotificationSubCode { code, subcode }
+            }
+        }
+    }
+}
+
+/// Write a BGP Notification message with the given code, subcode and data in `buf`
+///
+/// # Safety
+/// `data` should point to valid data of length `data_len`, or be null if `data_len` is 0
+/// `buf` should be not null and point a byte buffer of length `buf_len` we can write to
+///
+/// This function does not consume the `data` and `buf` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_write_notification(
+    code: u8,
+    subcode: u8,
+    data: *const u8,
+    data_len: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> BgpMessageWriteResult {
+    let buf = unsafe { slice::from_raw_parts_mut(buf as *mut u8, buf_len) };
+    let data = if data_len == 0 {
+        Vec::new()
+    } else {
+        unsafe { slice::from_raw_parts(data, data_len) }.to_vec()
+    };
+
+    let notification = match BgpNotificationMessage::from_raw(code, subcode, data) {
+        Ok(notification) => notification,
+        Err(err) => return BgpMessageWriteError::from(err).into(),
+    };
+
+    write_bgp_message(&BgpMessage::Notification(notification), buf).into()
+}
+
+/// Write a BGP Cease Notification carrying a Shutdown Communication (RFC 8203 / RFC 9003) in `buf`
+///
+/// `subcode` must be either Administrative Shutdown (2) or Administrative Reset (4).
+/// `message` is a null-terminated UTF-8 string of at most 255 bytes, or null for an empty message.
+///
+/// # Safety
+/// `message` should be null or point to a valid null-terminated string
+/// `buf` should be not null and point a byte buffer of length `buf_len` we can write to
+///
+/// This function does not consume the `message` and `buf` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_write_shutdown_notification(
+    subcode: u8,
+    message: *const c_char,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> BgpMessageWriteResult {
+    if subcode != CeaseErrorSubCode::AdministrativeShutdown as u8
+        && subcode != CeaseErrorSubCode::AdministrativeReset as u8
+    {
+        return BgpMessageWriteError::NotAShutdownCommunicationSubCode(subcode).into();
+    }
+
+    let message = if message.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(message) }.to_str() {
+            Ok(message) => message,
+            Err(_) => return BgpMessageWriteError::ShutdownCommunicationNotUtf8.into(),
+        }
+    };
+
+    let data = match encode_shutdown_communication(message) {
+        Ok(data) => data,
+        Err(len) => return BgpMessageWriteError::ShutdownCommunicationTooLong(len).into(),
+    };
+
+    let buf = unsafe { slice::from_raw_parts_mut(buf as *mut u8, buf_len) };
+    let notification = match BgpNotificationMessage::from_raw(
+        BgpErrorNotificationCode::Cease as u8,
+        subcode,
+        data,
+    ) {
+        Ok(notification) => notification,
+        Err(err) => return BgpMessageWriteError::from(err).into(),
+    };
+
+    write_bgp_message(&BgpMessage::Notification(notification), buf).into()
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, CStr};
+    use std::slice;
+
+    use c_str_macro::c_str;
+    use netgauze_bgp_pkt::notification::BgpNotificationMessage;
+
+    use crate::capi::bgp::notification::{
+        netgauze_bgp_notification_details_free, netgauze_bgp_write_notification,
+        netgauze_bgp_write_shutdown_notification, BgpNotificationData, BgpNotificationDetails,
+    };
+    use crate::capi::bgp::write::BgpMessageWriteError;
+    use crate::cresult::CResult;
+    use crate::extensions::bgp_notification::ExtendBgpNotificationMessage;
+
+    /// The [BgpNotificationDetails] borrow the data of the notification,
+    /// which must be kept alive for as long as the details are used
+    fn make_details(
+        code: u8,
+        subcode: u8,
+        value: &[u8],
+    ) -> (BgpNotificationMessage, BgpNotificationDetails) {
+        let notification = BgpNotificationMessage::from_raw(code, subcode, value.to_vec()).unwrap();
+        let details = BgpNotificationDetails::from(&notification);
+        (notification, details)
+    }
+
+    #[test]
+    fn test_notification_details_shutdown_communication() {
+        let (_notification, details) = make_details(6, 4, b"\x05reset");
+        assert_eq!(
+            unsafe { CStr::from_ptr(details.code_name) },
+            c_str!("Cease")
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(details.subcode_name) },
+            c_str!("Administrative Reset")
+        );
+        match &details.data {
+            BgpNotificationData::ShutdownCommunication { message } => {
+                assert_eq!(unsafe { CStr::from_ptr(*message) }, c_str!("reset"))
+            }
+            data => panic!("unexpected data {data:?}"),
+        }
+        netgauze_bgp_notification_details_free(details);
+
+        // Truncated and non UTF-8 messages are given raw
+        for value in [&b"\x0areset"[..], &[2, 0xff, 0xfe]] {
+            let (_notification, details) = make_details(6, 2, value);
+            assert!(matches!(
+                details.data,
+                BgpNotificationData::Raw { value_len, .. } if value_len == value.len()
+            ));
+            netgauze_bgp_notification_details_free(details);
+        }
+    }
+
+    #[test]
+    fn test_notification_details_unsupported_capabilities() {
+        // Route Refresh (no value) and 4-octet AS (4 bytes)
+        let (_notification, details) = make_details(2, 7, &[2, 0, 65, 4, 0, 0, 0xfd, 0xe8]);
+        match &details.data {
+            BgpNotificationData::UnsupportedCapabilities(capabilities) => {
+                let capabilities =
+                    unsafe { slice::from_raw_parts(capabilities.base_ptr, capabilities.len) };
+                assert_eq!(capabilities.len(), 2);
+                assert_eq!((capabilities[0].code, capabilities[0].value_len), (2, 0));
+                assert!(capabilities[0].value.is_null());
+                assert_eq!((capabilities[1].code, capabilities[1].value_len), (65, 4));
+                assert_eq!(
+                    unsafe { slice::from_raw_parts(capabilities[1].value, 4) },
+                    &[0, 0, 0xfd, 0xe8]
+                );
+            }
+            data => panic!("unexpected data {data:?}"),
+        }
+        netgauze_bgp_notification_details_free(details);
+
+        // Capability longer than the data
+        let (_notification, details) = make_details(2, 7, &[65, 4, 0]);
+        assert!(matches!(details.data, BgpNotificationData::Raw { .. }));
+        netgauze_bgp_notification_details_free(details);
+    }
+
+    #[test]
+    fn test_notification_details_attributes() {
+        // Invalid ORIGIN, attribute truncated by the router
+        let (_notification, details) = make_details(3, 6, &[0x40, 1, 1]);
+        assert!(matches!(
+            details.data,
+            BgpNotificationData::MalformedAttribute {
+                attr_flags: 0x40,
+                attr_type: 1,
+                attr_value_len: 0,
+                ..
+            }
+        ));
+        netgauze_bgp_notification_details_free(details);
+
+        // Extended length attribute
+        let (_notification, details) = make_details(3, 9, &[0xd0, 32, 0, 2, 0xaa, 0xbb]);
+        match &details.data {
+            BgpNotificationData::MalformedAttribute {
+                attr_type: 32,
+                attr_value_len: 2,
+                attr_value,
+                ..
+            } => assert_eq!(
+                unsafe { slice::from_raw_parts(*attr_value, 2) },
+                &[0xaa, 0xbb]
+            ),
+            data => panic!("unexpected data {data:?}"),
+        }
+        netgauze_bgp_notification_details_free(details);
+
+        let (_notification, details) = make_details(3, 3, &[5]);
+        assert!(matches!(
+            details.data,
+            BgpNotificationData::MissingAttribute { attr_type: 5 }
+        ));
+        netgauze_bgp_notification_details_free(details);
+
+        let (_notification, details) = make_details(4, 0, &[]);
+        assert!(matches!(details.data, BgpNotificationData::Empty));
+        netgauze_bgp_notification_details_free(details);
+    }
+
+    #[test]
+    fn test_write_shutdown_notification() {
+        let mut buf = [0u8; 64];
+        let written = unsafe {
+            netgauze_bgp_write_shutdown_notification(
+                2,
+                c_str!("maintenance").as_ptr(),
+                buf.as_mut_ptr() as *mut c_char,
+                buf.len(),
+            )
+        };
+
+        // Header (19), code, subcode, length-prefixed message
+        let expected_len = 19 + 2 + 1 + 11;
+        assert!(matches!(written, CResult::Ok(len) if len == expected_len));
+        assert_eq!(&buf[..16], &[0xff; 16]);
+        assert_eq!(&buf[16..19], &[0, expected_len as u8, 3]);
+        assert_eq!(&buf[19..22], &[6, 2, 11]);
+        assert_eq!(&buf[22..expected_len], b"maintenance");
+    }
+
+    #[test]
+    fn test_write_notification_errors() {
+        let mut buf = [0u8; 64];
+        let buf_ptr = buf.as_mut_ptr() as *mut c_char;
+
+        let result = unsafe {
+            netgauze_bgp_write_shutdown_notification(3, std::ptr::null(), buf_ptr, buf.len())
+        };
+        assert!(matches!(
+            result,
+            CResult::Err(BgpMessageWriteError::NotAShutdownCommunicationSubCode(3))
+        ));
+
+        let result =
+            unsafe { netgauze_bgp_write_notification(0, 0, std::ptr::null(), 0, buf_ptr, 64) };
+        assert!(matches!(
+            result,
+            CResult::Err(BgpMessageWriteError::UndefinedNotificationCode(0))
+        ));
+
+        let result =
+            unsafe { netgauze_bgp_write_notification(6, 2, std::ptr::null(), 0, buf_ptr, 20) };
+        assert!(matches!(
+            result,
+            CResult::Err(BgpMessageWriteError::BufferTooSmall { needed: 21 })
+        ));
+    }
+}
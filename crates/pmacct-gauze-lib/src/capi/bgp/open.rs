@@ -36,12 +36,12 @@ pub type BgpOpenProcessResult = CResult<BgpOpenInfo, BgpOpenProcessError>;
 #[repr(C)]
 #[derive(Debug, Clone)]
 pub struct BgpOpenInfo {
-    version: u8,
+    pub(crate) version: u8,
     asn: u16,
-    hold_time: u16,
+    pub(crate) hold_time: u16,
     bgp_id: host_addr,
     capability_mp_protocol: cap_per_af,
-    capability_as4: cap_4as,
+    pub(crate) capability_as4: cap_4as,
     capability_add_paths: cap_per_af,
     capability_route_refresh: bool,
     capability_ext_nh_enc_data: cap_per_af_u16,
@@ -252,16 +252,52 @@ pub unsafe extern "C" fn netgauze_bgp_open_write_reply(
         }
     };
 
+    let bgp_open = match build_open_reply(
+        bgp_peer.myas,
+        bgp_peer.cap_4as.used,
+        bgp_peer.ht,
+        Ipv4Addr::from(&my_bgp_id),
+        open_rx,
+    ) {
+        Ok(bgp_open) => bgp_open,
+        Err(err) => return CResult::Err(err),
+    };
+
+    // Write to buffer
+    let mut cursor = Cursor::new(buf);
+    let write_result = {
+        let mut writer = BufWriter::new(&mut cursor);
+        bgp_open.write(&mut writer)
+    };
+
+    match write_result {
+        Ok(_) => CResult::Ok(cursor.position() as usize),
+        Err(err) => CResult::Err(BgpOpenWriteError::NetgauzeWriteError {
+            err_str: CString::new(format!("{:?}", err)).unwrap().into_raw(),
+        }),
+    }
+}
+
+/// Build the reply BGP Open with the correct capabilities for a collector from a received BGP Open
+///
+/// `my_as` and `cap_4as_used` reflect the collector's peer state for the BGP session
+pub(crate) fn build_open_reply(
+    my_as: u32,
+    cap_4as_used: bool,
+    hold_time: u16,
+    my_bgp_id: Ipv4Addr,
+    open_rx: &BgpOpenMessage,
+) -> Result<BgpMessage, BgpOpenWriteError> {
     // Find the ASN and the AS4 if we have one
-    let (my_as, as4_cap) = if bgp_peer.myas > u16::MAX as u32 {
-        if !bgp_peer.cap_4as.used {
-            return CResult::Err(BgpOpenWriteError::MyAsnTooHighForRemotePeer);
+    let (my_as, as4_cap) = if my_as > u16::MAX as u32 {
+        if !cap_4as_used {
+            return Err(BgpOpenWriteError::MyAsnTooHighForRemotePeer);
         }
         (BGP_AS_TRANS as u16, Some(open_rx.my_asn4()))
     } else {
         (
-            bgp_peer.myas as u16,
-            if bgp_peer.cap_4as.used {
+            my_as as u16,
+            if cap_4as_used {
                 Some(open_rx.my_asn4())
             } else {
                 None
@@ -298,7 +334,7 @@ pub unsafe extern "C" fn netgauze_bgp_open_write_reply(
                                     as4_value,
                                 ))
                             } else {
-                                return CResult::Err(
+                                return Err(
                                     BgpOpenWriteError::Asn4CapabilityFoundInOpenRxButNotInPeer,
                                 );
                             }
@@ -310,23 +346,7 @@ pub unsafe extern "C" fn netgauze_bgp_open_write_reply(
         }
     }
 
-    // Write to buffer
-    let bgp_open = BgpMessage::Open(BgpOpenMessage::new(
-        my_as,
-        bgp_peer.ht,
-        Ipv4Addr::from(&my_bgp_id),
-        tx_params,
-    ));
-    let mut cursor = Cursor::new(buf);
-    let write_result = {
-        let mut writer = BufWriter::new(&mut cursor);
-        bgp_open.write(&mut writer)
-    };
-
-    match write_result {
-        Ok(_) => CResult::Ok(cursor.position() as usize),
-        Err(err) => CResult::Err(BgpOpenWriteError::NetgauzeWriteError {
-            err_str: CString::new(format!("{:?}", err)).unwrap().into_raw(),
-        }),
-    }
+    Ok(BgpMessage::Open(BgpOpenMessage::new(
+        my_as, hold_time, my_bgp_id, tx_params,
+    )))
 }
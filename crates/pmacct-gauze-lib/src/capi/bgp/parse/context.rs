@@ -1,18 +1,115 @@
 use std::ptr::null_mut;
 
 use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+use netgauze_bgp_pkt::BgpMessage;
 
-use pmacct_gauze_bindings::bgp_peer;
+use pmacct_gauze_bindings::utils::cap_per_af::PerAddressTypeCapability;
+use pmacct_gauze_bindings::{bgp_peer, cap_per_af};
 
+use crate::capi::bgp::WrongBgpMessageTypeError;
 use crate::context_cache::ContextCache;
+use crate::cresult::CResult;
+use crate::extensions::context::bgp_parsing_context_from_opens;
+use crate::log::{pmacct_log, LogPriority};
 use crate::opaque::Opaque;
-use crate::{free_rust_raw_box, make_default};
+use crate::{free_rust_raw_box, make_default, make_rust_raw_box_pointer};
 
 pub type BgpContextCacheKey = *mut bgp_peer;
 pub type BgpContextCache = ContextCache<BgpContextCacheKey, BgpParsingContext>;
 free_rust_raw_box!(Opaque<BgpParsingContext>, Opaque_BgpParsingContext);
 make_default!(Opaque<BgpParsingContext>, Opaque_BgpParsingContext);
 
+/// Make a [BgpParsingContext] with the capabilities negotiated by an OPEN exchange,
+/// where `open_tx` was sent to the peer and `open_rx` was received from the peer.
+///
+/// The returned context must be freed using [netgauze_free_Opaque_BgpParsingContext]
+/// or given to [netgauze_bgp_context_cache_set]
+///
+/// # Safety
+/// `open_tx` should be not null and point to valid data
+/// `open_rx` should be not null and point to valid data
+///
+/// This function does not consume the `open_tx` and `open_rx` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_parsing_context_from_opens(
+    open_tx: *const Opaque<BgpMessage>,
+    open_rx: *const Opaque<BgpMessage>,
+) -> CResult<*mut Opaque<BgpParsingContext>, WrongBgpMessageTypeError> {
+    let open_tx = unsafe { open_tx.as_ref().unwrap().as_ref() };
+    let open_rx = unsafe { open_rx.as_ref().unwrap().as_ref() };
+
+    let (open_tx, open_rx) = match (open_tx, open_rx) {
+        (BgpMessage::Open(open_tx), BgpMessage::Open(open_rx)) => (open_tx, open_rx),
+        (BgpMessage::Open(_), other) | (other, _) => {
+            return WrongBgpMessageTypeError(other.get_type().into()).into()
+        }
+    };
+
+    CResult::Ok(make_rust_raw_box_pointer(Opaque::from(
+        bgp_parsing_context_from_opens(open_tx, open_rx),
+    )))
+}
+
+/// Capabilities used by a [BgpParsingContext]
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BgpParsingContextCapabilities {
+    pub asn4: bool,
+    /// Set to 1 for each AFI/SAFI where Add-Path is used
+    pub add_path: cap_per_af,
+    /// Number of labels for each AFI/SAFI where Multiple Labels is used
+    pub multiple_labels: cap_per_af,
+}
+
+impl From<&BgpParsingContext> for BgpParsingContextCapabilities {
+    fn from(bgp_parsing_context: &BgpParsingContext) -> Self {
+        let (add_path, add_path_errs) = cap_per_af::from_iter(
+            bgp_parsing_context
+                .add_path()
+                .iter()
+                .filter(|(_, enabled)| **enabled)
+                .map(|(address_type, _)| (*address_type, u8::from(true))),
+        );
+        let (multiple_labels, multiple_labels_errs) = cap_per_af::from_iter(
+            bgp_parsing_context
+                .multiple_labels()
+                .iter()
+                .map(|(address_type, count)| (*address_type, *count)),
+        );
+
+        for err in add_path_errs.iter().chain(multiple_labels_errs.iter()) {
+            pmacct_log(
+                LogPriority::Warning,
+                &format!(
+                    "[pmacct-gauze] Address Family {:?} is not supported in pmacct!\n",
+                    err.0
+                ),
+            );
+        }
+
+        Self {
+            asn4: bgp_parsing_context.asn4(),
+            add_path,
+            multiple_labels,
+        }
+    }
+}
+
+/// Get the capabilities used by a [BgpParsingContext], e.g. to log what was negotiated
+///
+/// # Safety
+/// `bgp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_parsing_context` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_parsing_context_get_capabilities(
+    bgp_parsing_context: *const Opaque<BgpParsingContext>,
+) -> BgpParsingContextCapabilities {
+    let bgp_parsing_context = unsafe { bgp_parsing_context.as_ref().unwrap().as_ref() };
+
+    BgpParsingContextCapabilities::from(bgp_parsing_context)
+}
+
 free_rust_raw_box!(Opaque<BgpContextCache>, Opaque_BgpContextCache);
 make_default!(Opaque<BgpContextCache>, Opaque_BgpContextCache);
 
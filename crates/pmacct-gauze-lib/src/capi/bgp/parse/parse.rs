@@ -15,16 +15,38 @@ use pmacct_gauze_bindings::{
     bgp_header, BGP_NOTIFY_HEADER_ERR, BGP_NOTIFY_OPEN_ERR, BGP_NOTIFY_UPDATE_ERR, ERR, SUCCESS,
 };
 
+use crate::capi::parse_error::{
+    find_update_attribute_type, missing_bytes, needed_bytes, update_error_subcode,
+    ParseErrorDetails, ParseErrorKind,
+};
+use crate::coption::COption;
 use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
 use crate::opaque::Opaque;
-use crate::{drop_rust_raw_box, make_rust_raw_box_pointer};
+use crate::{drop_rust_raw_box, free_cslice_t_with_item_free, make_rust_raw_box_pointer};
+
+/// Length of the BGP message header (marker, length, type)
+const BGP_HEADER_LENGTH: usize = 19;
+
+/// Maximum length of a BGP message (RFC 4271)
+pub const BGP_MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Maximum length of a BGP message when Extended Messages are negotiated (RFC 8654)
+pub const BGP_MAX_EXTENDED_MESSAGE_LENGTH: usize = 65535;
 
 #[repr(C)]
 #[derive(Debug)]
 pub enum BgpParseError {
     NetgauzeBgpError {
         pmacct_error_code: i32,
+        /// Human-readable error for logs
         err_str: *mut c_char,
+        details: ParseErrorDetails,
+    },
+    /// The buffer does not contain a whole message yet.
+    /// At least `needed` more bytes must be read before retrying, 0 if unknown.
+    NeedMoreData {
+        needed: u32,
     },
     StringConversionError,
 }
@@ -65,9 +87,63 @@ pub unsafe extern "C" fn netgauze_bgp_parse_packet_with_context(
     buffer_length: u32,
     bgp_parsing_context: *mut Opaque<BgpParsingContext>,
 ) -> BgpParseResult {
+    netgauze_bgp_parse_packet_with_options(
+        buffer,
+        buffer_length,
+        bgp_parsing_context,
+        BgpParseOptions::default(),
+    )
+}
+
+/// Options changing how [netgauze_bgp_parse_packet_with_options] parses the messages of a session
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct BgpParseOptions {
+    /// Extended Messages (RFC 8654) were negotiated with the peer:
+    /// messages up to [BGP_MAX_EXTENDED_MESSAGE_LENGTH] bytes are accepted
+    /// instead of [BGP_MAX_MESSAGE_LENGTH]
+    pub extended_message: bool,
+}
+
+impl BgpParseOptions {
+    /// Maximum length of a BGP message with these options
+    pub fn max_message_length(&self) -> usize {
+        if self.extended_message {
+            BGP_MAX_EXTENDED_MESSAGE_LENGTH
+        } else {
+            BGP_MAX_MESSAGE_LENGTH
+        }
+    }
+}
+
+/// Parse a buffer with given length into a BGP Message with a given context and [BgpParseOptions]
+///
+/// A header announcing more than [BgpParseOptions::max_message_length] bytes is a Bad Message Length
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data
+/// `bgp_parsing_context` should be not null and point to valid data
+///
+/// `bgp_parsing_context` is not consumed
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_parse_packet_with_options(
+    buffer: *const c_char,
+    buffer_length: u32,
+    bgp_parsing_context: *mut Opaque<BgpParsingContext>,
+    options: BgpParseOptions,
+) -> BgpParseResult {
+    let max_length = options.max_message_length();
     let bgp_parsing_context = unsafe { bgp_parsing_context.as_mut().unwrap().as_mut() };
 
     let slice = unsafe { slice::from_raw_parts(buffer as *const u8, buffer_length as usize) };
+    match missing_bytes(slice, BGP_HEADER_LENGTH, max_length, |header| {
+        u16::from_be_bytes([header[16], header[17]]) as usize
+    }) {
+        Ok(None) => {}
+        Ok(Some(needed)) => return BgpParseError::NeedMoreData { needed }.into(),
+        Err(length) => return message_too_long(length, max_length, slice.get(18).copied()),
+    }
+
     let span = Span::new(slice);
     let result = BgpMessage::from_wire(span, bgp_parsing_context);
     if let Ok((end_span, msg)) = result {
@@ -87,27 +163,63 @@ pub unsafe extern "C" fn netgauze_bgp_parse_packet_with_context(
     }
 
     let err = result.err().unwrap();
-    // TODO special EoF error
 
-    let err_code = {
-        let err_map = |err: &BgpMessageParsingError| {
+    let (err_code, details) = {
+        let message_type = slice.get(18).copied();
+        let err_map = |err: &BgpMessageParsingError, offset: usize| {
+            let details = |kind| ParseErrorDetails::new(kind, offset, message_type);
             match err {
-                // NomError is probably just an EoF. Don't panic.
-                BgpMessageParsingError::NomError(_)
+                // The whole message is available, so NomError is a field truncated within it.
+                // pmacct ignores those. Don't panic.
+                BgpMessageParsingError::NomError(_) => {
+                    (SUCCESS as i32, details(message_kind(message_type)))
+                }
                 // Route Refresh is Ignored in pmacct. Ignore errors on them.
-                | BgpMessageParsingError::BgpRouteRefreshMessageParsingError(_) => SUCCESS as i32,
-                BgpMessageParsingError::ConnectionNotSynchronized(_)
-                | BgpMessageParsingError::UndefinedBgpMessageType(_)
-                | BgpMessageParsingError::BadMessageLength(_) => BGP_NOTIFY_HEADER_ERR as i32,
-                BgpMessageParsingError::BgpOpenMessageParsingError(_) => BGP_NOTIFY_OPEN_ERR as i32,
-                BgpMessageParsingError::BgpUpdateMessageParsingError(_) => BGP_NOTIFY_UPDATE_ERR as i32,
-                BgpMessageParsingError::BgpNotificationMessageParsingError(_) => ERR,
+                BgpMessageParsingError::BgpRouteRefreshMessageParsingError(_) => (
+                    SUCCESS as i32,
+                    details(ParseErrorKind::BgpRouteRefreshMessage),
+                ),
+                BgpMessageParsingError::ConnectionNotSynchronized(_) => (
+                    BGP_NOTIFY_HEADER_ERR as i32,
+                    details(ParseErrorKind::BgpMessageHeader).with_notification(1, 1),
+                ),
+                BgpMessageParsingError::BadMessageLength(_) => (
+                    BGP_NOTIFY_HEADER_ERR as i32,
+                    details(ParseErrorKind::BgpMessageHeader).with_notification(1, 2),
+                ),
+                BgpMessageParsingError::UndefinedBgpMessageType(_) => (
+                    BGP_NOTIFY_HEADER_ERR as i32,
+                    details(ParseErrorKind::BgpMessageHeader).with_notification(1, 3),
+                ),
+                BgpMessageParsingError::BgpOpenMessageParsingError(_) => (
+                    BGP_NOTIFY_OPEN_ERR as i32,
+                    details(ParseErrorKind::BgpOpenMessage).with_notification(2, 0),
+                ),
+                BgpMessageParsingError::BgpUpdateMessageParsingError(_) => {
+                    let attribute_type = find_update_attribute_type(slice, offset);
+                    (
+                        BGP_NOTIFY_UPDATE_ERR as i32,
+                        details(ParseErrorKind::BgpUpdateMessage)
+                            .with_attribute_type(attribute_type.map(u16::from))
+                            .with_notification(3, update_error_subcode(attribute_type)),
+                    )
+                }
+                BgpMessageParsingError::BgpNotificationMessageParsingError(_) => {
+                    (ERR, details(ParseErrorKind::BgpNotificationMessage))
+                }
             }
         };
 
         match &err {
-            Err::Incomplete(_) => 0,
-            Err::Error(err) | Err::Failure(err) => err_map(err.error()),
+            Err::Incomplete(needed) => {
+                return BgpParseError::NeedMoreData {
+                    needed: needed_bytes(needed),
+                }
+                .into()
+            }
+            Err::Error(err) | Err::Failure(err) => {
+                err_map(err.error(), err.span().location_offset())
+            }
         }
     };
 
@@ -119,15 +231,177 @@ pub unsafe extern "C" fn netgauze_bgp_parse_packet_with_context(
     BgpParseError::NetgauzeBgpError {
         pmacct_error_code: err_code,
         err_str: netgauze_error.into_raw(),
+        details,
     }
     .into()
 }
 
+/// Bad Message Length error for a header announcing more than `max_length` bytes
+fn message_too_long(length: usize, max_length: usize, message_type: Option<u8>) -> BgpParseResult {
+    let err_str = format!("BGP message length {length} exceeds the maximum of {max_length}");
+    let err_str = match CString::new(err_str) {
+        Ok(str) => str,
+        Err(_) => return BgpParseError::StringConversionError.into(),
+    };
+
+    BgpParseError::NetgauzeBgpError {
+        pmacct_error_code: BGP_NOTIFY_HEADER_ERR as i32,
+        err_str: err_str.into_raw(),
+        details: ParseErrorDetails::new(ParseErrorKind::BgpMessageHeader, 16, message_type)
+            .with_notification(1, 2),
+    }
+    .into()
+}
+
+/// [ParseErrorKind] of a BGP message of type `message_type`
+fn message_kind(message_type: Option<u8>) -> ParseErrorKind {
+    match message_type {
+        Some(1) => ParseErrorKind::BgpOpenMessage,
+        Some(2) => ParseErrorKind::BgpUpdateMessage,
+        Some(3) => ParseErrorKind::BgpNotificationMessage,
+        Some(5) => ParseErrorKind::BgpRouteRefreshMessage,
+        _ => ParseErrorKind::BgpMessageHeader,
+    }
+}
+
+/// All the whole BGP messages parsed from a buffer.
+///
+/// This structure must be freed using [netgauze_bgp_parsed_buffer_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BgpParsedBuffer {
+    pub messages: OwnedSlice<ParsedBgp>,
+    /// Number of bytes at the end of the buffer that were not consumed.
+    /// They must be kept and completed with the next read.
+    pub leftover_bytes: u32,
+    /// Error that stopped the parsing. The leftover bytes start with the erroneous message.
+    pub error: COption<BgpParseError>,
+}
+
+impl RustFree for ParsedBgp {
+    fn rust_free(self) {
+        drop_rust_raw_box(self.message)
+    }
+}
+
+free_cslice_t_with_item_free!(ParsedBgp);
+
+/// Parse all the whole BGP Messages contained in a buffer with a given context.
+///
+/// Messages that pmacct ignores when they are malformed (e.g. ROUTE-REFRESH) are skipped.
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buffer_length`
+/// `bgp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `buffer` and `bgp_parsing_context` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_parse_buffer(
+    buffer: *const c_char,
+    buffer_length: u32,
+    bgp_parsing_context: *mut Opaque<BgpParsingContext>,
+) -> BgpParsedBuffer {
+    netgauze_bgp_parse_buffer_with_options(
+        buffer,
+        buffer_length,
+        bgp_parsing_context,
+        BgpParseOptions::default(),
+    )
+}
+
+/// Parse all the whole BGP Messages contained in a buffer with a given context and [BgpParseOptions].
+///
+/// See [netgauze_bgp_parse_buffer]
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buffer_length`
+/// `bgp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `buffer` and `bgp_parsing_context` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_parse_buffer_with_options(
+    buffer: *const c_char,
+    buffer_length: u32,
+    bgp_parsing_context: *mut Opaque<BgpParsingContext>,
+    options: BgpParseOptions,
+) -> BgpParsedBuffer {
+    let mut messages = Vec::new();
+    let mut offset = 0u32;
+
+    let error = loop {
+        if offset == buffer_length {
+            break None;
+        }
+
+        let remaining = buffer.add(offset as usize);
+        let remaining_length = buffer_length - offset;
+        let result = netgauze_bgp_parse_packet_with_options(
+            remaining,
+            remaining_length,
+            bgp_parsing_context,
+            options.clone(),
+        );
+
+        match result {
+            CResult::Ok(parsed) => {
+                if parsed.read_bytes == 0 {
+                    parsed.rust_free();
+                    break None;
+                }
+
+                offset += parsed.read_bytes;
+                messages.push(parsed);
+            }
+            CResult::Err(BgpParseError::NeedMoreData { .. }) => break None,
+            CResult::Err(err) => {
+                let ignored = matches!(err, BgpParseError::NetgauzeBgpError { pmacct_error_code, .. } if pmacct_error_code == SUCCESS as i32);
+
+                // The whole message is in the buffer, otherwise NeedMoreData would have been returned
+                let remaining =
+                    slice::from_raw_parts(remaining as *const u8, remaining_length as usize);
+                let message_length = u16::from_be_bytes([remaining[16], remaining[17]]) as u32;
+
+                if !ignored || message_length < BGP_HEADER_LENGTH as u32 {
+                    break Some(err);
+                }
+
+                netgauze_bgp_parse_result_free(CResult::Err(err));
+                offset += message_length;
+            }
+        }
+    };
+
+    BgpParsedBuffer {
+        messages: OwnedSlice::from_vec(messages),
+        leftover_bytes: buffer_length - offset,
+        error: error.into(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_parsed_buffer_free(value: BgpParsedBuffer) {
+    value.messages.rust_free();
+    if let COption::Some(err) = value.error {
+        netgauze_bgp_parse_result_free(CResult::Err(err));
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn netgauze_bgp_parse_error_str(error: BgpParseError) -> *const c_char {
     error.as_str_ptr()
 }
 
+/// Get the [ParseErrorDetails] of a [BgpParseError], if any
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_parse_error_details(
+    error: BgpParseError,
+) -> COption<ParseErrorDetails> {
+    match error {
+        BgpParseError::NetgauzeBgpError { details, .. } => COption::Some(details),
+        BgpParseError::NeedMoreData { .. } | BgpParseError::StringConversionError => COption::None,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn netgauze_bgp_parse_result_free(value: BgpParseResult) {
     match value {
@@ -138,7 +412,7 @@ pub extern "C" fn netgauze_bgp_parse_result_free(value: BgpParseResult) {
             BgpParseError::NetgauzeBgpError { err_str, .. } => unsafe {
                 drop(CString::from_raw(err_str));
             },
-            BgpParseError::StringConversionError => {}
+            BgpParseError::NeedMoreData { .. } | BgpParseError::StringConversionError => {}
         },
     };
 }
@@ -155,6 +429,10 @@ impl BgpParseError {
     fn as_str_ptr(&self) -> *const c_char {
         match self {
             BgpParseError::NetgauzeBgpError { err_str, .. } => *err_str as *const c_char,
+            BgpParseError::NeedMoreData { .. } => c_str! {
+                "BgpParseError::NeedMoreData"
+            }
+            .as_ptr(),
             BgpParseError::StringConversionError => c_str! {
                 "BgpParseError::StringConversionError"
             }
@@ -168,3 +446,83 @@ impl<T> From<BgpParseError> for CResult<T, BgpParseError> {
         Self::Err(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+
+    use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+    use pmacct_gauze_bindings::BGP_NOTIFY_HEADER_ERR;
+
+    use crate::capi::bgp::parse::{
+        netgauze_bgp_parse_packet_with_options, netgauze_bgp_parse_result_free, BgpParseError,
+        BgpParseOptions, BgpParseResult, BGP_MAX_MESSAGE_LENGTH,
+    };
+    use crate::capi::parse_error::ParseErrorKind;
+    use crate::cresult::CResult;
+    use crate::opaque::Opaque;
+
+    /// Header of an UPDATE announcing `length` bytes, without its body
+    fn update_header(length: u16) -> Vec<u8> {
+        let mut header = vec![0xff; 16];
+        header.extend_from_slice(&length.to_be_bytes());
+        header.push(2);
+        header
+    }
+
+    fn parse(buffer: &[u8], extended_message: bool) -> BgpParseResult {
+        unsafe {
+            netgauze_bgp_parse_packet_with_options(
+                buffer.as_ptr() as *const c_char,
+                buffer.len() as u32,
+                &mut Opaque::from(BgpParsingContext::default()),
+                BgpParseOptions { extended_message },
+            )
+        }
+    }
+
+    #[test]
+    fn test_parse_message_too_long() {
+        let header = update_header(BGP_MAX_MESSAGE_LENGTH as u16 + 1);
+        let result = parse(&header, false);
+        match &result {
+            CResult::Err(BgpParseError::NetgauzeBgpError {
+                pmacct_error_code,
+                details,
+                ..
+            }) => {
+                assert_eq!(*pmacct_error_code, BGP_NOTIFY_HEADER_ERR as i32);
+                assert_eq!(details.kind, ParseErrorKind::BgpMessageHeader);
+                assert_eq!(details.offset, 16);
+                assert_eq!(
+                    (details.notification_code, details.notification_subcode),
+                    (1, 2)
+                );
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+        netgauze_bgp_parse_result_free(result);
+
+        // The longest message without Extended Messages is only incomplete
+        let header = update_header(BGP_MAX_MESSAGE_LENGTH as u16);
+        assert!(matches!(
+            parse(&header, false),
+            CResult::Err(BgpParseError::NeedMoreData { needed }) if needed as usize == BGP_MAX_MESSAGE_LENGTH - header.len()
+        ));
+    }
+
+    #[test]
+    fn test_parse_extended_message() {
+        let header = update_header(BGP_MAX_MESSAGE_LENGTH as u16 + 1);
+        assert!(matches!(
+            parse(&header, true),
+            CResult::Err(BgpParseError::NeedMoreData { needed }) if needed as usize == BGP_MAX_MESSAGE_LENGTH + 1 - header.len()
+        ));
+
+        let header = update_header(u16::MAX);
+        assert!(matches!(
+            parse(&header, true),
+            CResult::Err(BgpParseError::NeedMoreData { needed }) if needed as usize == u16::MAX as usize - header.len()
+        ));
+    }
+}
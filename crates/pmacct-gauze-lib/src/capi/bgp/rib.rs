@@ -0,0 +1,184 @@
+use std::net::IpAddr;
+
+use netgauze_bgp_pkt::BgpMessage;
+
+use pmacct_gauze_bindings::{afi_t, bgp_peer, prefix, rd_t, safi_t};
+
+use crate::capi::bgp::update::ParsedBgpUpdate;
+use crate::capi::bgp::WrongBgpMessageTypeError;
+use crate::capi::rib::{
+    make_prefix_key, make_rib_entries, make_withdraw_packets, Rib, RibEntry, RibUpdateSummary,
+};
+use crate::cresult::CResult;
+use crate::cslice::OwnedSlice;
+use crate::extensions::rd::RdOriginType;
+use crate::opaque::Opaque;
+use crate::{free_rust_raw_box, make_default};
+
+/// Identity of a peer in a [BgpRib].
+///
+/// pmacct reuses the [bgp_peer] of a closed session for the next ones,
+/// so the routes are kept by peer address, BGP Identifier and AS instead.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct BgpRibPeerKey {
+    pub peer_address: Option<IpAddr>,
+    /// None until the OPEN message of the peer is received
+    pub bgp_id: Option<IpAddr>,
+    pub asn: u32,
+}
+
+impl BgpRibPeerKey {
+    /// # Safety
+    /// `peer` should be not null and point to valid data
+    pub unsafe fn from_peer(peer: *const bgp_peer) -> Self {
+        let peer = unsafe { peer.as_ref().unwrap() };
+
+        Self {
+            peer_address: IpAddr::try_from(&peer.addr).ok(),
+            bgp_id: IpAddr::try_from(&peer.id).ok(),
+            asn: peer.as_,
+        }
+    }
+}
+
+/// Routes received on the BGP sessions of the peers
+pub type BgpRib = Rib<BgpRibPeerKey>;
+
+free_rust_raw_box!(Opaque<BgpRib>, Opaque_BgpRib);
+make_default!(Opaque<BgpRib>, Opaque_BgpRib);
+
+pub type BgpRibUpdateResult = CResult<RibUpdateSummary, WrongBgpMessageTypeError>;
+
+/// Apply a BGP UPDATE received from `peer` to the [BgpRib]
+///
+/// # Safety
+/// `bgp_rib` should be not null and point to valid data
+/// `peer` should be not null and point to valid data
+/// `bgp_msg` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_rib`, `peer` and `bgp_msg` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_rib_update(
+    bgp_rib: *mut Opaque<BgpRib>,
+    peer: *mut bgp_peer,
+    bgp_msg: *const Opaque<BgpMessage>,
+) -> BgpRibUpdateResult {
+    let bgp_rib = unsafe { bgp_rib.as_mut().unwrap().as_mut() };
+    let bgp_msg = unsafe { bgp_msg.as_ref().unwrap().as_ref() };
+
+    let update = match bgp_msg {
+        BgpMessage::Update(update) => update,
+        _ => return WrongBgpMessageTypeError(bgp_msg.get_type().into()).into(),
+    };
+
+    let peer_key = unsafe { BgpRibPeerKey::from_peer(peer) };
+    CResult::Ok(bgp_rib.apply_update(peer_key, None, update))
+}
+
+/// Get all the routes of `peer` in the [BgpRib]
+///
+/// The returned [OwnedSlice<RibEntry>] must be freed with [crate::capi::rib::CSlice_free_RibEntry].
+/// It borrows the attributes of the routes from the [BgpRib] and must be freed before
+/// the [BgpRib] is modified.
+///
+/// # Safety
+/// `bgp_rib` should be not null and point to valid data
+/// `peer` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_rib` and `peer` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_rib_dump(
+    bgp_rib: *const Opaque<BgpRib>,
+    peer: *mut bgp_peer,
+) -> OwnedSlice<RibEntry> {
+    let bgp_rib = unsafe { bgp_rib.as_ref().unwrap().as_ref() };
+
+    let peer_key = unsafe { BgpRibPeerKey::from_peer(peer) };
+    let result = bgp_rib
+        .get_peer(&peer_key)
+        .map(|rib_peer| make_rib_entries(rib_peer, RdOriginType::BGP))
+        .unwrap_or_default();
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Get the routes of `peer` in the [BgpRib] to a prefix.
+/// `rd` may be null for routes without a Route Distinguisher.
+///
+/// The returned [OwnedSlice<RibEntry>] must be freed with [crate::capi::rib::CSlice_free_RibEntry].
+/// It borrows the attributes of the routes from the [BgpRib] and must be freed before
+/// the [BgpRib] is modified.
+///
+/// # Safety
+/// `bgp_rib` should be not null and point to valid data
+/// `peer` should be not null and point to valid data
+/// `prefix` should be not null and point to valid data
+/// `rd` should be null or point to valid data
+///
+/// This function does not consume the `bgp_rib`, `peer`, `prefix` and `rd` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_rib_query(
+    bgp_rib: *const Opaque<BgpRib>,
+    peer: *mut bgp_peer,
+    afi: afi_t,
+    safi: safi_t,
+    prefix: *const prefix,
+    rd: *const rd_t,
+) -> OwnedSlice<RibEntry> {
+    let bgp_rib = unsafe { bgp_rib.as_ref().unwrap().as_ref() };
+
+    let prefix_key = match unsafe { make_prefix_key(afi, safi, prefix, rd) } {
+        Some(prefix_key) => prefix_key,
+        None => return OwnedSlice::from_vec(Vec::new()),
+    };
+
+    let peer_key = unsafe { BgpRibPeerKey::from_peer(peer) };
+    let result = bgp_rib
+        .get_peer(&peer_key)
+        .map(|rib_peer| {
+            rib_peer
+                .paths(&prefix_key)
+                .map(|(path_key, route)| {
+                    RibEntry::new(&prefix_key, path_key, route, RdOriginType::BGP)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Remove `peer` from the [BgpRib] when its BGP session closes, and get a withdrawal
+/// for each of its routes so that they can be processed like the withdrawals of an UPDATE
+///
+/// The returned [ParsedBgpUpdate] has no updates, its packets must be freed
+/// with [crate::capi::bgp::update::CSlice_free_ProcessPacket]
+///
+/// # Safety
+/// `bgp_rib` should be not null and point to valid data
+/// `peer` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_rib` and `peer` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_rib_withdraw_peer(
+    bgp_rib: *mut Opaque<BgpRib>,
+    peer: *mut bgp_peer,
+) -> ParsedBgpUpdate {
+    let bgp_rib = unsafe { bgp_rib.as_mut().unwrap().as_mut() };
+
+    let peer_key = unsafe { BgpRibPeerKey::from_peer(peer) };
+    let packets = bgp_rib
+        .remove_peer(&peer_key)
+        .map(|rib_peer| {
+            make_withdraw_packets(&rib_peer, RdOriginType::BGP)
+                .into_iter()
+                .map(|(_, packet)| packet)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ParsedBgpUpdate {
+        packets: OwnedSlice::from_vec(packets),
+        update_count: 0,
+    }
+}
@@ -0,0 +1,719 @@
+use std::ffi::c_char;
+use std::mem;
+use std::net::Ipv4Addr;
+
+use netgauze_bgp_pkt::notification::{
+    BgpNotificationMessage, FiniteStateMachineError, HoldTimerExpiredError, MessageHeaderError,
+    OpenMessageError,
+};
+use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+use netgauze_bgp_pkt::BgpMessage;
+
+use pmacct_gauze_bindings::{in_addr, ERR, SUCCESS};
+
+use crate::capi::bgp::open::{
+    build_open_reply, netgauze_bgp_open_write_result_free, netgauze_bgp_process_open, BgpOpenInfo,
+    BgpOpenWriteError,
+};
+use crate::capi::bgp::parse::{
+    netgauze_bgp_parse_packet_with_options, netgauze_bgp_parse_result_free, BgpParseError,
+    BgpParseOptions,
+};
+use crate::capi::bgp::write::{
+    append_bgp_message, netgauze_bgp_message_write_result_free, BgpMessageWriteResult,
+};
+use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::extensions::bgp_notification::{
+    BgpNotificationBuildError, ExtendBgpNotificationMessage,
+};
+use crate::extensions::context::{bgp_parsing_context_from_opens, extended_message_from_opens};
+use crate::opaque::Opaque;
+use crate::{
+    drop_rust_raw_box, free_cslice_t_with_item_free, free_rust_raw_box, make_rust_raw_box_pointer,
+};
+
+/// Length of the BGP message header (marker, length, type)
+const BGP_HEADER_LENGTH: usize = 19;
+
+/// Capability code of the 4-octet AS number capability (RFC 6793)
+const FOUR_OCTET_AS_CAPABILITY_CODE: u8 = 65;
+
+/// Hold time used while waiting for the peer's OPEN (RFC 4271 section 8, "large value")
+const OPEN_HOLD_TIME: u64 = 240;
+
+/// State of a passive [BgpSession] (RFC 4271 section 8.2.2).
+///
+/// The collector never initiates the connection and only replies to the peer's OPEN,
+/// so the Idle, Connect and OpenSent states are never used.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BgpSessionState {
+    /// Waiting for the peer's OPEN
+    Active,
+    /// OPEN and KEEPALIVE sent, waiting for the peer's KEEPALIVE
+    OpenConfirm,
+    Established,
+    /// The session is over, the TCP connection should be closed once `tx` is sent
+    Closed,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BgpSessionConfig {
+    /// The collector ASN. If 0, the ASN of the peer is used like pmacct does by default
+    pub my_as: u32,
+    pub my_bgp_id: in_addr,
+    pub hold_time: u16,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub enum BgpSessionEvent {
+    /// The session reached the Established state. Contains the peer's OPEN information.
+    Established(BgpOpenInfo),
+    /// An UPDATE was received. The message is owned by the event.
+    Update(*mut Opaque<BgpMessage>),
+    /// The peer sent a NOTIFICATION and the session is closed. The message is owned by the event.
+    NotificationReceived(*mut Opaque<BgpMessage>),
+    /// The collector sent a NOTIFICATION and the session is closed.
+    NotificationSent { code: u8, subcode: u8 },
+    /// The hold timer expired, a NOTIFICATION was sent and the session is closed.
+    HoldTimerExpired,
+}
+
+impl RustFree for BgpSessionEvent {
+    fn rust_free(self) {
+        match self {
+            BgpSessionEvent::Update(message) | BgpSessionEvent::NotificationReceived(message) => {
+                drop_rust_raw_box(message)
+            }
+            BgpSessionEvent::Established(_)
+            | BgpSessionEvent::NotificationSent { .. }
+            | BgpSessionEvent::HoldTimerExpired => {}
+        }
+    }
+}
+
+free_cslice_t_with_item_free!(BgpSessionEvent);
+
+/// Result of feeding bytes or a timer tick to a [BgpSession].
+///
+/// `tx` contains the bytes that must be sent to the peer.
+/// This structure must be freed using [netgauze_bgp_session_output_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BgpSessionOutput {
+    pub state: BgpSessionState,
+    pub events: OwnedSlice<BgpSessionEvent>,
+    pub tx: OwnedSlice<u8>,
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_session_output_free(output: BgpSessionOutput) {
+    output.events.rust_free();
+    output.tx.rust_free();
+}
+
+/// Passive BGP session finite state machine for a collector.
+///
+/// The session does no I/O: it is fed with received bytes and timer ticks
+/// and accumulates the bytes to send and the events for the caller.
+/// Times are given by the caller in seconds.
+#[derive(Debug)]
+pub struct BgpSession {
+    config: BgpSessionConfig,
+    state: BgpSessionState,
+    parsing_context: BgpParsingContext,
+    parse_options: BgpParseOptions,
+    open_info: Option<BgpOpenInfo>,
+    hold_time: u16,
+    now: u64,
+    last_rx: u64,
+    last_tx: u64,
+    rx_buffer: Vec<u8>,
+    tx_buffer: Vec<u8>,
+    events: Vec<BgpSessionEvent>,
+}
+
+impl BgpSession {
+    pub fn new(config: BgpSessionConfig, now: u64) -> Self {
+        Self {
+            config,
+            state: BgpSessionState::Active,
+            parsing_context: BgpParsingContext::default(),
+            parse_options: BgpParseOptions::default(),
+            open_info: None,
+            hold_time: config.hold_time,
+            now,
+            last_rx: now,
+            last_tx: now,
+            rx_buffer: Vec::new(),
+            tx_buffer: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> BgpSessionState {
+        self.state
+    }
+
+    pub fn parsing_context_mut(&mut self) -> &mut BgpParsingContext {
+        &mut self.parsing_context
+    }
+
+    /// Feed bytes received from the peer. Incomplete messages are kept until the next call.
+    pub fn receive(&mut self, bytes: &[u8], now: u64) {
+        self.now = now;
+        if self.state == BgpSessionState::Closed {
+            return;
+        }
+
+        self.rx_buffer.extend_from_slice(bytes);
+
+        let mut offset = 0;
+        while self.state != BgpSessionState::Closed {
+            let pending = &self.rx_buffer[offset..];
+            if pending.len() < BGP_HEADER_LENGTH {
+                break;
+            }
+
+            let length_field = [pending[16], pending[17]];
+            let message_length = u16::from_be_bytes(length_field) as usize;
+            if message_length < BGP_HEADER_LENGTH
+                || message_length > self.parse_options.max_message_length()
+            {
+                self.close_with(BgpNotificationMessage::MessageHeaderError(
+                    MessageHeaderError::BadMessageLength {
+                        value: length_field.to_vec(),
+                    },
+                ));
+                break;
+            }
+
+            if pending.len() < message_length {
+                break;
+            }
+
+            let result = unsafe {
+                netgauze_bgp_parse_packet_with_options(
+                    pending.as_ptr() as *const c_char,
+                    message_length as u32,
+                    Opaque::mut_from_mut(&mut self.parsing_context),
+                    self.parse_options.clone(),
+                )
+            };
+
+            offset += message_length;
+            self.last_rx = now;
+
+            match result {
+                CResult::Ok(parsed) => {
+                    let message = unsafe { Box::from_raw(parsed.message) };
+                    self.process_message(Opaque::value(*message));
+                }
+                CResult::Err(err) => self.process_parse_error(err),
+            }
+        }
+
+        self.rx_buffer.drain(..offset);
+    }
+
+    /// Run the session timers: send KEEPALIVEs and enforce the hold time
+    pub fn tick(&mut self, now: u64) {
+        self.now = now;
+
+        let hold_time = match self.state {
+            BgpSessionState::Closed => return,
+            BgpSessionState::Active => OPEN_HOLD_TIME,
+            BgpSessionState::OpenConfirm | BgpSessionState::Established => self.hold_time as u64,
+        };
+
+        // A hold time of 0 means that no KEEPALIVE is exchanged
+        if hold_time == 0 {
+            return;
+        }
+
+        if now >= self.last_rx + hold_time {
+            self.events.push(BgpSessionEvent::HoldTimerExpired);
+            self.close_with(BgpNotificationMessage::HoldTimerExpiredError(
+                HoldTimerExpiredError::Unspecific {
+                    sub_code: 0,
+                    value: vec![],
+                },
+            ));
+            return;
+        }
+
+        if self.state != BgpSessionState::Active && now >= self.last_tx + hold_time / 3 {
+            self.send(&BgpMessage::KeepAlive);
+        }
+    }
+
+    /// Take the events and bytes to send accumulated since the last call
+    pub fn take_output(&mut self) -> BgpSessionOutput {
+        BgpSessionOutput {
+            state: self.state,
+            events: OwnedSlice::from_vec(mem::take(&mut self.events)),
+            tx: OwnedSlice::from_vec(mem::take(&mut self.tx_buffer)),
+        }
+    }
+
+    fn process_message(&mut self, message: BgpMessage) {
+        match (self.state, message) {
+            (BgpSessionState::Closed, _) => {}
+            (_, message @ BgpMessage::Notification(_)) => {
+                self.events.push(BgpSessionEvent::NotificationReceived(
+                    make_rust_raw_box_pointer(Opaque::from(message)),
+                ));
+                self.state = BgpSessionState::Closed;
+            }
+            (BgpSessionState::Active, message @ BgpMessage::Open(_)) => self.process_open(message),
+            (BgpSessionState::Active, _) => {
+                self.close_with(BgpNotificationMessage::FiniteStateMachineError(
+                    FiniteStateMachineError::Unspecific { value: vec![] },
+                ))
+            }
+            (BgpSessionState::OpenConfirm, BgpMessage::KeepAlive) => {
+                self.state = BgpSessionState::Established;
+                if let Some(open_info) = self.open_info.clone() {
+                    self.events.push(BgpSessionEvent::Established(open_info));
+                }
+            }
+            (BgpSessionState::OpenConfirm, _) => {
+                self.close_with(BgpNotificationMessage::FiniteStateMachineError(
+                    FiniteStateMachineError::ReceiveUnexpectedMessageInOpenConfirmState {
+                        value: vec![],
+                    },
+                ))
+            }
+            (BgpSessionState::Established, message @ BgpMessage::Update(_)) => {
+                self.events
+                    .push(BgpSessionEvent::Update(make_rust_raw_box_pointer(
+                        Opaque::from(message),
+                    )));
+            }
+            (BgpSessionState::Established, BgpMessage::KeepAlive)
+            | (BgpSessionState::Established, BgpMessage::RouteRefresh(_)) => {}
+            (BgpSessionState::Established, BgpMessage::Open(_)) => {
+                self.close_with(BgpNotificationMessage::FiniteStateMachineError(
+                    FiniteStateMachineError::ReceiveUnexpectedMessageInEstablishedState {
+                        value: vec![],
+                    },
+                ))
+            }
+        }
+    }
+
+    fn process_open(&mut self, message: BgpMessage) {
+        let open_info = unsafe { netgauze_bgp_process_open(Opaque::const_from_ref(&message)) };
+        let (open_info, open_rx) = match (open_info, &message) {
+            (CResult::Ok(open_info), BgpMessage::Open(open_rx)) => (open_info, open_rx),
+            _ => {
+                self.close_with(BgpNotificationMessage::OpenMessageError(
+                    OpenMessageError::Unspecific { value: vec![] },
+                ));
+                return;
+            }
+        };
+
+        if open_info.version != 4 {
+            self.close_with(BgpNotificationMessage::OpenMessageError(
+                OpenMessageError::UnsupportedVersionNumber {
+                    value: 4u16.to_be_bytes().to_vec(),
+                },
+            ));
+            return;
+        }
+
+        // RFC 4271 section 6.2: the hold time must be either zero or at least three seconds
+        if open_info.hold_time == 1 || open_info.hold_time == 2 {
+            self.close_with(BgpNotificationMessage::OpenMessageError(
+                OpenMessageError::UnacceptableHoldTime { value: vec![] },
+            ));
+            return;
+        }
+
+        let my_as = match self.config.my_as {
+            0 => open_rx.my_asn4(),
+            my_as => my_as,
+        };
+
+        let open_tx = match build_open_reply(
+            my_as,
+            open_info.capability_as4.used,
+            self.config.hold_time,
+            Ipv4Addr::from(&self.config.my_bgp_id),
+            open_rx,
+        ) {
+            Ok(open_tx) => open_tx,
+            Err(err) => {
+                let notification = open_reply_error_notification(&err, my_as);
+                netgauze_bgp_open_write_result_free(CResult::Err(err));
+                self.close_with(notification);
+                return;
+            }
+        };
+
+        if let BgpMessage::Open(open_tx) = &open_tx {
+            self.parsing_context = bgp_parsing_context_from_opens(open_tx, open_rx);
+            self.parse_options.extended_message = extended_message_from_opens(open_tx, open_rx);
+        }
+
+        self.send(&open_tx);
+        self.send(&BgpMessage::KeepAlive);
+
+        self.hold_time = self.config.hold_time.min(open_info.hold_time);
+        self.open_info = Some(open_info);
+        self.state = BgpSessionState::OpenConfirm;
+    }
+
+    fn process_parse_error(&mut self, err: BgpParseError) {
+        let notification = match &err {
+            BgpParseError::NetgauzeBgpError {
+                pmacct_error_code,
+                details,
+                ..
+            } => match *pmacct_error_code {
+                // Ignored by pmacct (e.g. malformed ROUTE-REFRESH)
+                code if code == SUCCESS as i32 => None,
+                // Malformed NOTIFICATION, the session is closed without answering
+                code if code == ERR => {
+                    self.state = BgpSessionState::Closed;
+                    None
+                }
+                _ => Some(
+                    BgpNotificationMessage::from_raw(
+                        details.notification_code,
+                        details.notification_subcode,
+                        vec![],
+                    )
+                    .unwrap_or_else(|_: BgpNotificationBuildError| {
+                        BgpNotificationMessage::MessageHeaderError(MessageHeaderError::Unspecific {
+                            value: vec![],
+                        })
+                    }),
+                ),
+            },
+            // Cannot happen as only whole messages are parsed
+            BgpParseError::NeedMoreData { .. } => None,
+            BgpParseError::StringConversionError => {
+                Some(BgpNotificationMessage::MessageHeaderError(
+                    MessageHeaderError::Unspecific { value: vec![] },
+                ))
+            }
+        };
+
+        netgauze_bgp_parse_result_free(CResult::Err(err));
+
+        if let Some(notification) = notification {
+            self.close_with(notification);
+        }
+    }
+
+    fn close_with(&mut self, notification: BgpNotificationMessage) {
+        self.events.push(BgpSessionEvent::NotificationSent {
+            code: notification.code() as u8,
+            subcode: notification.raw_subcode(),
+        });
+        self.send(&BgpMessage::Notification(notification));
+        self.state = BgpSessionState::Closed;
+    }
+
+    fn send(&mut self, message: &BgpMessage) {
+        if let Err(err) = append_bgp_message(message, &mut self.tx_buffer) {
+            netgauze_bgp_message_write_result_free(BgpMessageWriteResult::Err(err));
+            return;
+        }
+
+        self.last_tx = self.now;
+    }
+}
+
+/// NOTIFICATION sent to the peer when no OPEN reply can be built for it
+fn open_reply_error_notification(err: &BgpOpenWriteError, my_as: u32) -> BgpNotificationMessage {
+    match err {
+        // RFC 5492 section 5: the collector ASN needs the 4-octet AS number capability
+        // (RFC 6793) that the peer did not advertise
+        BgpOpenWriteError::MyAsnTooHighForRemotePeer => {
+            let mut value = vec![FOUR_OCTET_AS_CAPABILITY_CODE, 4];
+            value.extend_from_slice(&my_as.to_be_bytes());
+            BgpNotificationMessage::OpenMessageError(OpenMessageError::UnsupportedCapability {
+                value,
+            })
+        }
+        // The OPEN and its processed capabilities disagree, nothing points at a specific field
+        BgpOpenWriteError::Asn4CapabilityFoundInOpenRxButNotInPeer => {
+            BgpNotificationMessage::OpenMessageError(OpenMessageError::Unspecific { value: vec![] })
+        }
+        // Local errors, not caused by the peer's OPEN
+        BgpOpenWriteError::WrongBgpMessageTypeError(_)
+        | BgpOpenWriteError::NetgauzeWriteError { .. } => {
+            BgpNotificationMessage::FiniteStateMachineError(FiniteStateMachineError::Unspecific {
+                value: vec![],
+            })
+        }
+    }
+}
+
+free_rust_raw_box!(Opaque<BgpSession>, Opaque_BgpSession);
+
+/// Create a new passive [BgpSession] for a freshly accepted peer connection
+///
+/// The session must be freed using [netgauze_free_Opaque_BgpSession]
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_session_new(
+    config: BgpSessionConfig,
+    now: u64,
+) -> *mut Opaque<BgpSession> {
+    make_rust_raw_box_pointer(Opaque::from(BgpSession::new(config, now)))
+}
+
+/// Feed bytes received from the peer to a [BgpSession]
+///
+/// # Safety
+/// `bgp_session` should be not null and point to valid data
+/// `buffer` should be not null and point to valid data of length `buffer_length`
+///
+/// This function does not consume the `bgp_session` and `buffer` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_session_receive(
+    bgp_session: *mut Opaque<BgpSession>,
+    buffer: *const c_char,
+    buffer_length: u32,
+    now: u64,
+) -> BgpSessionOutput {
+    let bgp_session = unsafe { bgp_session.as_mut().unwrap().as_mut() };
+    let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, buffer_length as usize) };
+
+    bgp_session.receive(bytes, now);
+    bgp_session.take_output()
+}
+
+/// Run the timers of a [BgpSession]. Should be called at least once per second.
+///
+/// # Safety
+/// `bgp_session` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_session` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_session_tick(
+    bgp_session: *mut Opaque<BgpSession>,
+    now: u64,
+) -> BgpSessionOutput {
+    let bgp_session = unsafe { bgp_session.as_mut().unwrap().as_mut() };
+
+    bgp_session.tick(now);
+    bgp_session.take_output()
+}
+
+/// Get a mutable pointer to the [BgpParsingContext] used by a [BgpSession]
+///
+/// # Safety
+/// `bgp_session` should be not null and point to valid data
+///
+/// This function does not consume the `bgp_session` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_session_get_parsing_context(
+    bgp_session: *mut Opaque<BgpSession>,
+) -> *mut Opaque<BgpParsingContext> {
+    let bgp_session = unsafe { bgp_session.as_mut().unwrap().as_mut() };
+
+    Opaque::mut_from_mut(bgp_session.parsing_context_mut())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use netgauze_bgp_pkt::capabilities::{BgpCapability, FourOctetAsCapability};
+    use netgauze_bgp_pkt::open::{BgpOpenMessage, BgpOpenMessageParameter};
+    use netgauze_bgp_pkt::BgpMessage;
+
+    use pmacct_gauze_bindings::in_addr;
+
+    use crate::capi::bgp::session::{
+        netgauze_bgp_session_output_free, BgpSession, BgpSessionConfig, BgpSessionEvent,
+        BgpSessionState,
+    };
+    use crate::capi::bgp::write::append_bgp_message;
+
+    fn to_bytes(message: &BgpMessage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        append_bgp_message(message, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn peer_open() -> Vec<u8> {
+        open_with_capabilities(vec![BgpCapability::FourOctetAs(
+            FourOctetAsCapability::new(65000),
+        )])
+    }
+
+    fn open_with_capabilities(capabilities: Vec<BgpCapability>) -> Vec<u8> {
+        to_bytes(&BgpMessage::Open(BgpOpenMessage::new(
+            65000,
+            90,
+            Ipv4Addr::new(192, 0, 2, 1),
+            vec![BgpOpenMessageParameter::Capabilities(capabilities)],
+        )))
+    }
+
+    /// Header of an UPDATE announcing `length` bytes, without its body
+    fn update_header(length: u16) -> Vec<u8> {
+        let mut header = vec![0xff; 16];
+        header.extend_from_slice(&length.to_be_bytes());
+        header.push(2);
+        header
+    }
+
+    fn session() -> BgpSession {
+        BgpSession::new(
+            BgpSessionConfig {
+                my_as: 0,
+                my_bgp_id: in_addr::from(&Ipv4Addr::new(192, 0, 2, 254)),
+                hold_time: 180,
+            },
+            0,
+        )
+    }
+
+    #[test]
+    fn test_session_established() {
+        let mut session = session();
+        let open = peer_open();
+        let keepalive = to_bytes(&BgpMessage::KeepAlive);
+
+        // OPEN split across two segments
+        let (first, second) = open.split_at(10);
+        session.receive(first, 1);
+        assert_eq!(session.state(), BgpSessionState::Active);
+        session.receive(second, 1);
+        assert_eq!(session.state(), BgpSessionState::OpenConfirm);
+        assert!(session.tx_buffer.ends_with(&keepalive));
+        assert!(session.tx_buffer.len() > keepalive.len());
+        netgauze_bgp_session_output_free(session.take_output());
+
+        session.receive(&keepalive, 2);
+        assert_eq!(session.state(), BgpSessionState::Established);
+        assert_eq!(session.events.len(), 1);
+        assert!(matches!(session.events[0], BgpSessionEvent::Established(_)));
+        netgauze_bgp_session_output_free(session.take_output());
+
+        // Negotiated hold time is 90s, a KEEPALIVE is due every 30s
+        session.tick(40);
+        assert_eq!(session.tx_buffer, keepalive);
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+
+    #[test]
+    fn test_session_hold_timer_expired() {
+        let mut session = session();
+        session.receive(&peer_open(), 0);
+        session.receive(&to_bytes(&BgpMessage::KeepAlive), 0);
+        netgauze_bgp_session_output_free(session.take_output());
+
+        session.tick(91);
+        assert_eq!(session.state(), BgpSessionState::Closed);
+        assert!(matches!(
+            session.events[0],
+            BgpSessionEvent::HoldTimerExpired
+        ));
+        assert!(matches!(
+            session.events[1],
+            BgpSessionEvent::NotificationSent {
+                code: 4,
+                subcode: 0
+            }
+        ));
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+
+    #[test]
+    fn test_session_unexpected_message() {
+        let mut session = session();
+        session.receive(&to_bytes(&BgpMessage::KeepAlive), 0);
+        assert_eq!(session.state(), BgpSessionState::Closed);
+        assert!(matches!(
+            session.events[0],
+            BgpSessionEvent::NotificationSent { code: 5, .. }
+        ));
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+
+    #[test]
+    fn test_session_open_reply_error() {
+        let mut session = BgpSession::new(
+            BgpSessionConfig {
+                my_as: 4200000000,
+                my_bgp_id: in_addr::from(&Ipv4Addr::new(192, 0, 2, 254)),
+                hold_time: 180,
+            },
+            0,
+        );
+
+        // The peer does not support 4-octet AS numbers
+        let open = to_bytes(&BgpMessage::Open(BgpOpenMessage::new(
+            65000,
+            90,
+            Ipv4Addr::new(192, 0, 2, 1),
+            vec![],
+        )));
+        session.receive(&open, 0);
+
+        assert_eq!(session.state(), BgpSessionState::Closed);
+        assert!(matches!(
+            session.events[0],
+            BgpSessionEvent::NotificationSent {
+                code: 2,
+                subcode: 7
+            }
+        ));
+        // Unsupported Capability data: the 4-octet AS number capability with the collector ASN
+        assert!(session
+            .tx_buffer
+            .ends_with(&[65, 4, 0xfa, 0x56, 0xea, 0x00]));
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+
+    #[test]
+    fn test_session_message_too_long() {
+        let mut session = session();
+        session.receive(&peer_open(), 0);
+        session.receive(&to_bytes(&BgpMessage::KeepAlive), 0);
+        netgauze_bgp_session_output_free(session.take_output());
+
+        session.receive(&update_header(4097), 1);
+        assert_eq!(session.state(), BgpSessionState::Closed);
+        assert!(matches!(
+            session.events[0],
+            BgpSessionEvent::NotificationSent {
+                code: 1,
+                subcode: 2
+            }
+        ));
+        // Bad Message Length data: the erroneous length field
+        assert!(session.tx_buffer.ends_with(&[0x10, 0x01]));
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+
+    #[test]
+    fn test_session_extended_message() {
+        let mut session = session();
+        session.receive(
+            &open_with_capabilities(vec![
+                BgpCapability::FourOctetAs(FourOctetAsCapability::new(65000)),
+                BgpCapability::ExtendedMessage,
+            ]),
+            0,
+        );
+        session.receive(&to_bytes(&BgpMessage::KeepAlive), 0);
+        netgauze_bgp_session_output_free(session.take_output());
+
+        // Up to 65535 bytes with Extended Messages, the rest of the UPDATE is awaited
+        session.receive(&update_header(4097), 1);
+        assert_eq!(session.state(), BgpSessionState::Established);
+        assert!(session.events.is_empty());
+        netgauze_bgp_session_output_free(session.take_output());
+    }
+}
@@ -1,4 +1,4 @@
-use ipnet::Ipv4Net;
+use ipnet::{IpNet, Ipv4Net};
 use netgauze_bgp_pkt::nlri::{MplsLabel, RouteDistinguisher};
 use netgauze_bgp_pkt::path_attribute::{
     Aigp, As4Path, AsPath, MpReach, MpUnreach, PathAttribute, PathAttributeValue,
@@ -69,12 +69,12 @@ pub struct ParsedBgpUpdate {
 
 #[repr(C)]
 pub struct ProcessPacket {
-    update_type: u32,
-    afi: afi_t,
-    safi: safi_t,
-    prefix: prefix,
-    attr: bgp_attr,
-    attr_extra: bgp_attr_extra,
+    pub(crate) update_type: u32,
+    pub(crate) afi: afi_t,
+    pub(crate) safi: safi_t,
+    pub(crate) prefix: prefix,
+    pub(crate) attr: bgp_attr,
+    pub(crate) attr_extra: bgp_attr_extra,
 }
 
 free_cslice_t!(ProcessPacket);
@@ -94,6 +94,194 @@ impl Debug for ProcessPacket {
     }
 }
 
+impl ProcessPacket {
+    /// Withdrawal of a route, without path attributes
+    pub(crate) fn withdraw(
+        afi: afi_t,
+        safi: safi_t,
+        prefix: prefix,
+        attr_extra: bgp_attr_extra,
+    ) -> Self {
+        Self {
+            update_type: BGP_NLRI_WITHDRAW,
+            afi,
+            safi,
+            prefix,
+            attr: bgp_attr::default_zeroed(),
+            attr_extra,
+        }
+    }
+}
+
+/// A route of an MP_REACH_NLRI or MP_UNREACH_NLRI attribute
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MpNlri {
+    pub(crate) prefix: IpNet,
+    pub(crate) path_id: Option<path_id_t>,
+    /// Bottom of stack MPLS label, zeroes if the route has none
+    pub(crate) label: [u8; 3],
+    pub(crate) rd: Option<RouteDistinguisher>,
+}
+
+impl MpNlri {
+    pub(crate) fn new(prefix: IpNet, path_id: Option<path_id_t>) -> Self {
+        Self {
+            prefix,
+            path_id,
+            label: [0, 0, 0],
+            rd: None,
+        }
+    }
+
+    fn with_label(mut self, label_stack: &[MplsLabel]) -> Self {
+        self.label = bottom_of_stack_label(label_stack);
+        self
+    }
+
+    fn with_rd(mut self, rd: RouteDistinguisher) -> Self {
+        self.rd = Some(rd);
+        self
+    }
+}
+
+/// Next hop and routes of an MP_REACH_NLRI attribute,
+/// None if pmacct does not support its address type
+pub(crate) fn mp_reach_nlri(mp_reach: &MpReach) -> Option<(IpAddr, Vec<MpNlri>)> {
+    // pmacct only has AFI IPv4/6 & BGP-LS
+    // and SAFI UNICAST MPLS-LABEL MPLS-VPN
+    let result = match mp_reach {
+        MpReach::Ipv4Unicast { next_hop, nlri, .. } => (
+            *next_hop,
+            nlri.iter()
+                .map(|nlri| MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id()))
+                .collect(),
+        ),
+        MpReach::Ipv4NlriMplsLabels { next_hop, nlri, .. } => (
+            *next_hop,
+            nlri.iter()
+                .map(|nlri| {
+                    MpNlri::new(IpNet::from(nlri.prefix()), nlri.path_id())
+                        .with_label(nlri.labels())
+                })
+                .collect(),
+        ),
+        MpReach::Ipv4MplsVpnUnicast { next_hop, nlri } => (
+            next_hop.next_hop(),
+            nlri.iter()
+                .map(|nlri| {
+                    MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id())
+                        .with_label(nlri.label_stack())
+                        .with_rd(nlri.rd())
+                })
+                .collect(),
+        ),
+        MpReach::Ipv6Unicast {
+            next_hop_global,
+            nlri,
+            ..
+        } => (
+            IpAddr::V6(*next_hop_global),
+            nlri.iter()
+                .map(|nlri| MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id()))
+                .collect(),
+        ),
+        MpReach::Ipv6NlriMplsLabels { next_hop, nlri, .. } => (
+            *next_hop,
+            nlri.iter()
+                .map(|nlri| {
+                    MpNlri::new(IpNet::from(nlri.prefix()), nlri.path_id())
+                        .with_label(nlri.labels())
+                })
+                .collect(),
+        ),
+        MpReach::Ipv6MplsVpnUnicast { next_hop, nlri } => (
+            next_hop.next_hop(),
+            nlri.iter()
+                .map(|nlri| {
+                    MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id())
+                        .with_label(nlri.label_stack())
+                        .with_rd(nlri.rd())
+                })
+                .collect(),
+        ),
+
+        // not supported by pmacct
+        MpReach::Ipv4Multicast { .. }
+        | MpReach::Ipv6Multicast { .. }
+        | MpReach::L2Evpn { .. }
+        | MpReach::RouteTargetMembership { .. }
+        | MpReach::BgpLs { .. }
+        | MpReach::BgpLsVpn { .. }
+        | MpReach::Unknown { .. } => return None,
+    };
+
+    Some(result)
+}
+
+/// Routes of an MP_UNREACH_NLRI attribute, None if pmacct does not support its address type
+pub(crate) fn mp_unreach_nlri(mp_unreach: &MpUnreach) -> Option<Vec<MpNlri>> {
+    // pmacct only has AFI IPv4/6 & BGP-LS
+    // and SAFI UNICAST MPLS-LABEL MPLS-VPN
+    let result = match mp_unreach {
+        MpUnreach::Ipv4Unicast { nlri } => nlri
+            .iter()
+            .map(|nlri| MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id()))
+            .collect(),
+        MpUnreach::Ipv4NlriMplsLabels { nlri } => nlri
+            .iter()
+            .map(|nlri| {
+                MpNlri::new(IpNet::from(nlri.prefix()), nlri.path_id()).with_label(nlri.labels())
+            })
+            .collect(),
+        MpUnreach::Ipv4MplsVpnUnicast { nlri } => nlri
+            .iter()
+            .map(|nlri| {
+                MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id())
+                    .with_label(nlri.label_stack())
+                    .with_rd(nlri.rd())
+            })
+            .collect(),
+        MpUnreach::Ipv6Unicast { nlri } => nlri
+            .iter()
+            .map(|nlri| MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id()))
+            .collect(),
+        MpUnreach::Ipv6NlriMplsLabels { nlri } => nlri
+            .iter()
+            .map(|nlri| {
+                MpNlri::new(IpNet::from(nlri.prefix()), nlri.path_id()).with_label(nlri.labels())
+            })
+            .collect(),
+        MpUnreach::Ipv6MplsVpnUnicast { nlri } => nlri
+            .iter()
+            .map(|nlri| {
+                MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id())
+                    .with_label(nlri.label_stack())
+                    .with_rd(nlri.rd())
+            })
+            .collect(),
+
+        // not supported by pmacct
+        MpUnreach::Ipv4Multicast { .. }
+        | MpUnreach::Ipv6Multicast { .. }
+        | MpUnreach::L2Evpn { .. }
+        | MpUnreach::RouteTargetMembership { .. }
+        | MpUnreach::BgpLs { .. }
+        | MpUnreach::BgpLsVpn { .. }
+        | MpUnreach::Unknown { .. } => return None,
+    };
+
+    Some(result)
+}
+
+/// Fill the path id, label and RD of an [MpNlri] in `attr_extra`
+fn fill_mp_nlri(attr_extra: &mut bgp_attr_extra, nlri: &MpNlri) {
+    fill_path_id(attr_extra, nlri.path_id);
+    attr_extra.label = nlri.label;
+    if let Some(rd) = nlri.rd {
+        fill_rd(attr_extra, rd);
+    }
+}
+
 pub fn process_mp_unreach(
     mp_unreach: &MpUnreach,
     attr: &mut bgp_attr,
@@ -118,115 +306,29 @@ pub fn process_mp_unreach(
         }
     };
 
-    let update_type = BGP_NLRI_WITHDRAW;
-
-    match mp_unreach {
-        // pmacct only has AFI IPv4/6 & BGP-LS
-        // and SAFI UNICAST MPLS-LABEL MPLS-VPN
-        MpUnreach::Ipv4Unicast { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                });
-            }
-        }
-        MpUnreach::Ipv4NlriMplsLabels { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.labels());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.prefix()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                });
-            }
-        }
-        MpUnreach::Ipv4MplsVpnUnicast { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.label_stack());
-                fill_rd(attr_extra, nlri.rd());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-        MpUnreach::Ipv6Unicast { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-        MpUnreach::Ipv6NlriMplsLabels { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.labels());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.prefix()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-        MpUnreach::Ipv6MplsVpnUnicast { nlri: nlris } => {
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.label_stack());
-                fill_rd(attr_extra, nlri.rd());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-
-        // not supported by pmacct
-        MpUnreach::Ipv4Multicast { .. }
-        | MpUnreach::Ipv6Multicast { .. }
-        | MpUnreach::L2Evpn { .. }
-        | MpUnreach::RouteTargetMembership { .. }
-        | MpUnreach::BgpLs { .. }
-        | MpUnreach::BgpLsVpn { .. }
-        | MpUnreach::Unknown { .. } => {
+    let nlris = match mp_unreach_nlri(mp_unreach) {
+        Some(nlris) => nlris,
+        None => {
             pmacct_log(LogPriority::Warning, &format!("[pmacct-gauze] warn! received mp_unreach with unsupported or unknown afi/safi {}/{} address type {:?}\n",
                                                       mp_unreach.afi(), mp_unreach.safi(), mp_unreach.address_type()));
+            return;
         }
+    };
+
+    for nlri in nlris {
+        fill_mp_nlri(attr_extra, &nlri);
+
+        packets.push(ProcessPacket {
+            update_type: BGP_NLRI_WITHDRAW,
+            prefix: make_prefix(&nlri.prefix),
+            attr: *attr,
+            attr_extra: *attr_extra,
+            afi,
+            safi,
+        });
     }
 }
+
 pub fn process_mp_reach(
     mp_reach: &MpReach,
     attr: &mut bgp_attr,
@@ -251,147 +353,35 @@ pub fn process_mp_reach(
         }
     };
 
-    let update_type = BGP_NLRI_UPDATE;
-
-    match mp_reach {
-        // pmacct only has AFI IPv4/6 & BGP-LS
-        // and SAFI UNICAST MPLS-LABEL MPLS-VPN
-        MpReach::Ipv4Unicast {
-            next_hop,
-            next_hop_local: _,
-            nlri: nlris,
-        } => {
-            fill_attr_mp_next_hop(attr, next_hop);
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                });
-            }
-        }
-        MpReach::Ipv4NlriMplsLabels {
-            next_hop,
-            next_hop_local: _,
-            nlri: nlris,
-        } => {
-            fill_attr_mp_next_hop(attr, next_hop);
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.labels());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.prefix()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                });
-            }
-        }
-        MpReach::Ipv4MplsVpnUnicast {
-            next_hop,
-            nlri: nlris,
-        } => {
-            fill_attr_mp_next_hop(attr, &next_hop.next_hop());
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.label_stack());
-                fill_rd(attr_extra, nlri.rd());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-        MpReach::Ipv6Unicast {
-            next_hop_global,
-            next_hop_local: _,
-            nlri: nlris,
-        } => {
-            fill_attr_ipv6_next_hop(attr, next_hop_global);
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                });
-            }
-        }
-        MpReach::Ipv6NlriMplsLabels {
-            next_hop,
-            next_hop_local: _next_hop_local,
-            nlri: nlris,
-        } => {
-            fill_attr_mp_next_hop(attr, next_hop);
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.labels());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.prefix()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-        MpReach::Ipv6MplsVpnUnicast {
-            next_hop,
-            nlri: nlris,
-        } => {
-            fill_attr_mp_next_hop(attr, &next_hop.next_hop());
-
-            for nlri in nlris {
-                fill_path_id(attr_extra, nlri.path_id());
-                fill_mpls_label(attr_extra, nlri.label_stack());
-                fill_rd(attr_extra, nlri.rd());
-
-                packets.push(ProcessPacket {
-                    update_type,
-                    prefix: prefix::from(&nlri.network().address()),
-                    attr: *attr,
-                    attr_extra: *attr_extra,
-                    afi,
-                    safi,
-                })
-            }
-        }
-
-        // not supported by pmacct
-        MpReach::Ipv4Multicast { .. }
-        | MpReach::Ipv6Multicast { .. }
-        | MpReach::L2Evpn { .. }
-        | MpReach::RouteTargetMembership { .. }
-        | MpReach::BgpLs { .. }
-        | MpReach::BgpLsVpn { .. }
-        | MpReach::Unknown { .. } => {
+    let (next_hop, nlris) = match mp_reach_nlri(mp_reach) {
+        Some(result) => result,
+        None => {
             pmacct_log(LogPriority::Warning, &format!("[pmacct-gauze] warn! received mp_reach with unsupported or unknown afi/safi {}/{} address type {:?}\n",
                                                       mp_reach.afi(), mp_reach.safi(), mp_reach.address_type()));
+            return;
         }
+    };
+
+    fill_attr_mp_next_hop(attr, &next_hop);
+
+    for nlri in nlris {
+        fill_mp_nlri(attr_extra, &nlri);
+
+        packets.push(ProcessPacket {
+            update_type: BGP_NLRI_UPDATE,
+            prefix: make_prefix(&nlri.prefix),
+            attr: *attr,
+            attr_extra: *attr_extra,
+            afi,
+            safi,
+        });
+    }
+}
+
+pub(crate) fn make_prefix(net: &IpNet) -> prefix {
+    match net {
+        IpNet::V4(net) => prefix::from(net),
+        IpNet::V6(net) => prefix::from(net),
     }
 }
 
@@ -688,7 +678,7 @@ fn fill_attr_ipv6_next_hop(attr: &mut bgp_attr, next_hop: &Ipv6Addr) {
     attr.mp_nexthop = host_addr::from(next_hop)
 }
 
-fn fill_attr_mp_next_hop(attr: &mut bgp_attr, next_hop: &IpAddr) {
+pub(crate) fn fill_attr_mp_next_hop(attr: &mut bgp_attr, next_hop: &IpAddr) {
     match next_hop {
         IpAddr::V4(ipv4) => fill_attr_ipv4_next_hop(attr, ipv4, true),
         IpAddr::V6(ipv6) => fill_attr_ipv6_next_hop(attr, ipv6),
@@ -699,10 +689,11 @@ fn fill_path_id(attr_extra: &mut bgp_attr_extra, path_id: Option<path_id_t>) {
     attr_extra.path_id = path_id.unwrap_or(0);
 }
 
-fn fill_mpls_label(attr_extra: &mut bgp_attr_extra, label_stack: &[MplsLabel]) {
+/// Value of the bottom of stack label of a label stack, zeroes if there is none
+pub(crate) fn bottom_of_stack_label(label_stack: &[MplsLabel]) -> [u8; 3] {
     let bos = label_stack.iter().rev().find(|label| label.is_bottom());
 
-    attr_extra.label = if let Some(bos) = bos {
+    if let Some(bos) = bos {
         *bos.value()
     } else {
         [0, 0, 0]
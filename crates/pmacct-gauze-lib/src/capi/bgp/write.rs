@@ -0,0 +1,128 @@
+use std::ffi::{c_char, CString};
+use std::io::BufWriter;
+use std::slice;
+
+use c_str_macro::c_str;
+use netgauze_bgp_pkt::BgpMessage;
+use netgauze_parse_utils::WritablePdu;
+
+use crate::cresult::CResult;
+
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub enum BgpMessageWriteError {
+    BufferTooSmall { needed: usize },
+    UndefinedNotificationCode(u8),
+    UndefinedNotificationSubCode { code: u8, subcode: u8 },
+    NotAShutdownCommunicationSubCode(u8),
+    ShutdownCommunicationTooLong(usize),
+    ShutdownCommunicationNotUtf8,
+    NetgauzeWriteError { err_str: *mut c_char },
+}
+
+pub type BgpMessageWriteResult = CResult<usize, BgpMessageWriteError>;
+
+impl<T> From<BgpMessageWriteError> for CResult<T, BgpMessageWriteError> {
+    fn from(value: BgpMessageWriteError) -> Self {
+        Self::Err(value)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_message_write_result_err_str(
+    value: BgpMessageWriteError,
+) -> *const c_char {
+    match value {
+        BgpMessageWriteError::BufferTooSmall { .. } => c_str! {
+            "BgpMessageWriteError::BufferTooSmall"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::UndefinedNotificationCode(_) => c_str! {
+            "BgpMessageWriteError::UndefinedNotificationCode"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::UndefinedNotificationSubCode { .. } => c_str! {
+            "BgpMessageWriteError::UndefinedNotificationSubCode"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::NotAShutdownCommunicationSubCode(_) => c_str! {
+            "BgpMessageWriteError::NotAShutdownCommunicationSubCode"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::ShutdownCommunicationTooLong(_) => c_str! {
+            "BgpMessageWriteError::ShutdownCommunicationTooLong"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::ShutdownCommunicationNotUtf8 => c_str! {
+            "BgpMessageWriteError::ShutdownCommunicationNotUtf8"
+        }
+        .as_ptr(),
+        BgpMessageWriteError::NetgauzeWriteError { err_str } => err_str,
+    }
+}
+
+#[allow(clippy::single_match)]
+#[no_mangle]
+pub extern "C" fn netgauze_bgp_message_write_result_free(value: BgpMessageWriteResult) {
+    match value {
+        CResult::Ok(_) => {}
+        CResult::Err(write_error) => match write_error {
+            BgpMessageWriteError::NetgauzeWriteError { err_str } => unsafe {
+                drop(CString::from_raw(err_str));
+            },
+            _ => {}
+        },
+    };
+}
+
+/// Serialize a [BgpMessage] at the end of `bytes`
+pub(crate) fn append_bgp_message(
+    bgp_message: &BgpMessage,
+    bytes: &mut Vec<u8>,
+) -> Result<(), BgpMessageWriteError> {
+    let write_result = {
+        let mut writer = BufWriter::new(bytes);
+        bgp_message.write(&mut writer)
+    };
+
+    write_result.map_err(|err| BgpMessageWriteError::NetgauzeWriteError {
+        err_str: CString::new(format!("{:?}", err)).unwrap().into_raw(),
+    })
+}
+
+/// Serialize a [BgpMessage] in `buf` and return the number of bytes written.
+///
+/// Nothing is written to `buf` if the message does not fit.
+pub(crate) fn write_bgp_message(
+    bgp_message: &BgpMessage,
+    buf: &mut [u8],
+) -> Result<usize, BgpMessageWriteError> {
+    let mut bytes = Vec::with_capacity(bgp_message.len());
+    append_bgp_message(bgp_message, &mut bytes)?;
+
+    if bytes.len() > buf.len() {
+        return Err(BgpMessageWriteError::BufferTooSmall {
+            needed: bytes.len(),
+        });
+    }
+
+    buf[..bytes.len()].copy_from_slice(&bytes);
+
+    Ok(bytes.len())
+}
+
+/// Write a BGP KeepAlive message in `buf`
+///
+/// # Safety
+/// `buf` should be not null and point a byte buffer of length `buf_len` we can write to
+///
+/// This function does not consume the `buf` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bgp_write_keepalive(
+    buf: *mut c_char,
+    buf_len: usize,
+) -> BgpMessageWriteResult {
+    let buf = unsafe { slice::from_raw_parts_mut(buf as *mut u8, buf_len) };
+
+    write_bgp_message(&BgpMessage::KeepAlive, buf).into()
+}
@@ -0,0 +1,398 @@
+use std::ffi::{c_char, CString};
+
+use c_str_macro::c_str;
+use netgauze_bmp_pkt::{BmpMessageValue, InitiationInformation, TerminationInformation};
+
+use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::free_cslice_t_with_item_free;
+use crate::opaque::Opaque;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BmpInformationError {
+    WrongBmpMessageType(WrongBmpMessageTypeError),
+    /// The message does not contain the requested TLV
+    NotFound,
+    /// The value contains a NUL byte and cannot be turned into a C string
+    InteriorNul,
+}
+
+impl<T> From<BmpInformationError> for CResult<T, BmpInformationError> {
+    fn from(value: BmpInformationError) -> Self {
+        Self::Err(value)
+    }
+}
+
+impl From<WrongBmpMessageTypeError> for BmpInformationError {
+    fn from(value: WrongBmpMessageTypeError) -> Self {
+        Self::WrongBmpMessageType(value)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_information_error_str(value: BmpInformationError) -> *const c_char {
+    match value {
+        BmpInformationError::WrongBmpMessageType(_) => c_str! {
+            "BmpInformationError::WrongBmpMessageType"
+        }
+        .as_ptr(),
+        BmpInformationError::NotFound => c_str! {
+            "BmpInformationError::NotFound"
+        }
+        .as_ptr(),
+        BmpInformationError::InteriorNul => c_str! {
+            "BmpInformationError::InteriorNul"
+        }
+        .as_ptr(),
+    }
+}
+
+/// The string must be freed using [netgauze_bmp_information_string_free]
+pub type BmpInformationStringResult = CResult<*mut c_char, BmpInformationError>;
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_information_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        unsafe { drop(CString::from_raw(value)) }
+    }
+}
+
+/// An owned C string, freed with its [OwnedSlice]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpInformationString {
+    pub value: *mut c_char,
+}
+
+impl RustFree for BmpInformationString {
+    fn rust_free(self) {
+        netgauze_bmp_information_string_free(self.value)
+    }
+}
+
+free_cslice_t_with_item_free!(BmpInformationString);
+
+/// The [OwnedSlice<BmpInformationString>] must be freed with [CSlice_free_BmpInformationString]
+pub type BmpInformationStringListResult =
+    CResult<OwnedSlice<BmpInformationString>, BmpInformationError>;
+
+fn to_c_string(value: &str) -> Result<*mut c_char, BmpInformationError> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|_| BmpInformationError::InteriorNul)
+}
+
+fn to_c_string_list<'a>(
+    values: impl Iterator<Item = &'a String>,
+) -> BmpInformationStringListResult {
+    let mut result = Vec::new();
+    for value in values {
+        match to_c_string(value) {
+            Ok(value) => result.push(BmpInformationString { value }),
+            Err(err) => {
+                OwnedSlice::from_vec(result).rust_free();
+                return err.into();
+            }
+        }
+    }
+
+    CResult::Ok(OwnedSlice::from_vec(result))
+}
+
+/// Get the Initiation Information TLVs of a BMP Initiation Message
+fn init_information(
+    bmp_value: &BmpMessageValue,
+) -> Result<&Vec<InitiationInformation>, BmpInformationError> {
+    match bmp_value {
+        BmpMessageValue::Initiation(init) => Ok(init.information()),
+        _ => Err(WrongBmpMessageTypeError(bmp_value.get_type().into()).into()),
+    }
+}
+
+/// Get the Termination Information TLVs of a BMP Termination Message
+fn term_information(
+    bmp_value: &BmpMessageValue,
+) -> Result<&Vec<TerminationInformation>, BmpInformationError> {
+    match bmp_value {
+        BmpMessageValue::Termination(term) => Ok(term.information()),
+        _ => Err(WrongBmpMessageTypeError(bmp_value.get_type().into()).into()),
+    }
+}
+
+/// Get the value of the first Initiation Information TLV selected by `select` as a C string
+fn init_get_string(
+    bmp_value: &BmpMessageValue,
+    select: impl Fn(&InitiationInformation) -> Option<&String>,
+) -> BmpInformationStringResult {
+    let information = match init_information(bmp_value) {
+        Ok(information) => information,
+        Err(err) => return err.into(),
+    };
+
+    let value = information.iter().find_map(select);
+    match value.map(|value| to_c_string(value)) {
+        Some(Ok(value)) => CResult::Ok(value),
+        Some(Err(err)) => err.into(),
+        None => BmpInformationError::NotFound.into(),
+    }
+}
+
+/// Get the sysName of a BMP Initiation Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_init_get_sys_name(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpInformationStringResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    init_get_string(bmp_value, |tlv| match tlv {
+        InitiationInformation::SystemName(sys_name) => Some(sys_name),
+        _ => None,
+    })
+}
+
+/// Get the sysDescr of a BMP Initiation Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_init_get_sys_descr(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpInformationStringResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    init_get_string(bmp_value, |tlv| match tlv {
+        InitiationInformation::SystemDescription(sys_descr) => Some(sys_descr),
+        _ => None,
+    })
+}
+
+/// Get all the String TLVs of a BMP Initiation Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_init_get_strings(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpInformationStringListResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let information = match init_information(bmp_value) {
+        Ok(information) => information,
+        Err(err) => return err.into(),
+    };
+
+    to_c_string_list(information.iter().filter_map(|tlv| match tlv {
+        InitiationInformation::String(value) => Some(value),
+        _ => None,
+    }))
+}
+
+/// Get all the String TLVs of a BMP Termination Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_term_get_strings(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpInformationStringListResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let information = match term_information(bmp_value) {
+        Ok(information) => information,
+        Err(err) => return err.into(),
+    };
+
+    to_c_string_list(information.iter().filter_map(|tlv| match tlv {
+        TerminationInformation::String(value) => Some(value),
+        _ => None,
+    }))
+}
+
+pub type BmpTermReasonResult = CResult<u16, BmpInformationError>;
+
+/// Get the numeric reason code of a BMP Termination Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_term_get_reason(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpTermReasonResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let information = match term_information(bmp_value) {
+        Ok(information) => information,
+        Err(err) => return err.into(),
+    };
+
+    let reason = information.iter().find_map(|tlv| match tlv {
+        TerminationInformation::Reason(reason) => Some(*reason as u16),
+        _ => None,
+    });
+
+    match reason {
+        Some(reason) => CResult::Ok(reason),
+        None => BmpInformationError::NotFound.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, CStr};
+
+    use netgauze_bmp_pkt::BmpMessageValue;
+
+    use crate::capi::bmp::information::{
+        netgauze_bmp_information_string_free, netgauze_bmp_init_get_strings,
+        netgauze_bmp_init_get_sys_descr, netgauze_bmp_init_get_sys_name,
+        netgauze_bmp_term_get_reason, netgauze_bmp_term_get_strings, BmpInformationError,
+        BmpInformationStringListResult, BmpInformationStringResult,
+        CSlice_free_BmpInformationString,
+    };
+    use crate::capi::bmp::parse::{
+        netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free, BmpParseResult,
+    };
+    use crate::capi::bmp::test_util::bmp_message;
+    use crate::cresult::CResult;
+    use crate::opaque::Opaque;
+
+    /// Information TLV of type `tlv_type` with `value`
+    fn tlv(tlv_type: u16, value: &[u8]) -> Vec<u8> {
+        let mut tlv = tlv_type.to_be_bytes().to_vec();
+        tlv.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    fn parse(message: &[u8]) -> BmpParseResult {
+        unsafe {
+            netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, message.len() as u32)
+        }
+    }
+
+    fn message_value(result: &BmpParseResult) -> *const Opaque<BmpMessageValue> {
+        match result {
+            CResult::Ok(parsed) => parsed.message,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+    }
+
+    fn string(result: BmpInformationStringResult) -> String {
+        match result {
+            CResult::Ok(value) => {
+                let string = unsafe { CStr::from_ptr(value) }
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                netgauze_bmp_information_string_free(value);
+                string
+            }
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+    }
+
+    fn strings(result: BmpInformationStringListResult) -> Vec<String> {
+        match result {
+            CResult::Ok(values) => {
+                let strings = unsafe { values.as_slice() }
+                    .iter()
+                    .map(|value| {
+                        unsafe { CStr::from_ptr(value.value) }
+                            .to_str()
+                            .unwrap()
+                            .to_string()
+                    })
+                    .collect();
+                CSlice_free_BmpInformationString(values);
+                strings
+            }
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_init_get_information() {
+        let mut body = tlv(0, b"first");
+        body.extend_from_slice(&tlv(1, b"descr"));
+        body.extend_from_slice(&tlv(0, b"second"));
+        body.extend_from_slice(&tlv(2, b"r1"));
+        body.extend_from_slice(&tlv(0, b"third"));
+        body.extend_from_slice(&tlv(2, b"r2"));
+        let result = parse(&bmp_message(3, 4, &body));
+        let value = message_value(&result);
+
+        assert_eq!(
+            string(unsafe { netgauze_bmp_init_get_sys_name(value) }),
+            "r1"
+        );
+        assert_eq!(
+            string(unsafe { netgauze_bmp_init_get_sys_descr(value) }),
+            "descr"
+        );
+        assert_eq!(
+            strings(unsafe { netgauze_bmp_init_get_strings(value) }),
+            vec!["first", "second", "third"]
+        );
+        assert!(matches!(
+            unsafe { netgauze_bmp_term_get_reason(value) },
+            CResult::Err(BmpInformationError::WrongBmpMessageType(_))
+        ));
+
+        netgauze_bmp_parse_result_free(result);
+
+        // No sysDescr
+        let result = parse(&bmp_message(3, 4, &tlv(2, b"r1")));
+        assert!(matches!(
+            unsafe { netgauze_bmp_init_get_sys_descr(message_value(&result)) },
+            CResult::Err(BmpInformationError::NotFound)
+        ));
+        netgauze_bmp_parse_result_free(result);
+    }
+
+    #[test]
+    fn test_term_get_information() {
+        // Out of resources
+        let mut body = tlv(0, b"bye");
+        body.extend_from_slice(&tlv(1, &[0, 2]));
+        let result = parse(&bmp_message(3, 5, &body));
+        let value = message_value(&result);
+
+        assert!(matches!(
+            unsafe { netgauze_bmp_term_get_reason(value) },
+            CResult::Ok(2)
+        ));
+        assert_eq!(
+            strings(unsafe { netgauze_bmp_term_get_strings(value) }),
+            vec!["bye"]
+        );
+        assert!(matches!(
+            unsafe { netgauze_bmp_init_get_sys_name(value) },
+            CResult::Err(BmpInformationError::WrongBmpMessageType(_))
+        ));
+
+        netgauze_bmp_parse_result_free(result);
+
+        // No reason
+        let result = parse(&bmp_message(3, 5, &tlv(0, b"bye")));
+        assert!(matches!(
+            unsafe { netgauze_bmp_term_get_reason(message_value(&result)) },
+            CResult::Err(BmpInformationError::NotFound)
+        ));
+        netgauze_bmp_parse_result_free(result);
+    }
+}
@@ -1,17 +1,18 @@
 use std::error::Error;
+use std::ffi::c_void;
 use std::fmt::{Display, Formatter};
 use std::ptr;
 
 use libc::{AF_INET, AF_INET6};
 use netgauze_bgp_pkt::BgpMessage;
 use netgauze_bmp_pkt::iana::BmpMessageType;
-use netgauze_bmp_pkt::{BmpMessageValue, InitiationInformation, PeerKey, TerminationInformation};
-use netgauze_parse_utils::WritablePdu;
+use netgauze_bmp_pkt::{BmpMessageValue, PeerKey};
 
 use pmacct_gauze_bindings::{
     bmp_chars, bmp_data, bmp_log_tlv, host_addr, rd_t, timeval, u_int8_t, DefaultZeroed,
 };
 
+use crate::capi::bmp::parse::{BmpV4Tlv, BmpV4Tlvs};
 use crate::cresult::CResult;
 use crate::cslice::OwnedSlice;
 pub use crate::cslice::RustFree;
@@ -21,10 +22,18 @@ use crate::extensions::rd::{ExtendRdT, RdOriginType};
 use crate::free_cslice_t;
 use crate::opaque::Opaque;
 
+pub mod information;
+pub mod openmetrics;
 pub mod parse;
 pub mod peer_state;
+pub mod peer_table;
 pub mod print;
+pub mod rib;
+pub mod route_mirroring;
 pub mod stats;
+pub mod stream;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 impl Opaque<BmpMessageValue> {
     pub fn peer_key(&self) -> Option<PeerKey> {
@@ -61,7 +70,11 @@ impl<T> From<WrongBmpMessageTypeError> for CResult<T, WrongBmpMessageTypeError>
 /// The `CSlice<bmp_log_tlv>` must be manually freed with [CSlice_free_bmp_log_tlv]
 pub type BmpTlvListResult = CResult<OwnedSlice<bmp_log_tlv>, WrongBmpMessageTypeError>;
 
-/// Get an [OwnedSlice<bmp_log_tlv>] from any BMP Message
+/// Get an [OwnedSlice<bmp_log_tlv>] from any BMP Message that has TLVs
+/// (Initiation, Peer Up, Termination and Route Monitoring)
+///
+/// Only the TLVs decoded by NetGauze are returned, see [netgauze_bmp_get_tlvs_v4]
+/// for the TLVs of BMP v4 messages.
 ///
 /// # Safety
 /// `bmp_message_value_opaque` should be not null and point to valid data
@@ -71,58 +84,149 @@ pub unsafe extern "C" fn netgauze_bmp_get_tlvs(
 ) -> BmpTlvListResult {
     let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
 
-    let tlvs = match bmp_value {
-        BmpMessageValue::Initiation(init) => {
-            let mut tlvs = Vec::<bmp_log_tlv>::with_capacity(init.information().len());
+    match make_message_tlvs(bmp_value) {
+        Ok(tlvs) => CResult::Ok(OwnedSlice::from_vec(tlvs)),
+        Err(err) => err.into(),
+    }
+}
 
-            for tlv in init.information() {
-                tlvs.push(bmp_log_tlv {
-                    pen: 0, // TODO support PEN when netgauze supports bmp v4
-                    type_: tlv.get_type().into(),
-                    len: (tlv.len() - InitiationInformation::BASE_LENGTH) as u16,
-                    val: tlv.get_value_ptr(),
-                })
-            }
+/// Get an [OwnedSlice<bmp_log_tlv>] from any BMP Message that has TLVs
+/// (Initiation, Peer Up, Termination and Route Monitoring), including the TLVs of
+/// BMP v4 messages that NetGauze does not decode, in the order they were received
+///
+/// `bmp_v4_tlvs` is the [crate::capi::bmp::parse::ParsedBmp::v4_tlvs] of the message,
+/// null for BMP v3 messages.
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+/// `bmp_v4_tlvs` should be null or point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` and `bmp_v4_tlvs` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_get_tlvs_v4(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+    bmp_v4_tlvs: *const Opaque<BmpV4Tlvs>,
+) -> BmpTlvListResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+    let bmp_v4_tlvs = unsafe { bmp_v4_tlvs.as_ref() }
+        .map(|bmp_v4_tlvs| bmp_v4_tlvs.as_ref().as_slice())
+        .unwrap_or_default();
 
-            tlvs
-        }
-        BmpMessageValue::PeerUpNotification(peer_up) => {
-            let mut tlvs = Vec::<bmp_log_tlv>::with_capacity(peer_up.information().len());
-
-            for tlv in peer_up.information() {
-                tlvs.push(bmp_log_tlv {
-                    pen: 0, // TODO support PEN when netgauze supports bmp v4
-                    type_: tlv.get_type().into(),
-                    len: (tlv.len() - InitiationInformation::BASE_LENGTH) as u16,
-                    val: tlv.get_value_ptr(),
-                })
-            }
+    match make_message_tlvs(bmp_value) {
+        Ok(tlvs) => CResult::Ok(OwnedSlice::from_vec(merge_v4_tlvs(tlvs, bmp_v4_tlvs))),
+        Err(err) => err.into(),
+    }
+}
 
-            tlvs
+free_cslice_t!(bmp_log_tlv);
+
+/// A Route Monitoring TLV of a BMP v4 message.
+/// Like [bmp_log_tlv], with the index of the NLRI of the BGP UPDATE the TLV applies to.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpRmTlv {
+    /// Index of the NLRI the TLV applies to, 0 for the whole message.
+    /// Group TLVs extend the TLVs of their index to the NLRI they list.
+    pub index: u16,
+    /// Private Enterprise Number, 0 for IANA TLVs
+    pub pen: u32,
+    pub type_: u16,
+    pub len: u16,
+    /// Value of the TLV, borrowed from the `bmp_v4_tlvs` it comes from
+    pub val: *mut c_void,
+}
+
+free_cslice_t!(BmpRmTlv);
+
+/// The `CSlice<BmpRmTlv>` must be manually freed with [CSlice_free_BmpRmTlv]
+pub type BmpRmTlvListResult = CResult<OwnedSlice<BmpRmTlv>, WrongBmpMessageTypeError>;
+
+/// Get an [OwnedSlice<BmpRmTlv>] of the TLVs of a Route Monitoring Message (VRF/table name,
+/// group, stateless parsing, path marking and enterprise TLVs), in the order they were received.
+/// The BGP PDU TLV is the BGP UPDATE of the message and is not returned.
+///
+/// `bmp_v4_tlvs` is the [crate::capi::bmp::parse::ParsedBmp::v4_tlvs] of the message,
+/// null for BMP v3 messages, which have no TLVs.
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+/// `bmp_v4_tlvs` should be null or point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` and `bmp_v4_tlvs` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rm_get_tlvs(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+    bmp_v4_tlvs: *const Opaque<BmpV4Tlvs>,
+) -> BmpRmTlvListResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+    let bmp_v4_tlvs = unsafe { bmp_v4_tlvs.as_ref() }
+        .map(|bmp_v4_tlvs| bmp_v4_tlvs.as_ref().as_slice())
+        .unwrap_or_default();
+
+    if !matches!(bmp_value, BmpMessageValue::RouteMonitoring(_)) {
+        return WrongBmpMessageTypeError(bmp_value.get_type().into()).into();
+    }
+
+    let tlvs = bmp_v4_tlvs
+        .iter()
+        .map(|tlv| BmpRmTlv {
+            index: tlv.index,
+            pen: tlv.get_pen(),
+            type_: tlv.get_raw_type(),
+            len: tlv.get_value_len(),
+            val: tlv.get_value_ptr(),
+        })
+        .collect();
+
+    CResult::Ok(OwnedSlice::from_vec(tlvs))
+}
+
+/// The [bmp_log_tlv] decoded by NetGauze in a BMP message
+fn make_message_tlvs(
+    bmp_value: &BmpMessageValue,
+) -> Result<Vec<bmp_log_tlv>, WrongBmpMessageTypeError> {
+    match bmp_value {
+        BmpMessageValue::Initiation(init) => Ok(make_bmp_log_tlvs(init.information())),
+        BmpMessageValue::PeerUpNotification(peer_up) => {
+            Ok(make_bmp_log_tlvs(peer_up.information()))
         }
-        BmpMessageValue::Termination(term) => {
-            let mut tlvs = Vec::<bmp_log_tlv>::with_capacity(term.information().len());
-
-            for tlv in term.information() {
-                tlvs.push(bmp_log_tlv {
-                    pen: 0, // TODO support PEN when Netgauze supports bmp v4
-                    type_: tlv.get_type().into(),
-                    len: (tlv.len() - TerminationInformation::BASE_LENGTH) as u16,
-                    val: tlv.get_value_ptr(),
-                })
-            }
+        BmpMessageValue::Termination(term) => Ok(make_bmp_log_tlvs(term.information())),
+        // Route Monitoring TLVs only exist in BMP v4, NetGauze never has any
+        BmpMessageValue::RouteMonitoring(_) => Ok(Vec::new()),
+        _ => Err(WrongBmpMessageTypeError(bmp_value.get_type().into())),
+    }
+}
+
+/// Insert the `v4_tlvs` between the `tlvs` of the BMP v3 message at their [BmpV4Tlv::position]
+fn merge_v4_tlvs(tlvs: Vec<bmp_log_tlv>, v4_tlvs: &[BmpV4Tlv]) -> Vec<bmp_log_tlv> {
+    let mut result = Vec::with_capacity(tlvs.len() + v4_tlvs.len());
+    let mut v4_tlvs = v4_tlvs.iter().peekable();
 
-            tlvs
+    for (position, tlv) in tlvs.into_iter().enumerate() {
+        while let Some(v4_tlv) = v4_tlvs.next_if(|v4_tlv| v4_tlv.position <= position) {
+            result.push(make_bmp_log_tlv(v4_tlv));
         }
-        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
-    };
+        result.push(tlv);
+    }
+    result.extend(v4_tlvs.map(make_bmp_log_tlv));
 
-    let c_slice = OwnedSlice::from_vec(tlvs);
+    result
+}
 
-    CResult::Ok(c_slice)
+/// Make a [bmp_log_tlv] pointing to the value of `tlv`
+fn make_bmp_log_tlv<T: TlvExtension>(tlv: &T) -> bmp_log_tlv {
+    bmp_log_tlv {
+        pen: tlv.get_pen(),
+        type_: tlv.get_raw_type(),
+        len: tlv.get_value_len(),
+        val: tlv.get_value_ptr(),
+    }
 }
 
-free_cslice_t!(bmp_log_tlv);
+/// Make a [bmp_log_tlv] pointing to the value of each TLV in `information`
+pub(crate) fn make_bmp_log_tlvs<T: TlvExtension>(information: &[T]) -> Vec<bmp_log_tlv> {
+    information.iter().map(make_bmp_log_tlv).collect()
+}
 
 pub type BmpPeerHdrDataResult = CResult<bmp_data, WrongBmpMessageTypeError>;
 
@@ -142,6 +246,7 @@ pub unsafe extern "C" fn netgauze_bmp_peer_hdr_get_data(
         BmpMessageValue::PeerUpNotification(peer_up) => peer_up.peer_header(),
         BmpMessageValue::StatisticsReport(stats) => stats.peer_header(),
         BmpMessageValue::PeerDownNotification(peer_down) => peer_down.peer_header(),
+        BmpMessageValue::RouteMirroring(route_mirroring) => route_mirroring.peer_header(),
         _ => return WrongBmpMessageTypeError(bmp_msg.get_type().into()).into(),
     };
 
@@ -174,7 +279,9 @@ pub unsafe extern "C" fn netgauze_bmp_peer_hdr_get_data(
                     rd
                 })
                 .unwrap_or_else(rd_t::default_zeroed),
-            tlvs: ptr::null_mut(), // TODO only used in bmp RM, make a Rust function like for init and fill field in C
+            // Only used in BMP RM. This is a pmacct list that C fills from
+            // [netgauze_bmp_rm_get_tlvs], which also gives the NLRI index of the TLVs
+            tlvs: ptr::null_mut(),
         },
         tstamp: peer_hdr
             .timestamp()
@@ -203,3 +310,42 @@ pub unsafe extern "C" fn netgauze_bmp_route_monitor_get_bgp_update(
 
     CResult::Ok(Opaque::const_from_ref(bmp_rm.update_message()))
 }
+
+/// Raw payload of an experimental BMP Message (types 251 to 254).
+/// The `payload` pointer is borrowed from the BMP message.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpRawPayload {
+    pub message_type: u8,
+    pub payload_len: usize,
+    pub payload: *const u8,
+}
+
+pub type BmpRawPayloadResult = CResult<BmpRawPayload, WrongBmpMessageTypeError>;
+
+/// Get the [BmpRawPayload] of an experimental BMP Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_get_raw_payload(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpRawPayloadResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let payload = match bmp_value {
+        BmpMessageValue::Experimental251(payload)
+        | BmpMessageValue::Experimental252(payload)
+        | BmpMessageValue::Experimental253(payload)
+        | BmpMessageValue::Experimental254(payload) => payload,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    CResult::Ok(BmpRawPayload {
+        message_type: bmp_value.get_type().into(),
+        payload_len: payload.len(),
+        payload: payload.as_ptr(),
+    })
+}
@@ -0,0 +1,384 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::slice;
+
+use netgauze_bmp_pkt::PeerKey;
+
+use crate::capi::bmp::stats::{BmpPeerStats, BmpStatsCounterKey, BmpStatsStore};
+use crate::cslice::OwnedSlice;
+use crate::extensions::bmp_message::BmpRibType;
+use crate::opaque::Opaque;
+
+#[derive(Debug, Clone, Copy)]
+enum MetricType {
+    Counter,
+    Gauge,
+    Unknown,
+}
+
+impl MetricType {
+    fn name(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MetricFamily {
+    name: &'static str,
+    metric_type: MetricType,
+    help: &'static str,
+}
+
+impl MetricFamily {
+    const fn counter(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            metric_type: MetricType::Counter,
+            help,
+        }
+    }
+}
+
+const ROUTES: MetricFamily = MetricFamily {
+    name: "bmp_routes",
+    metric_type: MetricType::Gauge,
+    help: "Number of routes in a RIB of the BMP peer",
+};
+
+const ROUTES_PER_AFI_SAFI: MetricFamily = MetricFamily {
+    name: "bmp_routes_per_afi_safi",
+    metric_type: MetricType::Gauge,
+    help: "Number of routes of an AFI/SAFI in a RIB of the BMP peer",
+};
+
+const UNKNOWN_STAT: MetricFamily = MetricFamily {
+    name: "bmp_stat",
+    metric_type: MetricType::Unknown,
+    help: "BMP statistics counter of an experimental or unknown type",
+};
+
+/// [MetricFamily] of a BMP statistics counter type and RIB of the counter.
+///
+/// The RIB is the one of the per-peer header `peer_rib_type`, unless the type defines it.
+/// The Adj-RIB-In counters take the policy of the per-peer header, pre-policy by default.
+fn metric_family(
+    cnt_type: u16,
+    peer_rib_type: Option<BmpRibType>,
+) -> (MetricFamily, Option<BmpRibType>) {
+    let adj_rib_in = match peer_rib_type {
+        Some(BmpRibType::AdjRibInPostPolicy) => BmpRibType::AdjRibInPostPolicy,
+        _ => BmpRibType::AdjRibInPrePolicy,
+    };
+
+    let family = match cnt_type {
+        0 => MetricFamily::counter(
+            "bmp_prefixes_rejected_by_inbound_policy",
+            "Number of prefixes rejected by inbound policy",
+        ),
+        1 => MetricFamily::counter(
+            "bmp_duplicate_prefix_advertisements",
+            "Number of (known) duplicate prefix advertisements",
+        ),
+        2 => MetricFamily::counter(
+            "bmp_duplicate_withdraws",
+            "Number of (known) duplicate withdraws",
+        ),
+        3 => MetricFamily::counter(
+            "bmp_updates_invalidated_cluster_list_loop",
+            "Number of updates invalidated due to CLUSTER_LIST loop",
+        ),
+        4 => MetricFamily::counter(
+            "bmp_updates_invalidated_as_path_loop",
+            "Number of updates invalidated due to AS_PATH loop",
+        ),
+        5 => MetricFamily::counter(
+            "bmp_updates_invalidated_originator_id",
+            "Number of updates invalidated due to ORIGINATOR_ID",
+        ),
+        6 => MetricFamily::counter(
+            "bmp_updates_invalidated_as_confed_loop",
+            "Number of updates invalidated due to AS_CONFED loop",
+        ),
+        7 => return (ROUTES, Some(adj_rib_in)),
+        8 => return (ROUTES, Some(BmpRibType::LocalRib)),
+        9 => return (ROUTES_PER_AFI_SAFI, Some(adj_rib_in)),
+        10 => return (ROUTES_PER_AFI_SAFI, Some(BmpRibType::LocalRib)),
+        11 => MetricFamily::counter(
+            "bmp_updates_treat_as_withdraw",
+            "Number of updates subjected to treat-as-withdraw",
+        ),
+        12 => MetricFamily::counter(
+            "bmp_prefixes_treat_as_withdraw",
+            "Number of prefixes subjected to treat-as-withdraw",
+        ),
+        13 => MetricFamily::counter(
+            "bmp_duplicate_update_messages",
+            "Number of duplicate update messages received",
+        ),
+        14 => return (ROUTES, Some(BmpRibType::AdjRibOutPrePolicy)),
+        15 => return (ROUTES, Some(BmpRibType::AdjRibOutPostPolicy)),
+        16 => return (ROUTES_PER_AFI_SAFI, Some(BmpRibType::AdjRibOutPrePolicy)),
+        17 => return (ROUTES_PER_AFI_SAFI, Some(BmpRibType::AdjRibOutPostPolicy)),
+        _ => UNKNOWN_STAT,
+    };
+
+    (family, peer_rib_type)
+}
+
+fn rib_type_label(rib_type: BmpRibType) -> &'static str {
+    match rib_type {
+        BmpRibType::AdjRibInPrePolicy => "adj-rib-in-pre-policy",
+        BmpRibType::AdjRibInPostPolicy => "adj-rib-in-post-policy",
+        BmpRibType::LocalRib => "loc-rib",
+        BmpRibType::AdjRibOutPrePolicy => "adj-rib-out-pre-policy",
+        BmpRibType::AdjRibOutPostPolicy => "adj-rib-out-post-policy",
+    }
+}
+
+/// Format a Route Distinguisher as in RFC 4364 section 4.2
+fn rd_label(rd: u64) -> String {
+    let bytes = rd.to_be_bytes();
+    match u16::from_be_bytes([bytes[0], bytes[1]]) {
+        0 => format!(
+            "{}:{}",
+            u16::from_be_bytes([bytes[2], bytes[3]]),
+            u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]])
+        ),
+        1 => format!(
+            "{}.{}.{}.{}:{}",
+            bytes[2],
+            bytes[3],
+            bytes[4],
+            bytes[5],
+            u16::from_be_bytes([bytes[6], bytes[7]])
+        ),
+        2 => format!(
+            "{}:{}",
+            u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+            u16::from_be_bytes([bytes[6], bytes[7]])
+        ),
+        _ => format!("{:#018x}", rd),
+    }
+}
+
+/// Escape a label value as required by the OpenMetrics text format
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn write_labels(line: &mut String, labels: &[(&str, String)]) {
+    line.push('{');
+    for (index, (name, value)) in labels.iter().enumerate() {
+        if index != 0 {
+            line.push(',');
+        }
+        let _ = write!(line, "{}=\"{}\"", name, escape_label_value(value));
+    }
+    line.push('}');
+}
+
+fn peer_labels(router: &str, peer_key: &PeerKey) -> Vec<(&'static str, String)> {
+    let mut labels = vec![
+        ("router", router.to_string()),
+        (
+            "peer_address",
+            peer_key
+                .peer_address()
+                .map(|address| address.to_string())
+                .unwrap_or_default(),
+        ),
+        ("peer_as", peer_key.asn().to_string()),
+    ];
+
+    if let Some(rd) = peer_key.rd() {
+        labels.push(("peer_rd", rd_label(u64::from(rd))));
+    }
+
+    labels
+}
+
+/// Add the samples of the counters of a peer to the metric families they belong to
+fn collect_peer_samples(
+    families: &mut BTreeMap<&'static str, (MetricFamily, Vec<String>)>,
+    router: &str,
+    peer_key: &PeerKey,
+    peer_stats: &BmpPeerStats,
+) {
+    let peer_labels = peer_labels(router, peer_key);
+
+    for (key, value) in &peer_stats.counters {
+        let BmpStatsCounterKey {
+            cnt_type,
+            iana_afi,
+            iana_safi,
+        } = *key;
+
+        let (family, rib_type) = metric_family(cnt_type, peer_stats.rib_type);
+
+        let mut labels = peer_labels.clone();
+        if let Some(rib_type) = rib_type {
+            labels.push(("rib", rib_type_label(rib_type).to_string()));
+        }
+        if iana_afi != 0 {
+            labels.push(("afi", iana_afi.to_string()));
+            labels.push(("safi", iana_safi.to_string()));
+        }
+        if let MetricType::Unknown = family.metric_type {
+            labels.push(("type", cnt_type.to_string()));
+        }
+
+        let mut line = String::from(family.name);
+        if let MetricType::Counter = family.metric_type {
+            line.push_str("_total");
+        }
+        write_labels(&mut line, &labels);
+        let _ = write!(line, " {}", value);
+
+        families
+            .entry(family.name)
+            .or_insert_with(|| (family, Vec::new()))
+            .1
+            .push(line);
+    }
+}
+
+/// Render the latest statistics of the peers of the [BmpStatsStore]s of BMP routers
+/// in the OpenMetrics text format, ending with `# EOF`.
+///
+/// Use [crate::capi::bmp::stats::netgauze_bmp_stats_store_set_router] to set the `router` label
+///
+/// The returned [OwnedSlice<u8>] is not NUL-terminated
+/// and must be freed with [crate::capi::bgp::update::CSlice_free_u8]
+///
+/// # Safety
+/// `bmp_stats_stores` should point to `bmp_stats_stores_len` not null pointers to valid data,
+/// it may be null if `bmp_stats_stores_len` is 0
+///
+/// This function does not consume the `bmp_stats_stores` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stats_render_openmetrics(
+    bmp_stats_stores: *const *const Opaque<BmpStatsStore>,
+    bmp_stats_stores_len: usize,
+) -> OwnedSlice<u8> {
+    let bmp_stats_stores: &[*const Opaque<BmpStatsStore>] = if bmp_stats_stores_len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(bmp_stats_stores, bmp_stats_stores_len) }
+    };
+
+    // Samples of a metric family must be contiguous
+    let mut families = BTreeMap::new();
+    for bmp_stats_store in bmp_stats_stores {
+        let bmp_stats_store = unsafe { bmp_stats_store.as_ref().unwrap().as_ref() };
+
+        for (peer_key, peer_stats) in bmp_stats_store.peers.iter() {
+            collect_peer_samples(&mut families, &bmp_stats_store.router, peer_key, peer_stats);
+        }
+    }
+
+    let mut text = String::new();
+    for (family, mut samples) in families.into_values() {
+        samples.sort();
+
+        let _ = writeln!(text, "# TYPE {} {}", family.name, family.metric_type.name());
+        let _ = writeln!(text, "# HELP {} {}", family.name, family.help);
+        for sample in samples {
+            text.push_str(&sample);
+            text.push('\n');
+        }
+    }
+    text.push_str("# EOF\n");
+
+    OwnedSlice::from_vec(text.into_bytes())
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+
+    use crate::capi::bgp::update::CSlice_free_u8;
+    use crate::capi::bmp::openmetrics::netgauze_bmp_stats_render_openmetrics;
+    use crate::capi::bmp::parse::{netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free};
+    use crate::capi::bmp::stats::{
+        netgauze_bmp_stats_store_update, BmpStatsStore, CSlice_free_BmpStatsDelta,
+    };
+    use crate::capi::bmp::test_util::peer_message;
+    use crate::cresult::CResult;
+    use crate::opaque::Opaque;
+
+    /// BMP Statistics Report of the pre-policy Adj-RIB-In of peer 192.0.2.1 in AS 65000
+    fn statistics_report() -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 4];
+        // Duplicate withdraws
+        body.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 5]);
+        // Routes in Adj-RIB-In and Loc-RIB
+        body.extend_from_slice(&[0, 7, 0, 8, 0, 0, 0, 0, 0, 0, 0, 100]);
+        body.extend_from_slice(&[0, 8, 0, 8, 0, 0, 0, 0, 0, 0, 0, 80]);
+        // Routes in IPv4 Unicast Adj-RIB-In
+        body.extend_from_slice(&[0, 9, 0, 11, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 60]);
+
+        peer_message(1, 0, 0, 0, &body)
+    }
+
+    #[test]
+    fn test_render_openmetrics() {
+        let mut bmp_stats_store = Opaque::from(BmpStatsStore {
+            router: "r1".to_string(),
+            ..Default::default()
+        });
+
+        let message = statistics_report();
+        let result = unsafe {
+            netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, message.len() as u32)
+        };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        match unsafe { netgauze_bmp_stats_store_update(&mut bmp_stats_store, parsed.message, 0) } {
+            CResult::Ok(deltas) => CSlice_free_BmpStatsDelta(deltas),
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+        netgauze_bmp_parse_result_free(result);
+
+        let bmp_stats_stores = [&bmp_stats_store as *const Opaque<BmpStatsStore>];
+        let text = unsafe {
+            netgauze_bmp_stats_render_openmetrics(bmp_stats_stores.as_ptr(), bmp_stats_stores.len())
+        };
+        let rendered = String::from_utf8(unsafe { text.as_slice() }.to_vec()).unwrap();
+        CSlice_free_u8(text);
+
+        let peer = r#"router="r1",peer_address="192.0.2.1",peer_as="65000""#;
+        let expected = [
+            "# TYPE bmp_duplicate_withdraws counter".to_string(),
+            "# HELP bmp_duplicate_withdraws Number of (known) duplicate withdraws".to_string(),
+            format!(r#"bmp_duplicate_withdraws_total{{{peer},rib="adj-rib-in-pre-policy"}} 5"#),
+            "# TYPE bmp_routes gauge".to_string(),
+            "# HELP bmp_routes Number of routes in a RIB of the BMP peer".to_string(),
+            format!(r#"bmp_routes{{{peer},rib="adj-rib-in-pre-policy"}} 100"#),
+            format!(r#"bmp_routes{{{peer},rib="loc-rib"}} 80"#),
+            "# TYPE bmp_routes_per_afi_safi gauge".to_string(),
+            "# HELP bmp_routes_per_afi_safi Number of routes of an AFI/SAFI in a RIB of the BMP peer"
+                .to_string(),
+            format!(
+                r#"bmp_routes_per_afi_safi{{{peer},rib="adj-rib-in-pre-policy",afi="1",safi="1"}} 60"#
+            ),
+            "# EOF".to_string(),
+        ];
+        assert_eq!(rendered.lines().collect::<Vec<_>>(), expected);
+    }
+}
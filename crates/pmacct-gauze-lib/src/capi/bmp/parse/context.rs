@@ -1,14 +1,19 @@
+use std::net::IpAddr;
 use std::ptr::null_mut;
 
+use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
 use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
 use netgauze_bmp_pkt::{BmpMessageValue, PeerKey};
 
-use pmacct_gauze_bindings::bmp_peer;
+use pmacct_gauze_bindings::{bmp_peer, host_addr, rd_t, DefaultZeroed};
 
 use crate::context_cache::ContextCache;
+use crate::cslice::{OwnedSlice, RustFree};
 use crate::extensions::bmp_message::ExtendBmpMessage;
+use crate::extensions::context::ExtendBmpParsingContext;
+use crate::extensions::rd::{ExtendRdT, RdOriginType};
 use crate::opaque::Opaque;
-use crate::{free_rust_raw_box, make_default};
+use crate::{free_cslice_t, free_rust_raw_box, make_default};
 
 pub type BmpContextCacheKey = *mut bmp_peer;
 pub type BmpContextCache = ContextCache<BmpContextCacheKey, BmpParsingContext>;
@@ -76,10 +81,15 @@ pub unsafe extern "C" fn netgauze_bmp_context_cache_delete(
 make_default!(Opaque<BmpParsingContext>, Opaque_BmpParsingContext);
 free_rust_raw_box!(Opaque<BmpParsingContext>, Opaque_BmpParsingContext);
 
+/// Add the [BgpParsingContext] of the peer of a [BmpMessageValue] in [BmpParsingContext].
+///
+/// For a Peer Up Notification the capabilities negotiated in its OPEN messages are used,
+/// otherwise a default [BgpParsingContext] is added.
+///
 /// This function does not consume the `bmp_parsing_context` and `bmp_message_value_opaque` pointers
 #[allow(clippy::not_unsafe_ptr_arg_deref)] // The pointer is not null by contract
 #[no_mangle]
-pub extern "C" fn netgauze_bmp_parsing_context_add_default(
+pub extern "C" fn netgauze_bmp_parsing_context_add_peer(
     bmp_parsing_context: *mut Opaque<BmpParsingContext>,
     bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
 ) {
@@ -87,8 +97,49 @@ pub extern "C" fn netgauze_bmp_parsing_context_add_default(
     let bmp_message_value = unsafe { bmp_message_value_opaque.as_ref().unwrap() };
     let peer_header = bmp_message_value.as_ref().get_peer_header().unwrap();
 
+    if let BmpMessageValue::PeerUpNotification(peer_up) = bmp_message_value.as_ref() {
+        bmp_parsing_context.add_peer_from_peer_up(peer_up);
+        return;
+    }
+
+    let key = PeerKey::from_peer_header(peer_header);
+    bmp_parsing_context.add_default_peer(key);
+}
+
+/// Former name of [netgauze_bmp_parsing_context_add_peer], kept for existing callers
+///
+/// This function does not consume the `bmp_parsing_context` and `bmp_message_value_opaque` pointers
+#[allow(clippy::not_unsafe_ptr_arg_deref)] // The pointer is not null by contract
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_parsing_context_add_default(
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) {
+    netgauze_bmp_parsing_context_add_peer(bmp_parsing_context, bmp_message_value_opaque)
+}
+
+/// Get a mutable pointer to the [BgpParsingContext] of the peer of a [BmpMessageValue]
+/// in [BmpParsingContext], or null if the peer is unknown
+///
+/// This function does not consume the `bmp_parsing_context` and `bmp_message_value_opaque` pointers
+#[allow(clippy::not_unsafe_ptr_arg_deref)] // The pointer is not null by contract
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_parsing_context_get_peer(
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> *mut Opaque<BgpParsingContext> {
+    let bmp_parsing_context = unsafe { bmp_parsing_context.as_mut().unwrap().as_mut() };
+    let bmp_message_value = unsafe { bmp_message_value_opaque.as_ref().unwrap() };
+    let peer_header = match bmp_message_value.as_ref().get_peer_header() {
+        Some(peer_header) => peer_header,
+        None => return null_mut(),
+    };
+
     let key = PeerKey::from_peer_header(peer_header);
-    bmp_parsing_context.add_peer(key, Default::default());
+    match bmp_parsing_context.get_peer(&key) {
+        Some(parsing_context) => Opaque::mut_from_mut(parsing_context),
+        None => null_mut(),
+    }
 }
 
 /// This function does not consume the `bmp_parsing_context` pointer
@@ -106,14 +157,126 @@ pub extern "C" fn netgauze_bmp_parsing_context_delete(
     bmp_parsing_context.delete_peer(&key);
 }
 
+/// Last time each BMP peer was seen in a parsed message, in seconds.
+/// The time is the [crate::capi::bmp::parse::BmpParseOptions::now] given to the parser.
+pub type BmpPeerActivity = ContextCache<PeerKey, u64>;
+
+free_rust_raw_box!(Opaque<BmpPeerActivity>, Opaque_BmpPeerActivity);
+make_default!(Opaque<BmpPeerActivity>, Opaque_BmpPeerActivity);
+
+/// [PeerKey] of a BMP peer, in pmacct types
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpPeerKey {
+    pub peer_ip: host_addr,
+    pub peer_asn: u32,
+    pub bgp_id: host_addr,
+    pub rd: rd_t,
+}
+
+impl From<&PeerKey> for BmpPeerKey {
+    fn from(peer_key: &PeerKey) -> Self {
+        Self {
+            peer_ip: peer_key
+                .peer_address()
+                .as_ref()
+                .map(host_addr::from)
+                .unwrap_or_else(host_addr::default_ipv4),
+            peer_asn: peer_key.asn(),
+            bgp_id: host_addr::from(&peer_key.bgp_id()),
+            rd: peer_key
+                .rd()
+                .map(|rd| {
+                    let mut rd = rd_t::from(rd);
+                    rd.set_pmacct_rd_origin(RdOriginType::BMP);
+                    rd
+                })
+                .unwrap_or_else(rd_t::default_zeroed),
+        }
+    }
+}
+
+/// Addresses are compared as [IpAddr] and RDs field by field, with their origin
+impl PartialEq for BmpPeerKey {
+    fn eq(&self, other: &Self) -> bool {
+        let address = |address: &host_addr| IpAddr::try_from(address).ok();
+        let rd = |rd: &rd_t| (rd.type_, rd.as_, rd.val);
+
+        address(&self.peer_ip) == address(&other.peer_ip)
+            && self.peer_asn == other.peer_asn
+            && address(&self.bgp_id) == address(&other.bgp_id)
+            && rd(&self.rd) == rd(&other.rd)
+    }
+}
+
+/// A BMP peer removed by [netgauze_bmp_peer_activity_sweep]
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpSweptPeer {
+    pub peer: BmpPeerKey,
+    /// Seconds since the peer was last seen
+    pub idle_secs: u64,
+}
+
+free_cslice_t!(BmpSweptPeer);
+
+/// Remove from [BmpPeerActivity] and [BmpParsingContext] the peers
+/// that have not been seen for more than `max_idle_secs` seconds,
+/// `now` being the current time in seconds
+///
+/// The returned [OwnedSlice<BmpSweptPeer>] must be freed with [CSlice_free_BmpSweptPeer]
+///
+/// # Safety
+/// `bmp_peer_activity` should be not null and point to valid data
+/// `bmp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_peer_activity` and `bmp_parsing_context` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_activity_sweep(
+    bmp_peer_activity: *mut Opaque<BmpPeerActivity>,
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+    max_idle_secs: u64,
+    now: u64,
+) -> OwnedSlice<BmpSweptPeer> {
+    let bmp_peer_activity = unsafe { bmp_peer_activity.as_mut().unwrap().as_mut() };
+    let bmp_parsing_context = unsafe { bmp_parsing_context.as_mut().unwrap().as_mut() };
+
+    let mut swept = Vec::new();
+    bmp_peer_activity.retain(|peer_key, last_seen| {
+        let idle_secs = now.saturating_sub(*last_seen);
+        if idle_secs <= max_idle_secs {
+            return true;
+        }
+
+        bmp_parsing_context.delete_peer(peer_key);
+        swept.push(BmpSweptPeer {
+            peer: BmpPeerKey::from(peer_key),
+            idle_secs,
+        });
+
+        false
+    });
+
+    OwnedSlice::from_vec(swept)
+}
+
 #[cfg(test)]
 mod test {
+    use std::ffi::c_char;
+
+    use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
+
     use pmacct_gauze_bindings::bmp_peer;
 
     use crate::capi::bmp::parse::{
-        netgauze_bmp_context_cache_set, netgauze_make_Opaque_BmpContextCache,
-        netgauze_make_Opaque_BmpParsingContext,
+        netgauze_bmp_context_cache_set, netgauze_bmp_parse_packet_with_options,
+        netgauze_bmp_parse_result_free, netgauze_bmp_peer_activity_sweep,
+        netgauze_make_Opaque_BmpContextCache, netgauze_make_Opaque_BmpParsingContext,
+        BmpParseOptions, BmpPeerActivity, CSlice_free_BmpSweptPeer,
     };
+    use crate::capi::bmp::test_util::{end_of_rib, peer_message};
+    use crate::cresult::CResult;
+    use crate::opaque::Opaque;
 
     #[test]
     fn test_leak() {
@@ -125,4 +288,51 @@ mod test {
             netgauze_bmp_context_cache_set(cache, &mut peer, ctx);
         }
     }
+
+    /// BMP Route Monitoring of peer 192.0.2.1 in AS 65000 with an IPv4 End-of-RIB
+    fn route_monitoring() -> Vec<u8> {
+        peer_message(0, 0, 0, 0, &end_of_rib())
+    }
+
+    #[test]
+    fn test_peer_activity_sweep() {
+        let mut peer_activity = Opaque::from(BmpPeerActivity::default());
+        let mut parsing_context = Opaque::from(BmpParsingContext::default());
+
+        let message = route_monitoring();
+        let result = unsafe {
+            netgauze_bmp_parse_packet_with_options(
+                message.as_ptr() as *const c_char,
+                message.len() as u32,
+                &mut parsing_context,
+                BmpParseOptions {
+                    peer_activity: &mut peer_activity,
+                    now: 100,
+                    ..Default::default()
+                },
+            )
+        };
+        assert!(matches!(result, CResult::Ok(_)));
+        netgauze_bmp_parse_result_free(result);
+        assert_eq!(
+            peer_activity.as_ref().values().collect::<Vec<_>>(),
+            vec![&100]
+        );
+
+        let swept = unsafe {
+            netgauze_bmp_peer_activity_sweep(&mut peer_activity, &mut parsing_context, 60, 160)
+        };
+        assert_eq!(swept.len, 0);
+        CSlice_free_BmpSweptPeer(swept);
+
+        let swept = unsafe {
+            netgauze_bmp_peer_activity_sweep(&mut peer_activity, &mut parsing_context, 60, 161)
+        };
+        let swept_peers = unsafe { swept.as_slice() };
+        assert_eq!(swept_peers.len(), 1);
+        assert_eq!(swept_peers[0].peer.peer_asn, 65000);
+        assert_eq!(swept_peers[0].idle_secs, 61);
+        CSlice_free_BmpSweptPeer(swept);
+        assert!(peer_activity.as_ref().is_empty());
+    }
 }
@@ -1,7 +1,11 @@
 pub use context::*;
 pub use parse::*;
+pub use v4::*;
 
 mod context;
 
 #[allow(clippy::module_inception)]
 mod parse;
+
+/// BMP v4 (draft-ietf-grow-bmp-tlv) messages decoded as BMP v3 for NetGauze
+mod v4;
@@ -1,25 +1,34 @@
 use std::error::Error;
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
-use std::slice;
+use std::{ptr, slice};
 
 use c_str_macro::c_str;
 use libc::c_char;
 use netgauze_bgp_pkt::wire::serializer::nlri::RouteDistinguisherWritingError;
 use netgauze_bgp_pkt::wire::serializer::IpAddrWritingError;
 use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
-use netgauze_bmp_pkt::{BmpMessage, BmpMessageValue};
-use netgauze_parse_utils::{ReadablePduWithOneInput, Span};
+use netgauze_bmp_pkt::{BmpMessage, BmpMessageValue, PeerKey};
+use netgauze_parse_utils::{LocatedParsingError, ReadablePduWithOneInput, Span};
 use nom::Offset;
 
 use pmacct_gauze_bindings::{bmp_common_hdr, bmp_peer_hdr};
 
+use crate::capi::bmp::parse::{decode_bmp_v4, BmpPeerActivity, BmpV4Tlvs, BMP_VERSION_4};
+use crate::capi::bmp::peer_table::{update_peer_table, BmpPeerTable};
+use crate::capi::bmp::stats::BmpStatsStore;
 use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::capi::parse_error::{
+    find_tlv_type, find_update_attribute_type, missing_bytes, needed_bytes, ParseErrorDetails,
+    ParseErrorKind,
+};
 use crate::coption::COption;
 use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
 use crate::extensions::bmp_message::ExtendBmpMessage;
+use crate::extensions::context::ExtendBmpParsingContext;
 use crate::opaque::Opaque;
-use crate::{drop_rust_raw_box, make_rust_raw_box_pointer};
+use crate::{drop_rust_raw_box, free_cslice_t_with_item_free, make_rust_raw_box_pointer};
 
 /// This structure must be manually freed using [bmp_parse_result_free]
 pub type BmpParseResult = CResult<ParsedBmp, BmpParseError>;
@@ -29,7 +38,16 @@ pub type BmpParseResult = CResult<ParsedBmp, BmpParseError>;
 pub enum BmpParseError {
     RouteDistinguisher,
     IpAddr,
-    NetgauzeBmpError(*mut c_char),
+    NetgauzeBmpError {
+        /// Human-readable error for logs
+        err_str: *mut c_char,
+        details: ParseErrorDetails,
+    },
+    /// The buffer does not contain a whole message yet.
+    /// At least `needed` more bytes must be read before retrying, 0 if unknown.
+    NeedMoreData {
+        needed: u32,
+    },
     StringConversion,
     WrongBmpMessageType(WrongBmpMessageTypeError),
 }
@@ -41,6 +59,9 @@ pub struct ParsedBmp {
     common_header: bmp_common_hdr,
     peer_header: COption<bmp_peer_hdr>,
     pub message: *mut Opaque<BmpMessageValue>,
+    /// TLVs of a BMP v4 message that NetGauze does not decode, null for BMP v3 messages.
+    /// See [crate::capi::bmp::netgauze_bmp_get_tlvs_v4].
+    pub v4_tlvs: *mut Opaque<BmpV4Tlvs>,
 }
 
 /// Parse a [BmpMessage] from a buffer with given length without context
@@ -71,22 +92,148 @@ pub unsafe extern "C" fn netgauze_bmp_parse_packet_with_context(
     buffer: *const c_char,
     buf_len: u32,
     bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+) -> BmpParseResult {
+    netgauze_bmp_parse_packet_with_options(
+        buffer,
+        buf_len,
+        bmp_parsing_context,
+        BmpParseOptions::default(),
+    )
+}
+
+/// Options changing how [netgauze_bmp_parse_packet_with_options] maintains the contexts
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpParseOptions {
+    /// Remove the [netgauze_bgp_pkt::wire::deserializer::BgpParsingContext] of a peer
+    /// from the [BmpParsingContext] when its Peer Down is parsed
+    pub drop_context_on_peer_down: bool,
+    /// If not null, record when each peer is seen, see [netgauze_bmp_peer_activity_sweep]
+    pub peer_activity: *mut Opaque<BmpPeerActivity>,
+    /// If not null, remove the statistics of a peer when its Peer Down is parsed,
+    /// see [crate::capi::bmp::stats::netgauze_bmp_stats_store_update]
+    pub stats_store: *mut Opaque<BmpStatsStore>,
+    /// If not null, update the [BmpPeerTable] with each parsed message
+    pub peer_table: *mut Opaque<BmpPeerTable>,
+    /// Current time in seconds, recorded in `peer_activity` and `peer_table`
+    /// for the peer of the message
+    pub now: u64,
+}
+
+impl Default for BmpParseOptions {
+    fn default() -> Self {
+        Self {
+            drop_context_on_peer_down: false,
+            peer_activity: ptr::null_mut(),
+            stats_store: ptr::null_mut(),
+            peer_table: ptr::null_mut(),
+            now: 0,
+        }
+    }
+}
+
+/// Parse a [BmpMessage] from a buffer with given length using a given context and [BmpParseOptions]
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buf_len`
+/// `bmp_parsing_context` should be not null and point to valid data
+/// `options.peer_activity` should be null or point to valid data
+/// `options.stats_store` should be null or point to valid data
+/// `options.peer_table` should be null or point to valid data
+///
+/// This function does not consume the `bmp_parsing_context`, `options.peer_activity`,
+/// `options.stats_store` and `options.peer_table` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_parse_packet_with_options(
+    buffer: *const c_char,
+    buf_len: u32,
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+    options: BmpParseOptions,
 ) -> BmpParseResult {
     let s = unsafe { slice::from_raw_parts(buffer as *const u8, buf_len as usize) };
+    match missing_bytes(
+        s,
+        BMP_COMMON_HEADER_LENGTH,
+        BMP_MAX_MESSAGE_LENGTH as usize,
+        |header| u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize,
+    ) {
+        Ok(None) => {}
+        Ok(Some(needed)) => return BmpParseError::NeedMoreData { needed }.into(),
+        Err(length) => return message_too_long(length, s.get(5).copied()),
+    }
+
+    // NetGauze only decodes BMP v3: a BMP v4 message is parsed from its v3 rewrite,
+    // so the offsets of the errors found by NetGauze are in the rewritten message
+    let v4_message = match s[0] {
+        BMP_VERSION_4 => {
+            let length = u32::from_be_bytes([s[1], s[2], s[3], s[4]]);
+            match decode_bmp_v4(&s[..length as usize]) {
+                Ok(v4_message) => Some((length, v4_message)),
+                Err(offset) => return malformed_v4_message(offset, s.get(5).copied()),
+            }
+        }
+        _ => None,
+    };
+    let s = match &v4_message {
+        Some((_, v4_message)) => v4_message.v3_message.as_slice(),
+        None => s,
+    };
+
     let span = Span::new(s);
 
     let bmp_parsing_context = unsafe { bmp_parsing_context.as_mut().unwrap() };
 
     let result = BmpMessage::from_wire(span, &mut bmp_parsing_context.as_mut());
     if let Ok((end_span, msg)) = result {
-        let read_bytes = span.offset(&end_span) as u32;
+        let read_bytes = match &v4_message {
+            Some((length, _)) => *length,
+            None => span.offset(&end_span) as u32,
+        };
 
         bmp_parsing_context.as_mut().update(&msg);
 
+        // Install the capabilities negotiated in the OPEN messages for the new peer
+        if let BmpMessage::V3(BmpMessageValue::PeerUpNotification(peer_up)) = &msg {
+            bmp_parsing_context.as_mut().add_peer_from_peer_up(peer_up);
+        }
+
+        if let Some(peer_table) = unsafe { options.peer_table.as_mut() } {
+            match &msg {
+                BmpMessage::V3(value) => update_peer_table(peer_table.as_mut(), value, options.now),
+            }
+        }
+
+        if let Some(peer_key) = msg.get_peer_header().map(PeerKey::from_peer_header) {
+            let peer_activity = unsafe { options.peer_activity.as_mut() };
+            let peer_activity = peer_activity.map(|peer_activity| peer_activity.as_mut());
+
+            let is_peer_down = matches!(
+                msg,
+                BmpMessage::V3(BmpMessageValue::PeerDownNotification(_))
+            );
+            if is_peer_down {
+                if let Some(stats_store) = unsafe { options.stats_store.as_mut() } {
+                    stats_store.as_mut().remove_peer(&peer_key);
+                }
+            }
+
+            if options.drop_context_on_peer_down && is_peer_down {
+                bmp_parsing_context.as_mut().delete_peer(&peer_key);
+                if let Some(peer_activity) = peer_activity {
+                    peer_activity.remove(&peer_key);
+                }
+            } else if let Some(peer_activity) = peer_activity {
+                peer_activity.insert(peer_key, options.now);
+            }
+        }
+
         return BmpParseResult::Ok(ParsedBmp {
             read_bytes,
             common_header: bmp_common_hdr {
-                version: msg.get_version().into(),
+                version: match v4_message {
+                    Some(_) => BMP_VERSION_4,
+                    None => msg.get_version().into(),
+                },
                 len: read_bytes,
                 type_: msg.get_type().into(),
             },
@@ -97,18 +244,234 @@ pub unsafe extern "C" fn netgauze_bmp_parse_packet_with_context(
             message: make_rust_raw_box_pointer(match msg {
                 BmpMessage::V3(value) => Opaque::from(value),
             }),
+            v4_tlvs: match v4_message {
+                Some((_, v4_message)) => make_rust_raw_box_pointer(Opaque::from(v4_message.tlvs)),
+                None => ptr::null_mut(),
+            },
         });
     }
 
     let err = result.err().unwrap();
-    // TODO special EoF error
+
+    let details = {
+        let message_type = s.get(5).copied();
+        match &err {
+            nom::Err::Incomplete(needed) => {
+                return BmpParseError::NeedMoreData {
+                    needed: needed_bytes(needed),
+                }
+                .into()
+            }
+            nom::Err::Error(err) | nom::Err::Failure(err) => {
+                let offset = err.span().location_offset();
+                ParseErrorDetails::new(message_kind(offset), offset, message_type)
+                    .with_attribute_type(find_bmp_attribute_type(s, message_type, offset))
+            }
+        }
+    };
 
     let netgauze_error = match CString::new(err.to_string()) {
         Ok(str) => str,
         Err(_) => return BmpParseError::StringConversion.into(),
     };
 
-    BmpParseError::NetgauzeBmpError(netgauze_error.into_raw()).into()
+    BmpParseError::NetgauzeBmpError {
+        err_str: netgauze_error.into_raw(),
+        details,
+    }
+    .into()
+}
+
+/// Length of the BMP v3 common header (version, length, type)
+pub(crate) const BMP_COMMON_HEADER_LENGTH: usize = 6;
+
+/// Largest BMP message accepted by the parser.
+///
+/// RFC 7854 does not bound the length field, but a message only carries a few BGP messages
+/// of at most 65535 bytes (RFC 8654) and some TLVs.
+pub const BMP_MAX_MESSAGE_LENGTH: u32 = 1 << 20;
+
+/// Length of the BMP v3 per-peer header
+pub(crate) const BMP_PEER_HEADER_LENGTH: usize = 42;
+
+/// Error for a header announcing more than [BMP_MAX_MESSAGE_LENGTH] bytes
+fn message_too_long(length: usize, message_type: Option<u8>) -> BmpParseResult {
+    bmp_error(
+        format!("BMP message length {length} exceeds the maximum of {BMP_MAX_MESSAGE_LENGTH}"),
+        ParseErrorDetails::new(ParseErrorKind::BmpMessageHeader, 1, message_type),
+    )
+}
+
+/// Error for a BMP v4 message that cannot be rewritten as BMP v3, see [decode_bmp_v4]
+fn malformed_v4_message(offset: usize, message_type: Option<u8>) -> BmpParseResult {
+    bmp_error(
+        format!("malformed BMP v4 message at offset {offset}"),
+        ParseErrorDetails::new(message_kind(offset), offset, message_type),
+    )
+}
+
+/// [BmpParseError::NetgauzeBmpError] for an error found before NetGauze parses the message
+fn bmp_error(err_str: String, details: ParseErrorDetails) -> BmpParseResult {
+    let err_str = match CString::new(err_str) {
+        Ok(str) => str,
+        Err(_) => return BmpParseError::StringConversion.into(),
+    };
+
+    BmpParseError::NetgauzeBmpError {
+        err_str: err_str.into_raw(),
+        details,
+    }
+    .into()
+}
+
+/// [ParseErrorKind] of an error at `offset` in a BMP message
+fn message_kind(offset: usize) -> ParseErrorKind {
+    if offset < BMP_COMMON_HEADER_LENGTH {
+        ParseErrorKind::BmpMessageHeader
+    } else {
+        ParseErrorKind::BmpMessage
+    }
+}
+
+/// Find the BGP path attribute type (Route Monitoring) or the TLV type
+/// (Initiation, Termination, Peer Up) containing `offset` in a raw BMP message
+fn find_bmp_attribute_type(message: &[u8], message_type: Option<u8>, offset: usize) -> Option<u16> {
+    let peer_header_end = BMP_COMMON_HEADER_LENGTH + BMP_PEER_HEADER_LENGTH;
+    let bgp_message_length = |at: usize| -> Option<usize> {
+        let bytes = message.get(at + 16..at + 18)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+
+    match message_type? {
+        // Route Monitoring
+        0 if offset >= peer_header_end => {
+            find_update_attribute_type(&message[peer_header_end..], offset - peer_header_end)
+                .map(u16::from)
+        }
+        // Peer Up Notification: local address, ports and the two OPEN messages precede the TLVs
+        3 => {
+            let sent_open = peer_header_end + 20;
+            let received_open = sent_open + bgp_message_length(sent_open)?;
+            let tlvs = received_open + bgp_message_length(received_open)?;
+            find_tlv_type(message, tlvs, offset)
+        }
+        // Initiation and Termination
+        4 | 5 => find_tlv_type(message, BMP_COMMON_HEADER_LENGTH, offset),
+        _ => None,
+    }
+}
+
+/// All the whole BMP messages parsed from a buffer.
+///
+/// This structure must be freed using [netgauze_bmp_parsed_buffer_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpParsedBuffer {
+    pub messages: OwnedSlice<ParsedBmp>,
+    /// Number of bytes at the end of the buffer that were not consumed.
+    /// They must be kept and completed with the next read.
+    pub leftover_bytes: u32,
+    /// Error that stopped the parsing. The leftover bytes start with the erroneous message.
+    pub error: COption<BmpParseError>,
+}
+
+impl RustFree for ParsedBmp {
+    fn rust_free(self) {
+        drop_rust_raw_box(self.message);
+        if !self.v4_tlvs.is_null() {
+            drop_rust_raw_box(self.v4_tlvs);
+        }
+    }
+}
+
+free_cslice_t_with_item_free!(ParsedBmp);
+
+/// Parse all the whole [BmpMessage] contained in a buffer using a given context.
+///
+/// The context is updated between messages, so a Peer Up is taken into account
+/// for the following messages of the same buffer.
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buf_len`
+/// `bmp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `buffer` and `bmp_parsing_context` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_parse_buffer(
+    buffer: *const c_char,
+    buf_len: u32,
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+) -> BmpParsedBuffer {
+    netgauze_bmp_parse_buffer_with_options(
+        buffer,
+        buf_len,
+        bmp_parsing_context,
+        BmpParseOptions::default(),
+    )
+}
+
+/// Parse all the whole [BmpMessage] contained in a buffer using a given context
+/// and [BmpParseOptions], see [netgauze_bmp_parse_buffer]
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buf_len`
+/// `bmp_parsing_context` should be not null and point to valid data
+/// `options.peer_activity` should be null or point to valid data
+/// `options.stats_store` should be null or point to valid data
+/// `options.peer_table` should be null or point to valid data
+///
+/// This function does not consume the `buffer`, `bmp_parsing_context`, `options.peer_activity`,
+/// `options.stats_store` and `options.peer_table` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_parse_buffer_with_options(
+    buffer: *const c_char,
+    buf_len: u32,
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+    options: BmpParseOptions,
+) -> BmpParsedBuffer {
+    let mut messages = Vec::new();
+    let mut offset = 0u32;
+
+    let error = loop {
+        if offset == buf_len {
+            break None;
+        }
+
+        let result = netgauze_bmp_parse_packet_with_options(
+            buffer.add(offset as usize),
+            buf_len - offset,
+            bmp_parsing_context,
+            options.clone(),
+        );
+
+        match result {
+            CResult::Ok(parsed) => {
+                if parsed.read_bytes == 0 {
+                    parsed.rust_free();
+                    break None;
+                }
+
+                offset += parsed.read_bytes;
+                messages.push(parsed);
+            }
+            CResult::Err(BmpParseError::NeedMoreData { .. }) => break None,
+            CResult::Err(err) => break Some(err),
+        }
+    };
+
+    BmpParsedBuffer {
+        messages: OwnedSlice::from_vec(messages),
+        leftover_bytes: buf_len - offset,
+        error: error.into(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_parsed_buffer_free(value: BmpParsedBuffer) {
+    value.messages.rust_free();
+    if let COption::Some(err) = value.error {
+        netgauze_bmp_parse_result_free(CResult::Err(err));
+    }
 }
 
 impl Display for BmpParseError {
@@ -126,7 +489,11 @@ impl BmpParseError {
                 "BmpParseError::RouteDistinguisher"
             }
             .as_ptr(),
-            BmpParseError::NetgauzeBmpError(err) => *err as *const c_char,
+            BmpParseError::NetgauzeBmpError { err_str, .. } => *err_str as *const c_char,
+            BmpParseError::NeedMoreData { .. } => c_str! {
+                "BmpParseError::NeedMoreData"
+            }
+            .as_ptr(),
             BmpParseError::StringConversion => c_str! {
                 "BmpParseError::StringConversion"
             }
@@ -166,20 +533,164 @@ pub extern "C" fn netgauze_bmp_parse_error_str(error: BmpParseError) -> *const c
     error.as_str_ptr()
 }
 
+/// Get the [ParseErrorDetails] of a [BmpParseError], if any
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_parse_error_details(
+    error: BmpParseError,
+) -> COption<ParseErrorDetails> {
+    match error {
+        BmpParseError::NetgauzeBmpError { details, .. } => COption::Some(details),
+        BmpParseError::RouteDistinguisher
+        | BmpParseError::IpAddr
+        | BmpParseError::NeedMoreData { .. }
+        | BmpParseError::StringConversion
+        | BmpParseError::WrongBmpMessageType(_) => COption::None,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn netgauze_bmp_parse_result_free(value: BmpParseResult) {
     match value {
-        CResult::Ok(parse_ok) => {
-            drop_rust_raw_box(parse_ok.message);
-        }
+        CResult::Ok(parse_ok) => parse_ok.rust_free(),
         CResult::Err(parse_error) => match parse_error {
-            BmpParseError::NetgauzeBmpError(err) => unsafe {
-                drop(CString::from_raw(err));
+            BmpParseError::NetgauzeBmpError { err_str, .. } => unsafe {
+                drop(CString::from_raw(err_str));
             },
             BmpParseError::RouteDistinguisher
+            | BmpParseError::NeedMoreData { .. }
             | BmpParseError::StringConversion
             | BmpParseError::IpAddr
             | BmpParseError::WrongBmpMessageType(_) => {}
         },
     };
 }
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+    use std::slice;
+
+    use crate::capi::bmp::parse::{
+        netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free, ParsedBmp,
+    };
+    use crate::capi::bmp::test_util::{bmp_message, end_of_rib, peer_header, PEER_ADDRESS};
+    use crate::capi::bmp::{
+        netgauze_bmp_get_tlvs, netgauze_bmp_get_tlvs_v4, netgauze_bmp_rm_get_tlvs,
+        netgauze_bmp_route_monitor_get_bgp_update, CSlice_free_BmpRmTlv, CSlice_free_bmp_log_tlv,
+    };
+    use crate::cresult::CResult;
+
+    /// PEN, type and length of the TLVs returned by [netgauze_bmp_get_tlvs_v4]
+    fn tlv_headers(parsed: &ParsedBmp) -> Vec<(u32, u16, u16)> {
+        let tlvs = match unsafe { netgauze_bmp_get_tlvs_v4(parsed.message, parsed.v4_tlvs) } {
+            CResult::Ok(tlvs) => tlvs,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        let tlv_headers = unsafe { tlvs.as_slice() }
+            .iter()
+            .map(|tlv| (tlv.pen, tlv.type_, tlv.len))
+            .collect();
+
+        CSlice_free_bmp_log_tlv(tlvs);
+        tlv_headers
+    }
+
+    #[test]
+    fn test_parse_v4_initiation() {
+        // sysDescr, enterprise TLV 1 of PEN 8284 and sysName
+        let mut body = vec![0, 1, 0, 2, b'p', b'm'];
+        body.extend_from_slice(&[0x80, 1, 0, 5, 0, 0, 0x20, 0x5c, b'x']);
+        body.extend_from_slice(&[0, 2, 0, 2, b'r', b'1']);
+        let message = bmp_message(4, 4, &body);
+        let len = message.len() as u32;
+
+        let result = unsafe { netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, len) };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        assert_eq!(parsed.read_bytes, len);
+        assert_eq!(parsed.common_header.version, 4);
+        assert_eq!(parsed.common_header.len, len);
+        // In the order they were received
+        assert_eq!(
+            tlv_headers(parsed),
+            vec![(0, 1, 2), (8284, 1, 1), (0, 2, 2)]
+        );
+
+        // Without the BMP v4 TLVs
+        match unsafe { netgauze_bmp_get_tlvs(parsed.message) } {
+            CResult::Ok(tlvs) => {
+                assert_eq!(tlvs.len, 2);
+                CSlice_free_bmp_log_tlv(tlvs);
+            }
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+
+        // Only Route Monitoring Messages have Route Monitoring TLVs
+        assert!(matches!(
+            unsafe { netgauze_bmp_rm_get_tlvs(parsed.message, parsed.v4_tlvs) },
+            CResult::Err(_)
+        ));
+
+        netgauze_bmp_parse_result_free(result);
+    }
+
+    #[test]
+    fn test_parse_v4_route_monitoring() {
+        // Per-peer header of a global instance peer 192.0.2.1 in AS 65000
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        // Group TLV of index 0 with NLRI indexes 1 and 2
+        body.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 1, 0, 2]);
+        // Path Marking TLV of index 1, path status and reason code
+        body.extend_from_slice(&[0, 4, 0, 6, 0, 1, 0, 0, 0, 2, 0, 1]);
+        // Enterprise TLV 7 of PEN 8284
+        body.extend_from_slice(&[0x80, 7, 0, 6, 0, 0, 0, 0, 0x20, 0x5c, 0xca, 0xfe]);
+        // BGP PDU TLV with an IPv4 End-of-RIB
+        body.extend_from_slice(&[0, 1, 0, 23, 0, 0]);
+        body.extend_from_slice(&end_of_rib());
+        let message = bmp_message(4, 0, &body);
+        let len = message.len() as u32;
+
+        let result = unsafe { netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, len) };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        assert_eq!(parsed.read_bytes, len);
+        assert_eq!(parsed.common_header.version, 4);
+        assert_eq!(
+            tlv_headers(parsed),
+            vec![(0, 2, 4), (0, 4, 6), (8284, 7, 2)]
+        );
+        assert!(matches!(
+            unsafe { netgauze_bmp_route_monitor_get_bgp_update(parsed.message) },
+            CResult::Ok(_)
+        ));
+
+        // With the index of the NLRI they apply to
+        let tlvs = match unsafe { netgauze_bmp_rm_get_tlvs(parsed.message, parsed.v4_tlvs) } {
+            CResult::Ok(tlvs) => tlvs,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        let rm_tlvs: Vec<_> = unsafe { tlvs.as_slice() }
+            .iter()
+            .map(|tlv| {
+                let value =
+                    unsafe { slice::from_raw_parts(tlv.val as *const u8, tlv.len as usize) };
+                (tlv.index, tlv.pen, tlv.type_, value.to_vec())
+            })
+            .collect();
+        CSlice_free_BmpRmTlv(tlvs);
+        assert_eq!(
+            rm_tlvs,
+            vec![
+                (0, 0, 2, vec![0, 1, 0, 2]),
+                (1, 0, 4, vec![0, 0, 0, 2, 0, 1]),
+                (0, 8284, 7, vec![0xca, 0xfe]),
+            ]
+        );
+
+        netgauze_bmp_parse_result_free(result);
+    }
+}
@@ -0,0 +1,409 @@
+use std::ffi::c_void;
+
+use crate::capi::bmp::parse::{BMP_COMMON_HEADER_LENGTH, BMP_PEER_HEADER_LENGTH};
+use crate::extensions::information_tlv::TlvExtension;
+use crate::free_rust_raw_box;
+use crate::opaque::Opaque;
+
+/// Version of the common header of BMP v4 messages (draft-ietf-grow-bmp-tlv)
+pub const BMP_VERSION_4: u8 = 4;
+
+/// Version of the common header of the messages decoded by NetGauze
+const BMP_VERSION_3: u8 = 3;
+
+/// When set in the type of a TLV, its value starts with a Private Enterprise Number
+/// (draft-ietf-grow-bmp-tlv-ebit)
+const BMP_TLV_ENTERPRISE_BIT: u16 = 0x8000;
+
+/// Route Monitoring TLV with the name of the VRF or table of the routes
+pub const BMP_RM_TLV_VRF_TABLE_NAME: u16 = 0;
+
+/// Route Monitoring TLV carrying the BGP UPDATE of the message
+pub const BMP_RM_TLV_BGP_PDU: u16 = 1;
+
+/// Route Monitoring TLV listing the 2 bytes indexes of the NLRI sharing the TLVs of its index
+pub const BMP_RM_TLV_GROUP: u16 = 2;
+
+/// Route Monitoring TLV with the capabilities needed to parse the BGP UPDATE
+pub const BMP_RM_TLV_STATELESS_PARSING: u16 = 3;
+
+/// Route Monitoring TLV with the 4 bytes status of a path and an optional 2 bytes reason code
+/// (draft-ietf-grow-bmp-path-marking-tlv)
+pub const BMP_RM_TLV_PATH_MARKING: u16 = 4;
+
+/// Length of the BGP message header (marker, length, type)
+const BGP_HEADER_LENGTH: usize = 19;
+
+/// Length of the local address, local port and remote port of a Peer Up Notification
+const BMP_PEER_UP_ADDRESSES_LENGTH: usize = 20;
+
+/// A BMP v4 TLV that the BMP v3 messages of NetGauze cannot carry:
+/// a Route Monitoring TLV or an enterprise TLV
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BmpV4Tlv {
+    /// Private Enterprise Number, 0 for IANA TLVs
+    pub pen: u32,
+    /// Type of the TLV, without the enterprise bit
+    pub tlv_type: u16,
+    /// Index of the NLRI the TLV applies to, 0 for the whole message.
+    /// Only Route Monitoring TLVs have an index.
+    pub index: u16,
+    /// Number of TLVs before this one that stayed in the BMP v3 message,
+    /// to put the TLVs back in the order they were received
+    pub position: usize,
+    pub value: Vec<u8>,
+}
+
+impl TlvExtension for BmpV4Tlv {
+    fn get_value_ptr(&self) -> *mut c_void {
+        self.value.as_ptr() as *mut c_void
+    }
+
+    fn get_raw_type(&self) -> u16 {
+        self.tlv_type
+    }
+
+    fn get_value_len(&self) -> u16 {
+        self.value.len() as u16
+    }
+
+    fn get_pen(&self) -> u32 {
+        self.pen
+    }
+}
+
+/// TLVs of a BMP v4 message that are not in its BMP v3 rewrite
+pub type BmpV4Tlvs = Vec<BmpV4Tlv>;
+
+free_rust_raw_box!(Opaque<BmpV4Tlvs>, Opaque_BmpV4Tlvs);
+
+/// A BMP v4 message split into the equivalent BMP v3 message and the TLVs v3 cannot carry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BmpV4Message {
+    pub v3_message: Vec<u8>,
+    pub tlvs: BmpV4Tlvs,
+}
+
+/// Rewrite a whole BMP v4 message as a BMP v3 message NetGauze can decode.
+///
+/// - Route Monitoring: the BGP PDU TLV becomes the BGP UPDATE of the v3 message,
+///   the other TLVs are kept in [BmpV4Message::tlvs]
+/// - Peer Up, Initiation and Termination: the enterprise TLVs are moved to
+///   [BmpV4Message::tlvs], the IANA TLVs stay in the v3 message
+/// - Other messages are identical in v3 and v4
+///
+/// Fails with the offset of the malformed part of `message`.
+pub fn decode_bmp_v4(message: &[u8]) -> Result<BmpV4Message, usize> {
+    let header = message
+        .get(..BMP_COMMON_HEADER_LENGTH)
+        .ok_or(message.len())?;
+    let message_type = header[5];
+
+    let mut v3_message = header.to_vec();
+    v3_message[0] = BMP_VERSION_3;
+
+    let mut tlvs = Vec::new();
+    match message_type {
+        // Route Monitoring
+        0 => {
+            let tlvs_start = BMP_COMMON_HEADER_LENGTH + BMP_PEER_HEADER_LENGTH;
+            let peer_header = message
+                .get(BMP_COMMON_HEADER_LENGTH..tlvs_start)
+                .ok_or(message.len())?;
+            v3_message.extend_from_slice(peer_header);
+
+            let mut bgp_pdu = None;
+            for (offset, tlv) in read_tlvs(message, tlvs_start, true)? {
+                if !is_valid_route_monitoring_tlv(&tlv) {
+                    return Err(offset);
+                }
+
+                if tlv.pen != 0 || tlv.tlv_type != BMP_RM_TLV_BGP_PDU {
+                    tlvs.push(tlv);
+                } else if bgp_pdu.replace(tlv.value).is_some() {
+                    return Err(offset);
+                }
+            }
+
+            v3_message.extend_from_slice(&bgp_pdu.ok_or(tlvs_start)?);
+        }
+        // Peer Up Notification, Initiation and Termination
+        3..=5 => {
+            let tlvs_start = match message_type {
+                3 => peer_up_tlvs_start(message)?,
+                _ => BMP_COMMON_HEADER_LENGTH,
+            };
+            v3_message.extend_from_slice(&message[BMP_COMMON_HEADER_LENGTH..tlvs_start]);
+
+            let mut position = 0;
+            for (_, mut tlv) in read_tlvs(message, tlvs_start, false)? {
+                if tlv.pen != 0 {
+                    tlv.position = position;
+                    tlvs.push(tlv);
+                    continue;
+                }
+
+                position += 1;
+                v3_message.extend_from_slice(&tlv.tlv_type.to_be_bytes());
+                v3_message.extend_from_slice(&(tlv.value.len() as u16).to_be_bytes());
+                v3_message.extend_from_slice(&tlv.value);
+            }
+        }
+        _ => v3_message.extend_from_slice(&message[BMP_COMMON_HEADER_LENGTH..]),
+    }
+
+    let length = v3_message.len() as u32;
+    v3_message[1..5].copy_from_slice(&length.to_be_bytes());
+
+    Ok(BmpV4Message { v3_message, tlvs })
+}
+
+/// Check the length of the Route Monitoring TLVs with a fixed structure
+fn is_valid_route_monitoring_tlv(tlv: &BmpV4Tlv) -> bool {
+    if tlv.pen != 0 {
+        return true;
+    }
+
+    match tlv.tlv_type {
+        BMP_RM_TLV_GROUP => tlv.value.len() % 2 == 0,
+        BMP_RM_TLV_PATH_MARKING => matches!(tlv.value.len(), 4 | 6),
+        _ => true,
+    }
+}
+
+/// Offset of the TLVs of a Peer Up Notification, after the sent and received OPEN messages
+fn peer_up_tlvs_start(message: &[u8]) -> Result<usize, usize> {
+    let mut offset =
+        BMP_COMMON_HEADER_LENGTH + BMP_PEER_HEADER_LENGTH + BMP_PEER_UP_ADDRESSES_LENGTH;
+
+    for _ in 0..2 {
+        let open_header = message
+            .get(offset..offset + BGP_HEADER_LENGTH)
+            .ok_or(offset)?;
+        let open_length = u16::from_be_bytes([open_header[16], open_header[17]]) as usize;
+        if open_length < BGP_HEADER_LENGTH || offset + open_length > message.len() {
+            return Err(offset);
+        }
+
+        offset += open_length;
+    }
+
+    Ok(offset)
+}
+
+/// Read the TLVs from `start` to the end of `message`, with their offset in `message`.
+///
+/// The TLV header is the type, with the enterprise bit, and the length of the value.
+/// Route Monitoring TLVs (`with_index`) then have the index of the NLRI they apply to.
+/// The value of an enterprise TLV starts with the Private Enterprise Number.
+fn read_tlvs(
+    message: &[u8],
+    start: usize,
+    with_index: bool,
+) -> Result<Vec<(usize, BmpV4Tlv)>, usize> {
+    let header_length = if with_index { 6 } else { 4 };
+
+    let mut tlvs = Vec::new();
+    let mut offset = start;
+    while offset < message.len() {
+        let header = message.get(offset..offset + header_length).ok_or(offset)?;
+        let raw_type = u16::from_be_bytes([header[0], header[1]]);
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let index = if with_index {
+            u16::from_be_bytes([header[4], header[5]])
+        } else {
+            0
+        };
+
+        let value_start = offset + header_length;
+        let value = message
+            .get(value_start..value_start + length)
+            .ok_or(offset)?;
+
+        let (pen, value) = if raw_type & BMP_TLV_ENTERPRISE_BIT == 0 {
+            (0, value)
+        } else {
+            let pen = value.get(..4).ok_or(offset)?;
+            (
+                u32::from_be_bytes([pen[0], pen[1], pen[2], pen[3]]),
+                &value[4..],
+            )
+        };
+
+        tlvs.push((
+            offset,
+            BmpV4Tlv {
+                pen,
+                tlv_type: raw_type & !BMP_TLV_ENTERPRISE_BIT,
+                index,
+                position: 0,
+                value: value.to_vec(),
+            },
+        ));
+
+        offset = value_start + length;
+    }
+
+    Ok(tlvs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::capi::bmp::parse::{decode_bmp_v4, BmpV4Tlv};
+    use crate::capi::bmp::test_util::{bmp_message, end_of_rib, peer_header, PEER_ADDRESS};
+
+    #[test]
+    fn test_decode_v4_route_monitoring() {
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        // Group TLV of index 0 with NLRI indexes 1 and 2
+        body.extend_from_slice(&[0, 2, 0, 4, 0, 0, 0, 1, 0, 2]);
+        // Enterprise TLV 7 of PEN 8284 for the NLRI of index 1
+        body.extend_from_slice(&[0x80, 7, 0, 6, 0, 1, 0, 0, 0x20, 0x5c, 0xca, 0xfe]);
+        // BGP PDU TLV
+        let update = end_of_rib();
+        body.extend_from_slice(&[0, 1, 0, update.len() as u8, 0, 0]);
+        body.extend_from_slice(&update);
+
+        let decoded = decode_bmp_v4(&bmp_message(4, 0, &body)).unwrap();
+
+        let mut v3_body = peer_header(0, 0, 0, PEER_ADDRESS);
+        v3_body.extend_from_slice(&update);
+        assert_eq!(decoded.v3_message, bmp_message(3, 0, &v3_body));
+        assert_eq!(
+            decoded.tlvs,
+            vec![
+                BmpV4Tlv {
+                    pen: 0,
+                    tlv_type: 2,
+                    index: 0,
+                    position: 0,
+                    value: vec![0, 1, 0, 2],
+                },
+                BmpV4Tlv {
+                    pen: 8284,
+                    tlv_type: 7,
+                    index: 1,
+                    position: 0,
+                    value: vec![0xca, 0xfe],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_v4_route_monitoring_malformed() {
+        let update = end_of_rib();
+        let mut bgp_pdu = vec![0, 1, 0, update.len() as u8, 0, 0];
+        bgp_pdu.extend_from_slice(&update);
+
+        // No BGP PDU TLV
+        let body = peer_header(0, 0, 0, PEER_ADDRESS);
+        assert_eq!(decode_bmp_v4(&bmp_message(4, 0, &body)), Err(48));
+
+        // Two BGP PDU TLVs
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&bgp_pdu);
+        body.extend_from_slice(&bgp_pdu);
+        assert_eq!(
+            decode_bmp_v4(&bmp_message(4, 0, &body)),
+            Err(48 + bgp_pdu.len())
+        );
+
+        // TLV longer than the message
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&bgp_pdu[..bgp_pdu.len() - 1]);
+        assert_eq!(decode_bmp_v4(&bmp_message(4, 0, &body)), Err(48));
+
+        // Enterprise TLV too short for its PEN
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&[0x80, 7, 0, 2, 0, 0, 0, 0]);
+        assert_eq!(decode_bmp_v4(&bmp_message(4, 0, &body)), Err(48));
+
+        // Group TLV with half an index
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&[0, 2, 0, 3, 0, 0, 0, 1, 0]);
+        body.extend_from_slice(&bgp_pdu);
+        assert_eq!(decode_bmp_v4(&bmp_message(4, 0, &body)), Err(48));
+
+        // Path Marking TLV without its whole path status
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&bgp_pdu);
+        body.extend_from_slice(&[0, 4, 0, 2, 0, 0, 0, 1]);
+        assert_eq!(
+            decode_bmp_v4(&bmp_message(4, 0, &body)),
+            Err(48 + bgp_pdu.len())
+        );
+    }
+
+    #[test]
+    fn test_decode_v4_initiation() {
+        // sysDescr, enterprise TLV 1 of PEN 8284 and sysName
+        let mut body = vec![0, 1, 0, 2, b'p', b'm'];
+        body.extend_from_slice(&[0x80, 1, 0, 5, 0, 0, 0x20, 0x5c, b'x']);
+        body.extend_from_slice(&[0, 2, 0, 2, b'r', b'1']);
+
+        let decoded = decode_bmp_v4(&bmp_message(4, 4, &body)).unwrap();
+
+        let mut v3_body = vec![0, 1, 0, 2, b'p', b'm'];
+        v3_body.extend_from_slice(&[0, 2, 0, 2, b'r', b'1']);
+        assert_eq!(decoded.v3_message, bmp_message(3, 4, &v3_body));
+        assert_eq!(
+            decoded.tlvs,
+            vec![BmpV4Tlv {
+                pen: 8284,
+                tlv_type: 1,
+                index: 0,
+                position: 1,
+                value: vec![b'x'],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_decode_v4_peer_up() {
+        let open = {
+            let mut open = vec![0xff; 16];
+            open.extend_from_slice(&[0, 29, 1, 4, 0xfd, 0xe8, 0, 90, 192, 0, 2, 1, 0]);
+            open
+        };
+
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&[0, 179, 0xc0, 0]);
+        body.extend_from_slice(&open);
+        body.extend_from_slice(&open);
+        let v3_body = body.clone();
+        // Enterprise TLV 3 of PEN 8284
+        body.extend_from_slice(&[0x80, 3, 0, 4, 0, 0, 0x20, 0x5c]);
+
+        let decoded = decode_bmp_v4(&bmp_message(4, 3, &body)).unwrap();
+        assert_eq!(decoded.v3_message, bmp_message(3, 3, &v3_body));
+        assert_eq!(
+            decoded.tlvs,
+            vec![BmpV4Tlv {
+                pen: 8284,
+                tlv_type: 3,
+                index: 0,
+                position: 0,
+                value: vec![],
+            }]
+        );
+
+        // Received OPEN truncated
+        assert_eq!(
+            decode_bmp_v4(&bmp_message(4, 3, &v3_body[..v3_body.len() - 1])),
+            Err(48 + 20 + open.len())
+        );
+    }
+
+    #[test]
+    fn test_decode_v4_other_messages() {
+        // Peer Down with reason 2 and FSM event code 0
+        let mut body = peer_header(0, 0, 0, PEER_ADDRESS);
+        body.extend_from_slice(&[2, 0, 0]);
+
+        let decoded = decode_bmp_v4(&bmp_message(4, 2, &body)).unwrap();
+        assert_eq!(decoded.v3_message, bmp_message(3, 2, &body));
+        assert!(decoded.tlvs.is_empty());
+    }
+}
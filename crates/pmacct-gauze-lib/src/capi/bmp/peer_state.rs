@@ -1,11 +1,16 @@
+use std::{ptr, slice};
+
 use netgauze_bgp_pkt::BgpMessage;
 use netgauze_bmp_pkt::{BmpMessageValue, PeerDownNotificationReason};
 use netgauze_parse_utils::WritablePdu;
 
-use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::capi::bgp::notification::BgpNotificationDetails;
+use crate::capi::bmp::{make_bmp_log_tlvs, WrongBmpMessageTypeError};
+use crate::coption::COption;
 use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
 use crate::opaque::Opaque;
-use pmacct_gauze_bindings::{bmp_log_peer_down, bmp_log_peer_up, host_addr, u_char};
+use pmacct_gauze_bindings::{bmp_log_peer_down, bmp_log_peer_up, bmp_log_tlv, host_addr, u_char};
 
 pub type BmpPeerUpHdrResult = CResult<bmp_log_peer_up, WrongBmpMessageTypeError>;
 
@@ -118,3 +123,182 @@ pub unsafe extern "C" fn netgauze_bmp_peer_down_get_info(
         loc_code,
     })
 }
+
+/// Why a BMP monitored session went down
+///
+/// This structure must be freed using [netgauze_bmp_peer_down_details_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpPeerDownDetails {
+    pub reason: u8,
+    /// BGP NOTIFICATION for reasons 1 and 3, null otherwise.
+    /// The pointer is borrowed from the BMP Peer Down Message.
+    pub notification: *const Opaque<BgpMessage>,
+    /// Decoded `notification`
+    pub notification_details: COption<BgpNotificationDetails>,
+    /// FSM event code for reason 2, 0 otherwise
+    pub fsm_event_code: u16,
+    /// Information TLV for reason 6, empty otherwise
+    pub tlvs: OwnedSlice<bmp_log_tlv>,
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_peer_down_details_free(value: BmpPeerDownDetails) {
+    value.tlvs.rust_free();
+    if let COption::Some(notification_details) = value.notification_details {
+        notification_details.rust_free();
+    }
+}
+
+pub type BmpPeerDownDetailsResult = CResult<BmpPeerDownDetails, WrongBmpMessageTypeError>;
+
+/// Get the [BmpPeerDownDetails] from a BMP Peer Down Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer.
+/// The result borrows from `bmp_message_value_opaque` and must not outlive it.
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_down_get_details(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpPeerDownDetailsResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let peer_down = match bmp_value {
+        BmpMessageValue::PeerDownNotification(peer_down) => peer_down,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let mut result = BmpPeerDownDetails {
+        reason: peer_down.reason().get_type() as u8,
+        notification: ptr::null(),
+        notification_details: COption::None,
+        fsm_event_code: 0,
+        tlvs: OwnedSlice::from_vec(Vec::new()),
+    };
+
+    match peer_down.reason() {
+        PeerDownNotificationReason::LocalSystemClosedNotificationPduFollows(message)
+        | PeerDownNotificationReason::RemoteSystemClosedNotificationPduFollows(message) => {
+            result.notification = Opaque::const_from_ref(message);
+            if let BgpMessage::Notification(notification) = message {
+                result.notification_details =
+                    COption::Some(BgpNotificationDetails::from(notification));
+            }
+        }
+        PeerDownNotificationReason::LocalSystemClosedFsmEventFollows(code) => {
+            result.fsm_event_code = *code;
+        }
+        PeerDownNotificationReason::LocalSystemClosedTlvDataFollows(information) => {
+            result.tlvs = OwnedSlice::from_vec(make_bmp_log_tlvs(slice::from_ref(information)));
+        }
+        _ => {}
+    }
+
+    CResult::Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+    use std::slice;
+
+    use crate::capi::bmp::parse::{netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free};
+    use crate::capi::bmp::peer_state::{
+        netgauze_bmp_peer_down_details_free, netgauze_bmp_peer_down_get_details, BmpPeerDownDetails,
+    };
+    use crate::capi::bmp::test_util::peer_message;
+    use crate::coption::COption;
+    use crate::cresult::CResult;
+
+    /// BGP NOTIFICATION of code `code` and subcode `subcode` without data
+    fn notification(code: u8, subcode: u8) -> Vec<u8> {
+        let mut notification = vec![0xff; 16];
+        notification.extend_from_slice(&[0, 21, 3, code, subcode]);
+        notification
+    }
+
+    /// Parse a Peer Down Notification with `body` and check its [BmpPeerDownDetails]
+    fn check_details(body: &[u8], check: impl Fn(&BmpPeerDownDetails)) {
+        let message = peer_message(2, 0, 0, 0, body);
+        let result = unsafe {
+            netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, message.len() as u32)
+        };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+
+        match unsafe { netgauze_bmp_peer_down_get_details(parsed.message) } {
+            CResult::Ok(details) => {
+                check(&details);
+                netgauze_bmp_peer_down_details_free(details);
+            }
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+        netgauze_bmp_parse_result_free(result);
+    }
+
+    #[test]
+    fn test_peer_down_notification() {
+        // Local system closed with a Hold Timer Expired NOTIFICATION
+        let mut body = vec![1];
+        body.extend_from_slice(&notification(4, 0));
+        check_details(&body, |details| {
+            assert_eq!(details.reason, 1);
+            assert!(!details.notification.is_null());
+            match &details.notification_details {
+                COption::Some(notification) => {
+                    assert_eq!((notification.code, notification.subcode), (4, 0))
+                }
+                COption::None => panic!("missing notification details"),
+            }
+            assert_eq!(details.fsm_event_code, 0);
+            assert_eq!(details.tlvs.len, 0);
+        });
+
+        // Remote system closed with a Cease Administrative Shutdown NOTIFICATION
+        let mut body = vec![3];
+        body.extend_from_slice(&notification(6, 2));
+        check_details(&body, |details| {
+            assert_eq!(details.reason, 3);
+            assert!(!details.notification.is_null());
+            match &details.notification_details {
+                COption::Some(notification) => {
+                    assert_eq!((notification.code, notification.subcode), (6, 2))
+                }
+                COption::None => panic!("missing notification details"),
+            }
+        });
+    }
+
+    #[test]
+    fn test_peer_down_fsm_event() {
+        check_details(&[2, 0, 7], |details| {
+            assert_eq!(details.reason, 2);
+            assert!(details.notification.is_null());
+            assert!(matches!(details.notification_details, COption::None));
+            assert_eq!(details.fsm_event_code, 7);
+            assert_eq!(details.tlvs.len, 0);
+        });
+    }
+
+    #[test]
+    fn test_peer_down_tlv() {
+        // Local system closed with a VRF/Table Name TLV (RFC 9069)
+        check_details(&[6, 0, 3, 0, 3, b'v', b'r', b'f'], |details| {
+            assert_eq!(details.reason, 6);
+            assert!(details.notification.is_null());
+            assert_eq!(details.fsm_event_code, 0);
+
+            let tlvs = unsafe { details.tlvs.as_slice() };
+            assert_eq!(tlvs.len(), 1);
+            assert_eq!((tlvs[0].pen, tlvs[0].type_, tlvs[0].len), (0, 3, 3));
+            assert_eq!(
+                unsafe { slice::from_raw_parts(tlvs[0].val as *const u8, 3) },
+                b"vrf"
+            );
+        });
+    }
+}
@@ -0,0 +1,348 @@
+use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
+use netgauze_bmp_pkt::{BmpMessageValue, PeerKey};
+
+use crate::capi::bgp::parse::BgpParsingContextCapabilities;
+use crate::capi::bmp::parse::BmpPeerKey;
+use crate::context_cache::ContextCache;
+use crate::coption::COption;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::extensions::bmp_message::ExtendBmpMessage;
+use crate::extensions::context::{bgp_parsing_context_from_peer_up, ExtendBmpParsingContext};
+use crate::opaque::Opaque;
+use crate::{free_cslice_t, free_rust_raw_box, make_default};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BmpPeerState {
+    /// Messages were received for the peer but not its Peer Up Notification
+    Unknown,
+    Up,
+    Down,
+}
+
+/// Number of BMP messages received for a peer, by message type
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BmpPeerMessageCounters {
+    pub route_monitoring: u64,
+    pub statistics_report: u64,
+    pub peer_down: u64,
+    pub peer_up: u64,
+    pub route_mirroring: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BmpPeerTableEntry {
+    state: BmpPeerState,
+    /// Context built from the OPEN messages of the last Peer Up Notification
+    parsing_context: Option<BgpParsingContext>,
+    /// Time of the last Peer Up Notification, in seconds
+    up_time: Option<u64>,
+    /// Time of the last message, in seconds
+    last_message_time: u64,
+    counters: BmpPeerMessageCounters,
+}
+
+impl BmpPeerTableEntry {
+    fn new(now: u64) -> Self {
+        Self {
+            state: BmpPeerState::Unknown,
+            parsing_context: None,
+            up_time: None,
+            last_message_time: now,
+            counters: BmpPeerMessageCounters::default(),
+        }
+    }
+}
+
+/// Monitored peers of a BMP router and their lifecycle
+pub type BmpPeerTable = ContextCache<PeerKey, BmpPeerTableEntry>;
+
+free_rust_raw_box!(Opaque<BmpPeerTable>, Opaque_BmpPeerTable);
+make_default!(Opaque<BmpPeerTable>, Opaque_BmpPeerTable);
+
+/// Update the [BmpPeerTable] entry of the peer of a [BmpMessageValue]
+/// received at time `now`, in seconds.
+/// Messages without a per-peer header are ignored.
+pub(crate) fn update_peer_table(
+    bmp_peer_table: &mut BmpPeerTable,
+    bmp_value: &BmpMessageValue,
+    now: u64,
+) {
+    let peer_key = match bmp_value.get_peer_header() {
+        Some(peer_header) => PeerKey::from_peer_header(peer_header),
+        None => return,
+    };
+
+    let entry = bmp_peer_table
+        .entry(peer_key)
+        .or_insert_with(|| BmpPeerTableEntry::new(now));
+    entry.last_message_time = now;
+
+    match bmp_value {
+        BmpMessageValue::RouteMonitoring(_) => entry.counters.route_monitoring += 1,
+        BmpMessageValue::StatisticsReport(_) => entry.counters.statistics_report += 1,
+        BmpMessageValue::RouteMirroring(_) => entry.counters.route_mirroring += 1,
+        BmpMessageValue::PeerUpNotification(peer_up) => {
+            entry.counters.peer_up += 1;
+            entry.state = BmpPeerState::Up;
+            entry.up_time = Some(now);
+            entry.parsing_context = bgp_parsing_context_from_peer_up(peer_up);
+        }
+        BmpMessageValue::PeerDownNotification(_) => {
+            entry.counters.peer_down += 1;
+            entry.state = BmpPeerState::Down;
+            entry.parsing_context = None;
+        }
+        _ => {}
+    }
+}
+
+/// Update the [BmpPeerTable] with a [BmpMessageValue] received at time `now`, in seconds
+///
+/// # Safety
+/// `bmp_peer_table` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_peer_table` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_table_update(
+    bmp_peer_table: *mut Opaque<BmpPeerTable>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+    now: u64,
+) {
+    let bmp_peer_table = unsafe { bmp_peer_table.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    update_peer_table(bmp_peer_table, bmp_value, now);
+}
+
+/// Delete the [BmpPeerTable] entry of the peer of a [BmpMessageValue]
+///
+/// # Safety
+/// `bmp_peer_table` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_peer_table` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_table_delete(
+    bmp_peer_table: *mut Opaque<BmpPeerTable>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) {
+    let bmp_peer_table = unsafe { bmp_peer_table.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    if let Some(peer_header) = bmp_value.get_peer_header() {
+        bmp_peer_table.remove(&PeerKey::from_peer_header(peer_header));
+    }
+}
+
+/// State of a peer in a [BmpPeerTable] at the time of [netgauze_bmp_peer_table_snapshot]
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpPeerSnapshot {
+    pub peer: BmpPeerKey,
+    pub state: BmpPeerState,
+    /// Capabilities negotiated in the OPEN messages of the Peer Up Notification
+    pub capabilities: COption<BgpParsingContextCapabilities>,
+    /// Time of the last Peer Up Notification in seconds, 0 if none was received
+    pub up_time_secs: u64,
+    /// Time of the last message of the peer in seconds
+    pub last_message_time_secs: u64,
+    pub counters: BmpPeerMessageCounters,
+}
+
+free_cslice_t!(BmpPeerSnapshot);
+
+/// Get a [BmpPeerSnapshot] of each peer in the [BmpPeerTable]
+///
+/// The returned [OwnedSlice<BmpPeerSnapshot>] must be freed with [CSlice_free_BmpPeerSnapshot]
+///
+/// # Safety
+/// `bmp_peer_table` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_peer_table` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_table_snapshot(
+    bmp_peer_table: *const Opaque<BmpPeerTable>,
+) -> OwnedSlice<BmpPeerSnapshot> {
+    let bmp_peer_table = unsafe { bmp_peer_table.as_ref().unwrap().as_ref() };
+
+    let snapshot = bmp_peer_table
+        .iter()
+        .map(|(peer_key, entry)| BmpPeerSnapshot {
+            peer: BmpPeerKey::from(peer_key),
+            state: entry.state,
+            capabilities: entry
+                .parsing_context
+                .as_ref()
+                .map(BgpParsingContextCapabilities::from)
+                .into(),
+            up_time_secs: entry.up_time.unwrap_or(0),
+            last_message_time_secs: entry.last_message_time,
+            counters: entry.counters,
+        })
+        .collect();
+
+    OwnedSlice::from_vec(snapshot)
+}
+
+/// Changes made to a [BmpParsingContext] by [netgauze_bmp_peer_table_reconcile]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BmpPeerTableReconcileResult {
+    /// Contexts of peers that are up but were missing
+    pub added: usize,
+    /// Contexts of peers that are down
+    pub removed: usize,
+}
+
+/// Make the peers of a [BmpParsingContext] match the peers of the [BmpPeerTable]:
+/// the context of a peer that is up is installed from its Peer Up Notification if missing,
+/// and the contexts of the peers that are down are removed.
+///
+/// Peers in [BmpPeerState::Unknown] state or missing from the table keep their context
+/// if they have one, the table may have been created after their Peer Up Notification.
+///
+/// # Safety
+/// `bmp_peer_table` should be not null and point to valid data
+/// `bmp_parsing_context` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_peer_table` and `bmp_parsing_context` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_peer_table_reconcile(
+    bmp_peer_table: *const Opaque<BmpPeerTable>,
+    bmp_parsing_context: *mut Opaque<BmpParsingContext>,
+) -> BmpPeerTableReconcileResult {
+    let bmp_peer_table = unsafe { bmp_peer_table.as_ref().unwrap().as_ref() };
+    let bmp_parsing_context = unsafe { bmp_parsing_context.as_mut().unwrap().as_mut() };
+
+    let mut result = BmpPeerTableReconcileResult::default();
+
+    bmp_parsing_context.retain(|peer_key, _| {
+        let keep = bmp_peer_table
+            .get(peer_key)
+            .map(|entry| entry.state != BmpPeerState::Down)
+            .unwrap_or(true);
+        if !keep {
+            result.removed += 1;
+        }
+        keep
+    });
+
+    for (peer_key, entry) in bmp_peer_table.iter() {
+        if entry.state != BmpPeerState::Up || bmp_parsing_context.get_peer(peer_key).is_some() {
+            continue;
+        }
+
+        match &entry.parsing_context {
+            Some(parsing_context) => {
+                bmp_parsing_context.add_peer(*peer_key, parsing_context.clone())
+            }
+            None => bmp_parsing_context.add_default_peer(*peer_key),
+        }
+        result.added += 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+
+    use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
+    use netgauze_bmp_pkt::{BmpMessageValue, PeerKey};
+
+    use crate::capi::bmp::parse::{netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free};
+    use crate::capi::bmp::peer_table::{
+        netgauze_bmp_peer_table_reconcile, update_peer_table, BmpPeerState, BmpPeerTable,
+    };
+    use crate::capi::bmp::test_util::{bmp_message, end_of_rib, peer_header};
+    use crate::cresult::CResult;
+    use crate::extensions::bmp_message::ExtendBmpMessage;
+    use crate::extensions::context::ExtendBmpParsingContext;
+    use crate::opaque::Opaque;
+
+    /// BMP message of type `message_type` with the per-peer header of the global instance
+    /// peer 192.0.2.`peer` in AS 65000 followed by `body`
+    fn message_from_peer(message_type: u8, peer: u8, body: &[u8]) -> Vec<u8> {
+        let mut message_body = peer_header(0, 0, 0, [192, 0, 2, peer]);
+        message_body.extend_from_slice(body);
+        bmp_message(3, message_type, &message_body)
+    }
+
+    /// Route Monitoring with an IPv4 End-of-RIB
+    fn route_monitoring(peer: u8) -> Vec<u8> {
+        message_from_peer(0, peer, &end_of_rib())
+    }
+
+    /// Peer Down Notification of a remote system closed without data
+    fn peer_down(peer: u8) -> Vec<u8> {
+        message_from_peer(2, peer, &[4])
+    }
+
+    /// Parse `message` without context and update `bmp_peer_table` with it at time `now`
+    fn update(bmp_peer_table: &mut BmpPeerTable, message: &[u8], now: u64) -> PeerKey {
+        let result = unsafe {
+            netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, message.len() as u32)
+        };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        let bmp_value: &BmpMessageValue = unsafe { parsed.message.as_ref().unwrap().as_ref() };
+        let peer_key = PeerKey::from_peer_header(bmp_value.get_peer_header().unwrap());
+        update_peer_table(bmp_peer_table, bmp_value, now);
+        netgauze_bmp_parse_result_free(result);
+
+        peer_key
+    }
+
+    #[test]
+    fn test_peer_table_update() {
+        let mut bmp_peer_table = BmpPeerTable::default();
+
+        let peer = update(&mut bmp_peer_table, &route_monitoring(1), 100);
+        let entry = bmp_peer_table.get(&peer).unwrap();
+        assert_eq!(entry.state, BmpPeerState::Unknown);
+        assert_eq!((entry.up_time, entry.last_message_time), (None, 100));
+        assert_eq!(entry.counters.route_monitoring, 1);
+
+        update(&mut bmp_peer_table, &peer_down(1), 150);
+        let entry = bmp_peer_table.get(&peer).unwrap();
+        assert_eq!(entry.state, BmpPeerState::Down);
+        assert_eq!(entry.last_message_time, 150);
+        assert_eq!(entry.counters.peer_down, 1);
+    }
+
+    #[test]
+    fn test_peer_table_reconcile() {
+        let mut bmp_peer_table = BmpPeerTable::default();
+        let up = update(&mut bmp_peer_table, &route_monitoring(1), 0);
+        bmp_peer_table.get_mut(&up).unwrap().state = BmpPeerState::Up;
+        let down = update(&mut bmp_peer_table, &peer_down(2), 0);
+        let unknown = update(&mut bmp_peer_table, &route_monitoring(3), 0);
+        let missing = update(&mut BmpPeerTable::default(), &route_monitoring(4), 0);
+
+        let mut bmp_parsing_context = Opaque::from(BmpParsingContext::default());
+        for peer_key in [down, unknown, missing] {
+            bmp_parsing_context.as_mut().add_default_peer(peer_key);
+        }
+
+        let result = unsafe {
+            netgauze_bmp_peer_table_reconcile(
+                &Opaque::from(bmp_peer_table),
+                &mut bmp_parsing_context,
+            )
+        };
+        assert_eq!((result.added, result.removed), (1, 1));
+
+        let bmp_parsing_context = bmp_parsing_context.as_mut();
+        assert!(bmp_parsing_context.get_peer(&up).is_some());
+        assert!(bmp_parsing_context.get_peer(&down).is_none());
+        assert!(bmp_parsing_context.get_peer(&unknown).is_some());
+        assert!(bmp_parsing_context.get_peer(&missing).is_some());
+    }
+}
@@ -0,0 +1,536 @@
+use netgauze_bgp_pkt::BgpMessage;
+use netgauze_bmp_pkt::{BmpMessageValue, PeerKey};
+
+use pmacct_gauze_bindings::{afi_t, prefix, rd_t, safi_t};
+
+use crate::capi::bgp::update::ProcessPacket;
+use crate::capi::bmp::parse::BmpPeerKey;
+use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::capi::rib::{make_prefix_key, make_withdraw_packets, Rib, RibEntry, RibUpdateSummary};
+use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::extensions::bmp_message::ExtendBmpPeerHeader;
+use crate::extensions::rd::RdOriginType;
+use crate::opaque::Opaque;
+use crate::{free_cslice_t, free_rust_raw_box, make_default};
+
+/// Routes of the peers of a BMP router
+pub type BmpRib = Rib<PeerKey>;
+
+free_rust_raw_box!(Opaque<BmpRib>, Opaque_BmpRib);
+make_default!(Opaque<BmpRib>, Opaque_BmpRib);
+
+pub type BmpRibUpdateResult = CResult<RibUpdateSummary, WrongBmpMessageTypeError>;
+
+/// Apply the BGP UPDATE of a BMP Route Monitoring Message to the [BmpRib]
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_rib` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_update(
+    bmp_rib: *mut Opaque<BmpRib>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpRibUpdateResult {
+    let bmp_rib = unsafe { bmp_rib.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let bmp_rm = match bmp_value {
+        BmpMessageValue::RouteMonitoring(rm) => rm,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let update = match bmp_rm.update_message() {
+        BgpMessage::Update(update) => update,
+        // NetGauze only accepts Route Monitoring Messages with a BGP UPDATE
+        _ => return CResult::Ok(RibUpdateSummary::default()),
+    };
+
+    let peer_header = bmp_rm.peer_header();
+    CResult::Ok(bmp_rib.apply_update(
+        PeerKey::from_peer_header(peer_header),
+        peer_header.rib_type(),
+        update,
+    ))
+}
+
+/// A route of a [BmpRib] and the peer it was received from
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpRibEntry {
+    pub peer: BmpPeerKey,
+    pub entry: RibEntry,
+}
+
+free_cslice_t!(BmpRibEntry);
+
+/// Get all the routes of the [BmpRib]
+///
+/// The returned [OwnedSlice<BmpRibEntry>] must be freed with [CSlice_free_BmpRibEntry].
+/// It borrows the attributes of the routes from the [BmpRib] and must be freed before
+/// the [BmpRib] is modified.
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_rib` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_dump(
+    bmp_rib: *const Opaque<BmpRib>,
+) -> OwnedSlice<BmpRibEntry> {
+    let bmp_rib = unsafe { bmp_rib.as_ref().unwrap().as_ref() };
+
+    let mut result = Vec::new();
+    for (peer_key, rib_peer) in bmp_rib.peers() {
+        let peer = BmpPeerKey::from(peer_key);
+        result.extend(
+            rib_peer
+                .routes()
+                .map(|(prefix_key, path_key, route)| BmpRibEntry {
+                    peer: peer.clone(),
+                    entry: RibEntry::new(prefix_key, path_key, route, RdOriginType::BMP),
+                }),
+        );
+    }
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Get the routes of all the peers of the [BmpRib] to a prefix.
+/// `rd` may be null for routes without a Route Distinguisher.
+///
+/// The returned [OwnedSlice<BmpRibEntry>] must be freed with [CSlice_free_BmpRibEntry].
+/// It borrows the attributes of the routes from the [BmpRib] and must be freed before
+/// the [BmpRib] is modified.
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+/// `prefix` should be not null and point to valid data
+/// `rd` should be null or point to valid data
+///
+/// This function does not consume the `bmp_rib`, `prefix` and `rd` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_query(
+    bmp_rib: *const Opaque<BmpRib>,
+    afi: afi_t,
+    safi: safi_t,
+    prefix: *const prefix,
+    rd: *const rd_t,
+) -> OwnedSlice<BmpRibEntry> {
+    let bmp_rib = unsafe { bmp_rib.as_ref().unwrap().as_ref() };
+
+    let prefix_key = match unsafe { make_prefix_key(afi, safi, prefix, rd) } {
+        Some(prefix_key) => prefix_key,
+        None => return OwnedSlice::from_vec(Vec::new()),
+    };
+
+    let mut result = Vec::new();
+    for (peer_key, rib_peer) in bmp_rib.peers() {
+        let peer = BmpPeerKey::from(peer_key);
+        result.extend(
+            rib_peer
+                .paths(&prefix_key)
+                .map(|(path_key, route)| BmpRibEntry {
+                    peer: peer.clone(),
+                    entry: RibEntry::new(&prefix_key, path_key, route, RdOriginType::BMP),
+                }),
+        );
+    }
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Number of routes of a peer in a [BmpRib]
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpRibPeerSummary {
+    pub peer: BmpPeerKey,
+    pub route_count: usize,
+    /// Number of RIB and AFI/SAFI for which End-of-RIB was received
+    pub end_of_rib_count: usize,
+}
+
+free_cslice_t!(BmpRibPeerSummary);
+
+/// Get a [BmpRibPeerSummary] of each peer of the [BmpRib]
+///
+/// The returned [OwnedSlice<BmpRibPeerSummary>] must be freed with [CSlice_free_BmpRibPeerSummary]
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_rib` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_peers(
+    bmp_rib: *const Opaque<BmpRib>,
+) -> OwnedSlice<BmpRibPeerSummary> {
+    let bmp_rib = unsafe { bmp_rib.as_ref().unwrap().as_ref() };
+
+    let result = bmp_rib
+        .peers()
+        .map(|(peer_key, rib_peer)| BmpRibPeerSummary {
+            peer: BmpPeerKey::from(peer_key),
+            route_count: rib_peer.route_count(),
+            end_of_rib_count: rib_peer.end_of_rib_count(),
+        })
+        .collect();
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Synthetic withdrawal of a route of a [BmpRib]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpRibWithdraw {
+    /// BMP RIB type (BMP_RIB_*) the route was in
+    pub rib_type: u8,
+    pub packet: ProcessPacket,
+}
+
+free_cslice_t!(BmpRibWithdraw);
+
+pub type BmpRibWithdrawResult = CResult<OwnedSlice<BmpRibWithdraw>, WrongBmpMessageTypeError>;
+
+/// Remove `peer_key` from the [BmpRib] and make a withdrawal for each of its routes
+fn withdraw_peer(bmp_rib: &mut BmpRib, peer_key: &PeerKey) -> Vec<BmpRibWithdraw> {
+    bmp_rib
+        .remove_peer(peer_key)
+        .map(|rib_peer| {
+            make_withdraw_packets(&rib_peer, RdOriginType::BMP)
+                .into_iter()
+                .map(|(rib_type, packet)| BmpRibWithdraw {
+                    // Routes of a BmpRib always have a RIB type
+                    rib_type: rib_type.map(u8::from).unwrap_or_default(),
+                    packet,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Remove the peers of the [BmpRib] with the [BmpPeerKey] `peer_key`, and get a withdrawal
+/// for each of their routes so that they can be processed like Route Monitoring withdrawals.
+/// A [BmpPeerKey] has no peer type, so this removes all the instances of the peer.
+///
+/// The returned [OwnedSlice<BmpRibWithdraw>] must be freed with [CSlice_free_BmpRibWithdraw]
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+/// `peer_key` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_rib` and `peer_key` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_withdraw_peer(
+    bmp_rib: *mut Opaque<BmpRib>,
+    peer_key: *const BmpPeerKey,
+) -> OwnedSlice<BmpRibWithdraw> {
+    let bmp_rib = unsafe { bmp_rib.as_mut().unwrap().as_mut() };
+    let peer_key = unsafe { peer_key.as_ref().unwrap() };
+
+    let peer_keys: Vec<PeerKey> = bmp_rib
+        .peers()
+        .map(|(key, _)| key)
+        .filter(|key| BmpPeerKey::from(*key) == *peer_key)
+        .cloned()
+        .collect();
+
+    let result = peer_keys
+        .iter()
+        .flat_map(|key| withdraw_peer(bmp_rib, key))
+        .collect();
+
+    OwnedSlice::from_vec(result)
+}
+
+/// Remove the peer of a BMP Peer Down Message from the [BmpRib], and get a withdrawal
+/// for each of its routes so that they can be processed like Route Monitoring withdrawals
+///
+/// The returned [OwnedSlice<BmpRibWithdraw>] must be freed with [CSlice_free_BmpRibWithdraw]
+///
+/// # Safety
+/// `bmp_rib` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_rib` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_rib_withdraw_peer_down(
+    bmp_rib: *mut Opaque<BmpRib>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpRibWithdrawResult {
+    let bmp_rib = unsafe { bmp_rib.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let peer_down = match bmp_value {
+        BmpMessageValue::PeerDownNotification(peer_down) => peer_down,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let peer_key = PeerKey::from_peer_header(peer_down.peer_header());
+
+    CResult::Ok(OwnedSlice::from_vec(withdraw_peer(bmp_rib, &peer_key)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use ipnet::IpNet;
+    use netgauze_bgp_pkt::nlri::RouteDistinguisher;
+    use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+    use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
+    use netgauze_bmp_pkt::{BmpMessage, BmpMessageValue, PeerKey};
+    use netgauze_iana::address_family::AddressType;
+    use netgauze_parse_utils::{ReadablePduWithOneInput, Span};
+
+    use pmacct_gauze_bindings::{
+        path_id_t, rd_t, DefaultZeroed, BGP_NLRI_WITHDRAW, BMP_RIB_ADJ_RIB_IN_POST,
+        BMP_RIB_ADJ_RIB_IN_PRE, BMP_RIB_ADJ_RIB_OUT_POST, BMP_RIB_ADJ_RIB_OUT_PRE, BMP_RIB_LOC_RIB,
+    };
+
+    use crate::capi::bmp::parse::BmpPeerKey;
+    use crate::capi::bmp::rib::{
+        netgauze_bmp_rib_update, netgauze_bmp_rib_withdraw_peer,
+        netgauze_bmp_rib_withdraw_peer_down, BmpRib, BmpRibWithdraw, CSlice_free_BmpRibWithdraw,
+    };
+    use crate::capi::bmp::test_util::peer_message;
+    use crate::capi::rib::test::{update, ATTRIBUTES};
+    use crate::cresult::CResult;
+    use crate::cslice::OwnedSlice;
+    use crate::extensions::bmp_message::ExtendBmpMessage;
+    use crate::extensions::context::ExtendBmpParsingContext;
+    use crate::opaque::Opaque;
+
+    const GLOBAL_INSTANCE_PEER: u8 = 0;
+    const RD_INSTANCE_PEER: u8 = 1;
+    const LOC_RIB_INSTANCE_PEER: u8 = 3;
+
+    const ADJ_RIB_OUT_FLAG: u8 = 0x10;
+    const POST_POLICY_FLAG: u8 = 0x40;
+
+    /// Route Monitoring with a BGP UPDATE announcing `nlri`, or the routes of `attributes`
+    fn route_monitoring(
+        peer_type: u8,
+        flags: u8,
+        rd: u64,
+        attributes: &[u8],
+        nlri: &[u8],
+    ) -> Vec<u8> {
+        peer_message(0, peer_type, flags, rd, &update(&[], attributes, nlri))
+    }
+
+    fn parse(message: &[u8], context: &mut BmpParsingContext) -> BmpMessageValue {
+        match BmpMessage::from_wire(Span::new(message), context) {
+            Ok((_, BmpMessage::V3(value))) => value,
+            result => panic!("unexpected parsing result {result:?}"),
+        }
+    }
+
+    /// Apply `message` to `bmp_rib` and get the key of its peer
+    fn apply(
+        bmp_rib: &mut Opaque<BmpRib>,
+        context: &mut BmpParsingContext,
+        message: &[u8],
+    ) -> PeerKey {
+        let value = parse(message, context);
+        let peer_key = PeerKey::from_peer_header(value.get_peer_header().unwrap());
+        match unsafe { netgauze_bmp_rib_update(bmp_rib, &Opaque::from(value)) } {
+            CResult::Ok(summary) => assert!(summary.announced > 0),
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+
+        peer_key
+    }
+
+    /// RIB type, prefix, path id, RD and label of withdrawals, which are freed
+    fn withdrawals(
+        result: OwnedSlice<BmpRibWithdraw>,
+    ) -> HashSet<(u8, Option<String>, path_id_t, Option<u64>, [u8; 3])> {
+        let withdrawals = unsafe { result.as_slice() }
+            .iter()
+            .map(|withdraw| {
+                let packet = &withdraw.packet;
+                assert_eq!(packet.update_type, BGP_NLRI_WITHDRAW);
+                let prefix = IpNet::try_from(&packet.prefix).ok();
+                let rd = RouteDistinguisher::try_from(packet.attr_extra.rd).ok();
+                (
+                    withdraw.rib_type,
+                    prefix.map(|prefix| prefix.to_string()),
+                    packet.attr_extra.path_id,
+                    // Routes without RD have a zeroed RD
+                    rd.map(u64::from).filter(|rd| *rd != 0),
+                    packet.attr_extra.label,
+                )
+            })
+            .collect();
+        CSlice_free_BmpRibWithdraw(result);
+
+        withdrawals
+    }
+
+    #[test]
+    fn test_bmp_rib_withdraw_peer_down() {
+        let mut bmp_rib = Opaque::from(BmpRib::default());
+        let mut context = BmpParsingContext::default();
+
+        // 10.0.0.0/8 in the Adj-RIB-In and Adj-RIB-Out, pre and post-policy
+        let mut peer_key = None;
+        for flags in [
+            0,
+            POST_POLICY_FLAG,
+            ADJ_RIB_OUT_FLAG,
+            ADJ_RIB_OUT_FLAG | POST_POLICY_FLAG,
+        ] {
+            let message = route_monitoring(GLOBAL_INSTANCE_PEER, flags, 0, &ATTRIBUTES, &[8, 10]);
+            peer_key = Some(apply(&mut bmp_rib, &mut context, &message));
+        }
+        let peer_key = peer_key.unwrap();
+        assert_eq!(bmp_rib.as_ref().peers().count(), 1);
+
+        // 192.168.0.0/16 in the pre-policy Adj-RIB-In with path ids 1 and 2
+        let mut add_path_context = BgpParsingContext::default();
+        add_path_context
+            .add_path_mut()
+            .insert(AddressType::Ipv4Unicast, true);
+        context.add_peer(peer_key, add_path_context);
+        let nlri = [0, 0, 0, 1, 16, 192, 168, 0, 0, 0, 2, 16, 192, 168];
+        let message = route_monitoring(GLOBAL_INSTANCE_PEER, 0, 0, &ATTRIBUTES, &nlri);
+        apply(&mut bmp_rib, &mut context, &message);
+
+        // Another peer is kept
+        let message = route_monitoring(LOC_RIB_INSTANCE_PEER, 0, 0, &ATTRIBUTES, &[8, 10]);
+        let loc_rib_key = apply(&mut bmp_rib, &mut context, &message);
+
+        // Only Peer Down Notifications are accepted
+        let message = route_monitoring(GLOBAL_INSTANCE_PEER, 0, 0, &ATTRIBUTES, &[8, 10]);
+        let message_value = Opaque::from(parse(&message, &mut context));
+        let result = unsafe { netgauze_bmp_rib_withdraw_peer_down(&mut bmp_rib, &message_value) };
+        assert!(matches!(result, CResult::Err(_)));
+
+        let peer_down = Opaque::from(parse(
+            &peer_message(2, GLOBAL_INSTANCE_PEER, 0, 0, &[4]),
+            &mut context,
+        ));
+        let result = unsafe { netgauze_bmp_rib_withdraw_peer_down(&mut bmp_rib, &peer_down) };
+        let result = match result {
+            CResult::Ok(result) => result,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+
+        let prefix = |prefix: &str| Some(prefix.to_string());
+        let no_label = [0; 3];
+        assert_eq!(
+            withdrawals(result),
+            HashSet::from([
+                (
+                    BMP_RIB_ADJ_RIB_IN_PRE as u8,
+                    prefix("10.0.0.0/8"),
+                    0,
+                    None,
+                    no_label
+                ),
+                (
+                    BMP_RIB_ADJ_RIB_IN_POST as u8,
+                    prefix("10.0.0.0/8"),
+                    0,
+                    None,
+                    no_label
+                ),
+                (
+                    BMP_RIB_ADJ_RIB_OUT_PRE as u8,
+                    prefix("10.0.0.0/8"),
+                    0,
+                    None,
+                    no_label
+                ),
+                (
+                    BMP_RIB_ADJ_RIB_OUT_POST as u8,
+                    prefix("10.0.0.0/8"),
+                    0,
+                    None,
+                    no_label
+                ),
+                (
+                    BMP_RIB_ADJ_RIB_IN_PRE as u8,
+                    prefix("192.168.0.0/16"),
+                    1,
+                    None,
+                    no_label
+                ),
+                (
+                    BMP_RIB_ADJ_RIB_IN_PRE as u8,
+                    prefix("192.168.0.0/16"),
+                    2,
+                    None,
+                    no_label
+                ),
+            ])
+        );
+
+        let bmp_rib_ref = bmp_rib.as_ref();
+        assert!(bmp_rib_ref.get_peer(&peer_key).is_none());
+        assert!(bmp_rib_ref.get_peer(&loc_rib_key).is_some());
+
+        // The peer has no more routes to withdraw
+        match unsafe { netgauze_bmp_rib_withdraw_peer_down(&mut bmp_rib, &peer_down) } {
+            CResult::Ok(result) => assert_eq!(withdrawals(result), HashSet::new()),
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_bmp_rib_withdraw_peer() {
+        let mut bmp_rib = Opaque::from(BmpRib::default());
+        let mut context = BmpParsingContext::default();
+
+        let message = route_monitoring(LOC_RIB_INSTANCE_PEER, 0, 0, &ATTRIBUTES, &[8, 10]);
+        let loc_rib_key = apply(&mut bmp_rib, &mut context, &message);
+
+        // VPN route 65000:2:10.0.0.0/8 with label 16 from the peer of RD 65000:1
+        let mut attributes = ATTRIBUTES[..7].to_vec();
+        attributes.extend_from_slice(&[0x80, 14, 30, 0, 1, 128, 12, 0, 0, 0, 0, 0, 0, 0, 0]);
+        attributes.extend_from_slice(&[192, 0, 2, 1, 0]);
+        attributes.extend_from_slice(&[96, 0, 1, 1, 0, 0, 0xfd, 0xe8, 0, 0, 0, 2, 10]);
+        let peer_rd = 0xfde8_0000_0001;
+        let message = route_monitoring(RD_INSTANCE_PEER, 0, peer_rd, &attributes, &[]);
+        let rd_peer_key = apply(&mut bmp_rib, &mut context, &message);
+        assert_eq!(bmp_rib.as_ref().peers().count(), 2);
+
+        // The RD of the peer is part of its key
+        let mut bmp_peer_key = BmpPeerKey::from(&rd_peer_key);
+        let rd = bmp_peer_key.rd;
+        bmp_peer_key.rd = rd_t::default_zeroed();
+        let result = unsafe { netgauze_bmp_rib_withdraw_peer(&mut bmp_rib, &bmp_peer_key) };
+        assert_eq!(withdrawals(result), HashSet::new());
+        assert_eq!(bmp_rib.as_ref().peers().count(), 2);
+
+        bmp_peer_key.rd = rd;
+        let result = unsafe { netgauze_bmp_rib_withdraw_peer(&mut bmp_rib, &bmp_peer_key) };
+        assert_eq!(
+            withdrawals(result),
+            HashSet::from([(
+                BMP_RIB_ADJ_RIB_IN_PRE as u8,
+                Some("10.0.0.0/8".to_string()),
+                0,
+                Some(0xfde8_0000_0002),
+                [0, 1, 1],
+            )])
+        );
+        assert!(bmp_rib.as_ref().get_peer(&rd_peer_key).is_none());
+
+        let bmp_peer_key = BmpPeerKey::from(&loc_rib_key);
+        let result = unsafe { netgauze_bmp_rib_withdraw_peer(&mut bmp_rib, &bmp_peer_key) };
+        assert_eq!(
+            withdrawals(result),
+            HashSet::from([(
+                BMP_RIB_LOC_RIB as u8,
+                Some("10.0.0.0/8".to_string()),
+                0,
+                None,
+                [0; 3],
+            )])
+        );
+        assert_eq!(bmp_rib.as_ref().peers().count(), 0);
+    }
+}
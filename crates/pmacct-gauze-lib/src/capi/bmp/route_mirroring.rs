@@ -0,0 +1,180 @@
+use std::ffi::c_void;
+
+use netgauze_bgp_pkt::BgpMessage;
+use netgauze_bmp_pkt::iana::RouteMirroringInformation;
+use netgauze_bmp_pkt::{BmpMessageValue, RouteMirroringValue};
+use netgauze_parse_utils::WritablePdu;
+
+use pmacct_gauze_bindings::bmp_log_tlv;
+
+use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::free_cslice_t;
+use crate::opaque::Opaque;
+
+/// The `message` pointer is borrowed from the BMP Route Mirroring Message
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct BmpMirroredBgpMessage {
+    pub message: *const Opaque<BgpMessage>,
+    pub message_size: usize,
+}
+
+free_cslice_t!(BmpMirroredBgpMessage);
+
+/// Content of a BMP Route Mirroring Message
+///
+/// This structure must be freed using [netgauze_bmp_route_mirroring_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpRouteMirroring {
+    /// Mirrored BGP PDUs, in the order of the message
+    pub messages: OwnedSlice<BmpMirroredBgpMessage>,
+    /// An Information TLV said that the mirrored PDU is errored
+    pub errored_pdu: bool,
+    /// An Information TLV said that messages were lost
+    pub messages_lost: bool,
+    /// Experimental TLVs (types 65531 to 65534), in the order of the message.
+    /// The `val` pointers are borrowed from the BMP Route Mirroring Message
+    pub experimental: OwnedSlice<bmp_log_tlv>,
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_route_mirroring_free(value: BmpRouteMirroring) {
+    value.messages.rust_free();
+    value.experimental.rust_free();
+}
+
+/// [bmp_log_tlv] pointing to the value of an Experimental TLV of type `tlv_type`
+fn experimental_tlv(tlv_type: u16, value: &[u8]) -> bmp_log_tlv {
+    bmp_log_tlv {
+        pen: 0,
+        type_: tlv_type,
+        len: value.len() as u16,
+        val: value.as_ptr() as *mut c_void,
+    }
+}
+
+pub type BmpRouteMirroringResult = CResult<BmpRouteMirroring, WrongBmpMessageTypeError>;
+
+/// Get a [BmpRouteMirroring] from a BMP Route Mirroring Message
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_route_mirroring_get_info(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpRouteMirroringResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let route_mirroring = match bmp_value {
+        BmpMessageValue::RouteMirroring(route_mirroring) => route_mirroring,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let mut messages = Vec::new();
+    let mut errored_pdu = false;
+    let mut messages_lost = false;
+    let mut experimental = Vec::new();
+
+    for value in route_mirroring.mirrored() {
+        match value {
+            RouteMirroringValue::BgpMessage(message) => messages.push(BmpMirroredBgpMessage {
+                message: Opaque::const_from_ref(message),
+                message_size: message.len(),
+            }),
+            RouteMirroringValue::Information(RouteMirroringInformation::ErroredPdu) => {
+                errored_pdu = true
+            }
+            RouteMirroringValue::Information(RouteMirroringInformation::MessageLost) => {
+                messages_lost = true
+            }
+            RouteMirroringValue::Experimental65531(value) => {
+                experimental.push(experimental_tlv(65531, value))
+            }
+            RouteMirroringValue::Experimental65532(value) => {
+                experimental.push(experimental_tlv(65532, value))
+            }
+            RouteMirroringValue::Experimental65533(value) => {
+                experimental.push(experimental_tlv(65533, value))
+            }
+            RouteMirroringValue::Experimental65534(value) => {
+                experimental.push(experimental_tlv(65534, value))
+            }
+        }
+    }
+
+    CResult::Ok(BmpRouteMirroring {
+        messages: OwnedSlice::from_vec(messages),
+        errored_pdu,
+        messages_lost,
+        experimental: OwnedSlice::from_vec(experimental),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::c_char;
+    use std::slice;
+
+    use netgauze_bgp_pkt::BgpMessage;
+
+    use crate::capi::bmp::parse::{netgauze_bmp_parse_packet, netgauze_bmp_parse_result_free};
+    use crate::capi::bmp::route_mirroring::{
+        netgauze_bmp_route_mirroring_free, netgauze_bmp_route_mirroring_get_info,
+    };
+    use crate::capi::bmp::test_util::{end_of_rib, peer_message};
+    use crate::cresult::CResult;
+
+    #[test]
+    fn test_route_mirroring_get_info() {
+        let update = end_of_rib();
+        // BGP Message TLV
+        let mut body = vec![0, 0, 0, update.len() as u8];
+        body.extend_from_slice(&update);
+        // Information TLVs: Errored PDU and Messages Lost
+        body.extend_from_slice(&[0, 1, 0, 2, 0, 0]);
+        body.extend_from_slice(&[0, 1, 0, 2, 0, 1]);
+        // Experimental TLV 65531
+        body.extend_from_slice(&[0xff, 0xfb, 0, 2, 0xca, 0xfe]);
+        let message = peer_message(6, 0, 0, 0, &body);
+
+        let result = unsafe {
+            netgauze_bmp_parse_packet(message.as_ptr() as *const c_char, message.len() as u32)
+        };
+        let parsed = match &result {
+            CResult::Ok(parsed) => parsed,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+
+        let route_mirroring = match unsafe { netgauze_bmp_route_mirroring_get_info(parsed.message) }
+        {
+            CResult::Ok(route_mirroring) => route_mirroring,
+            CResult::Err(err) => panic!("unexpected error {err:?}"),
+        };
+        assert!(route_mirroring.errored_pdu);
+        assert!(route_mirroring.messages_lost);
+
+        let messages = unsafe { route_mirroring.messages.as_slice() };
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].message_size, update.len());
+        assert!(matches!(
+            unsafe { (*messages[0].message).as_ref() },
+            BgpMessage::Update(_)
+        ));
+
+        let experimental = unsafe { route_mirroring.experimental.as_slice() };
+        assert_eq!(experimental.len(), 1);
+        assert_eq!((experimental[0].type_, experimental[0].len), (65531, 2));
+        assert_eq!(
+            unsafe { slice::from_raw_parts(experimental[0].val as *const u8, 2) },
+            [0xca, 0xfe]
+        );
+
+        netgauze_bmp_route_mirroring_free(route_mirroring);
+        netgauze_bmp_parse_result_free(result);
+    }
+}
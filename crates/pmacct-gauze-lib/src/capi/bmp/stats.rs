@@ -1,22 +1,111 @@
-use netgauze_bmp_pkt::BmpMessageValue;
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+use std::ptr;
 
-use pmacct_gauze_bindings::bmp_log_stats;
+use netgauze_bmp_pkt::{BmpMessageValue, PeerKey, StatisticsCounter};
+
+use pmacct_gauze_bindings::{afi_t, bmp_log_stats, safi_t};
 
 use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::context_cache::ContextCache;
 use crate::cresult::CResult;
 use crate::cslice::OwnedSlice;
 use crate::cslice::RustFree;
+use crate::extensions::bmp_message::{BmpRibType, ExtendBmpMessage, ExtendBmpPeerHeader};
 use crate::extensions::bmp_statistics::ExtendBmpStatistics;
-use crate::free_cslice_t;
 use crate::log::{pmacct_log, LogPriority};
 use crate::opaque::Opaque;
+use crate::{free_cslice_t, free_rust_raw_box, make_default};
 
 pub type BmpStatsResult = CResult<OwnedSlice<bmp_log_stats>, WrongBmpMessageTypeError>;
 
 free_cslice_t!(bmp_log_stats);
 
+/// A BMP statistics counter, including the ones whose AFI/SAFI pmacct cannot map
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BmpStatsCounter {
+    pub cnt_type: u16,
+    /// pmacct AFI, 0 if the counter is not per AFI/SAFI or pmacct does not support the AFI/SAFI
+    pub cnt_afi: afi_t,
+    /// pmacct SAFI, 0 if the counter is not per AFI/SAFI or pmacct does not support the AFI/SAFI
+    pub cnt_safi: safi_t,
+    /// IANA AFI, 0 if the counter is not per AFI/SAFI
+    pub iana_afi: u16,
+    /// IANA SAFI, 0 if the counter is not per AFI/SAFI
+    pub iana_safi: u8,
+    /// The counter is a gauge (number of routes) and not a monotonic counter
+    pub is_gauge: bool,
+    /// `cnt_data` holds the value of the counter.
+    /// Experimental and unknown counters only have one if their raw value is 4 or 8 bytes long
+    pub has_cnt_data: bool,
+    pub cnt_data: u64,
+    /// Raw value of experimental and unknown counters, borrowed from the BMP message.
+    /// null for counters NetGauze decodes
+    pub raw_value: *const u8,
+    pub raw_value_len: usize,
+}
+
+free_cslice_t!(BmpStatsCounter);
+
+pub type BmpStatsCountersResult = CResult<OwnedSlice<BmpStatsCounter>, WrongBmpMessageTypeError>;
+
+impl From<&StatisticsCounter> for BmpStatsCounter {
+    fn from(stat: &StatisticsCounter) -> Self {
+        let cnt_type = match stat.get_type() {
+            Ok(type_) => type_ as u16,
+            Err(code) => code,
+        };
+
+        let (cnt_afi, cnt_safi) = stat.get_afi_safi().ok().flatten().unwrap_or((0, 0));
+
+        let (iana_afi, iana_safi) = match stat.get_address_type() {
+            None => (0, 0),
+            Some(address_type) => (
+                address_type.address_family() as u16,
+                address_type.subsequent_address_family() as u8,
+            ),
+        };
+
+        let (has_cnt_data, cnt_data, raw_value, raw_value_len) = match stat.get_raw_value() {
+            None => (true, stat.get_value_as_u64().unwrap_or(0), ptr::null(), 0),
+            Some(raw_value) => {
+                let cnt_data = match raw_value.len() {
+                    4 => Some(u32::from_be_bytes(raw_value.try_into().unwrap()) as u64),
+                    8 => Some(u64::from_be_bytes(raw_value.try_into().unwrap())),
+                    _ => None,
+                };
+
+                (
+                    cnt_data.is_some(),
+                    cnt_data.unwrap_or(0),
+                    raw_value.as_ptr(),
+                    raw_value.len(),
+                )
+            }
+        };
+
+        BmpStatsCounter {
+            cnt_type,
+            cnt_afi,
+            cnt_safi,
+            iana_afi,
+            iana_safi,
+            is_gauge: stat.is_gauge(),
+            has_cnt_data,
+            cnt_data,
+            raw_value,
+            raw_value_len,
+        }
+    }
+}
+
 /// Get an [OwnedSlice<bmp_log_stats>] from a BMP Statistics Message
 ///
+/// [bmp_log_stats] can only hold the pmacct AFI/SAFI of a counter, so per-AFI/SAFI counters
+/// that pmacct cannot map are skipped, as are experimental and unknown counters.
+/// Use [netgauze_bmp_stats_get_counters] to get all of them.
+///
 /// # Safety
 /// `bmp_message_value_opaque` should be not null and point to valid data
 #[no_mangle]
@@ -90,3 +179,544 @@ pub unsafe extern "C" fn netgauze_bmp_stats_get_stats(
     let slice = OwnedSlice::from_vec(result);
     CResult::Ok(slice)
 }
+
+/// Get an [OwnedSlice<BmpStatsCounter>] from a BMP Statistics Message.
+///
+/// Unlike [netgauze_bmp_stats_get_stats], no counter is skipped: per-AFI/SAFI counters
+/// are kept when pmacct cannot map their AFI/SAFI, and experimental or unknown counters
+/// are delivered raw.
+///
+/// The raw values borrow from the BMP message and are only valid as long as it is.
+///
+/// # Safety
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_message_value_opaque` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stats_get_counters(
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> BmpStatsCountersResult {
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let stats = match bmp_value {
+        BmpMessageValue::StatisticsReport(stats) => stats,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let result = stats.counters().iter().map(BmpStatsCounter::from).collect();
+
+    CResult::Ok(OwnedSlice::from_vec(result))
+}
+
+/// Identifies a counter of a peer across BMP Statistics Messages
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) struct BmpStatsCounterKey {
+    pub(crate) cnt_type: u16,
+    pub(crate) iana_afi: u16,
+    pub(crate) iana_safi: u8,
+}
+
+impl From<&BmpStatsCounter> for BmpStatsCounterKey {
+    fn from(counter: &BmpStatsCounter) -> Self {
+        Self {
+            cnt_type: counter.cnt_type,
+            iana_afi: counter.iana_afi,
+            iana_safi: counter.iana_safi,
+        }
+    }
+}
+
+/// Last BMP Statistics Message received for a peer
+#[derive(Debug, Clone)]
+pub struct BmpPeerStats {
+    /// Timestamp of the per-peer header of the message in microseconds since the epoch,
+    /// or the time the message was processed if the router did not set it.
+    /// None if neither is known
+    pub(crate) timestamp_micros: Option<i64>,
+    /// RIB the statistics of the peer are about, from its per-peer header
+    pub(crate) rib_type: Option<BmpRibType>,
+    pub(crate) counters: HashMap<BmpStatsCounterKey, u64>,
+}
+
+/// Last statistics of each peer of a BMP router, used to compute deltas and rates
+#[derive(Debug, Clone, Default)]
+pub struct BmpStatsStore {
+    /// Name of the BMP router, used as label when rendering the statistics
+    pub(crate) router: String,
+    pub(crate) peers: ContextCache<PeerKey, BmpPeerStats>,
+}
+
+impl BmpStatsStore {
+    pub fn remove_peer(&mut self, peer_key: &PeerKey) {
+        self.peers.remove(peer_key);
+    }
+}
+
+free_rust_raw_box!(Opaque<BmpStatsStore>, Opaque_BmpStatsStore);
+make_default!(Opaque<BmpStatsStore>, Opaque_BmpStatsStore);
+
+/// Set the name of the BMP router of a [BmpStatsStore],
+/// used as `router` label by [crate::capi::bmp::openmetrics::netgauze_bmp_stats_render_openmetrics]
+///
+/// # Safety
+/// `bmp_stats_store` should be not null and point to valid data
+/// `router` should be not null and point to a valid NUL-terminated string
+///
+/// This function does not consume the `bmp_stats_store` and `router` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stats_store_set_router(
+    bmp_stats_store: *mut Opaque<BmpStatsStore>,
+    router: *const c_char,
+) {
+    let bmp_stats_store = unsafe { bmp_stats_store.as_mut().unwrap().as_mut() };
+    let router = unsafe { CStr::from_ptr(router) };
+
+    bmp_stats_store.router = router.to_string_lossy().into_owned();
+}
+
+/// A [BmpStatsCounter] and its evolution since the previous report of the same peer
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BmpStatsDelta {
+    pub counter: BmpStatsCounter,
+    /// The counter has a value and was in the previous report of the peer:
+    /// `delta`, `interval_secs` and `rate` are valid
+    pub has_delta: bool,
+    /// The monotonic counter decreased since the previous report: it has been reset
+    /// and `delta` is its new value. A 32-bit counter that was close to [u32::MAX]
+    /// and increased at a plausible rate is considered to have wrapped around instead
+    pub is_reset: bool,
+    /// Change of the value, may be negative for gauges
+    pub delta: i64,
+    /// Seconds elapsed since the previous report of the peer, from the timestamps of their
+    /// per-peer headers or the time they were processed when a timestamp is 0.
+    /// 0 if one of them is unknown or they are not increasing
+    pub interval_secs: f64,
+    /// `delta` per second, 0 if `interval_secs` is 0
+    pub rate: f64,
+}
+
+free_cslice_t!(BmpStatsDelta);
+
+pub type BmpStatsDeltaResult = CResult<OwnedSlice<BmpStatsDelta>, WrongBmpMessageTypeError>;
+
+/// Whether a monotonic counter is 32-bit and wraps around at [u32::MAX]
+fn is_counter32(counter: &BmpStatsCounter) -> bool {
+    if counter.raw_value.is_null() {
+        // All the monotonic counters NetGauze decodes are 32-bit
+        !counter.is_gauge
+    } else {
+        counter.raw_value_len == 4
+    }
+}
+
+/// A 32-bit counter that decreases may only have wrapped around if its previous value
+/// was that close to [u32::MAX], and its wrapped delta is at most that large
+const COUNTER32_WRAP_MARGIN: u32 = 1 << 24;
+
+/// Highest rate, in counts per second, at which a 32-bit counter may have wrapped around
+const COUNTER32_MAX_WRAP_RATE: f64 = 1_000_000.0;
+
+/// Whether a 32-bit counter that went from `previous_value` to `value` in `interval_secs`
+/// wrapped around rather than was reset. Without interval only the values are checked.
+fn is_counter32_wraparound(previous_value: u64, value: u64, interval_secs: f64) -> bool {
+    let (previous_value, value) = (previous_value as u32, value as u32);
+    let delta = value.wrapping_sub(previous_value);
+
+    previous_value >= u32::MAX - COUNTER32_WRAP_MARGIN
+        && delta <= COUNTER32_WRAP_MARGIN
+        && (interval_secs <= 0.0 || delta as f64 / interval_secs <= COUNTER32_MAX_WRAP_RATE)
+}
+
+/// Timestamp in microseconds of a BMP Statistics Message: the one of its per-peer header,
+/// or `now` in seconds if the router left it to 0. None if both are unknown
+fn stats_timestamp_micros(header_micros: Option<i64>, now: u64) -> Option<i64> {
+    header_micros
+        .filter(|micros| *micros != 0)
+        .or_else(|| (now != 0).then_some(now as i64 * 1_000_000))
+}
+
+/// Seconds elapsed between two per-peer header timestamps in microseconds
+fn stats_interval_secs(previous_micros: Option<i64>, micros: Option<i64>) -> f64 {
+    match (previous_micros, micros) {
+        (Some(previous_micros), Some(micros)) if micros > previous_micros => {
+            (micros - previous_micros) as f64 / 1_000_000.0
+        }
+        _ => 0.0,
+    }
+}
+
+fn make_stats_delta(
+    counter: BmpStatsCounter,
+    previous: Option<&BmpPeerStats>,
+    interval_secs: f64,
+) -> BmpStatsDelta {
+    let previous_value = previous
+        .filter(|_| counter.has_cnt_data)
+        .and_then(|previous| previous.counters.get(&BmpStatsCounterKey::from(&counter)));
+
+    let (has_delta, is_reset, delta) = match previous_value {
+        None => (false, false, 0),
+        Some(&previous_value) => {
+            if counter.is_gauge {
+                (true, false, counter.cnt_data as i64 - previous_value as i64)
+            } else if counter.cnt_data >= previous_value {
+                (true, false, (counter.cnt_data - previous_value) as i64)
+            } else if is_counter32(&counter)
+                && is_counter32_wraparound(previous_value, counter.cnt_data, interval_secs)
+            {
+                let delta = (counter.cnt_data as u32).wrapping_sub(previous_value as u32);
+                (true, false, delta as i64)
+            } else {
+                (true, true, counter.cnt_data as i64)
+            }
+        }
+    };
+
+    let rate = if has_delta && interval_secs > 0.0 {
+        delta as f64 / interval_secs
+    } else {
+        0.0
+    };
+
+    BmpStatsDelta {
+        counter,
+        has_delta,
+        is_reset,
+        delta,
+        interval_secs: if has_delta { interval_secs } else { 0.0 },
+        rate,
+    }
+}
+
+/// Record a BMP Statistics Message in [BmpStatsStore] and get a [BmpStatsDelta] for each of
+/// its counters, compared to the previous BMP Statistics Message of the same peer.
+///
+/// Rates are computed from the timestamps of the per-peer headers. Routers may set them to 0,
+/// `now`, the current time in seconds, is used instead for these messages. 0 if unknown.
+///
+/// The returned [OwnedSlice<BmpStatsDelta>] must be freed with [CSlice_free_BmpStatsDelta].
+/// The raw values of the counters borrow from the BMP message, see [netgauze_bmp_stats_get_counters]
+///
+/// # Safety
+/// `bmp_stats_store` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_stats_store` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stats_store_update(
+    bmp_stats_store: *mut Opaque<BmpStatsStore>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+    now: u64,
+) -> BmpStatsDeltaResult {
+    let bmp_stats_store = unsafe { bmp_stats_store.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let stats = match bmp_value {
+        BmpMessageValue::StatisticsReport(stats) => stats,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let timestamp_micros = stats_timestamp_micros(
+        stats
+            .peer_header()
+            .timestamp()
+            .map(|timestamp| timestamp.timestamp_micros()),
+        now,
+    );
+    let peer_key = PeerKey::from_peer_header(stats.peer_header());
+    let previous = bmp_stats_store.peers.get(&peer_key);
+    let interval_secs = previous
+        .map(|previous| stats_interval_secs(previous.timestamp_micros, timestamp_micros))
+        .unwrap_or(0.0);
+
+    let result: Vec<BmpStatsDelta> = stats
+        .counters()
+        .iter()
+        .map(|stat| make_stats_delta(BmpStatsCounter::from(stat), previous, interval_secs))
+        .collect();
+
+    let counters = result
+        .iter()
+        .filter(|delta| delta.counter.has_cnt_data)
+        .map(|delta| {
+            (
+                BmpStatsCounterKey::from(&delta.counter),
+                delta.counter.cnt_data,
+            )
+        })
+        .collect();
+
+    bmp_stats_store.peers.insert(
+        peer_key,
+        BmpPeerStats {
+            timestamp_micros,
+            rib_type: stats.peer_header().rib_type(),
+            counters,
+        },
+    );
+
+    CResult::Ok(OwnedSlice::from_vec(result))
+}
+
+/// Delete the statistics of the peer of a [BmpMessageValue] from [BmpStatsStore].
+/// This should be called on Peer Down.
+///
+/// # Safety
+/// `bmp_stats_store` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_stats_store` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stats_store_delete(
+    bmp_stats_store: *mut Opaque<BmpStatsStore>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) {
+    let bmp_stats_store = unsafe { bmp_stats_store.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    if let Some(peer_header) = bmp_value.get_peer_header() {
+        bmp_stats_store.remove_peer(&PeerKey::from_peer_header(peer_header));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use netgauze_bmp_pkt::{CounterU32, GaugeU64, StatisticsCounter};
+    use netgauze_iana::address_family::AddressType;
+
+    use pmacct_gauze_bindings::{afi_t, safi_t, AFI_IP, SAFI_UNICAST};
+
+    use crate::capi::bmp::stats::{
+        make_stats_delta, stats_interval_secs, stats_timestamp_micros, BmpPeerStats,
+        BmpStatsCounter, BmpStatsCounterKey,
+    };
+    use crate::extensions::bmp_statistics::ExtendBmpStatistics;
+
+    fn peer_stats(timestamp_micros: Option<i64>, counters: &[&BmpStatsCounter]) -> BmpPeerStats {
+        BmpPeerStats {
+            timestamp_micros,
+            rib_type: None,
+            counters: counters
+                .iter()
+                .map(|counter| (BmpStatsCounterKey::from(*counter), counter.cnt_data))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_stats_counter_conversion() {
+        let counter = BmpStatsCounter::from(&StatisticsCounter::NumberOfDuplicateWithdraws(
+            CounterU32::new(5),
+        ));
+        assert_eq!(counter.cnt_type, 2);
+        assert_eq!((counter.cnt_afi, counter.cnt_safi), (0, 0));
+        assert_eq!((counter.iana_afi, counter.iana_safi), (0, 0));
+        assert!(!counter.is_gauge);
+        assert!(counter.has_cnt_data);
+        assert_eq!(counter.cnt_data, 5);
+        assert!(counter.raw_value.is_null());
+
+        let counter = BmpStatsCounter::from(&StatisticsCounter::NumberOfRoutesInLocRib(
+            GaugeU64::new(1 << 40),
+        ));
+        assert_eq!(counter.cnt_type, 8);
+        assert!(counter.is_gauge);
+        assert_eq!(counter.cnt_data, 1 << 40);
+    }
+
+    #[test]
+    fn test_stats_counter_per_afi_safi_conversion() {
+        let counter = BmpStatsCounter::from(&StatisticsCounter::NumberOfRoutesInPerAfiSafiLocRib(
+            AddressType::Ipv4Unicast,
+            GaugeU64::new(10),
+        ));
+        assert_eq!(counter.cnt_type, 10);
+        assert_eq!(
+            (counter.cnt_afi, counter.cnt_safi),
+            (AFI_IP as afi_t, SAFI_UNICAST as safi_t)
+        );
+        assert_eq!((counter.iana_afi, counter.iana_safi), (1, 1));
+        assert!(counter.is_gauge);
+        assert_eq!(counter.cnt_data, 10);
+
+        // pmacct has no AFI/SAFI for BGP-LS VPN, the counter keeps the IANA ones
+        let counter =
+            BmpStatsCounter::from(&StatisticsCounter::NumberOfRoutesInPerAfiSafiAdjRibIn(
+                AddressType::BgpLsVpn,
+                GaugeU64::new(3),
+            ));
+        assert_eq!(counter.cnt_type, 9);
+        assert_eq!((counter.cnt_afi, counter.cnt_safi), (0, 0));
+        assert_eq!((counter.iana_afi, counter.iana_safi), (16388, 72));
+        assert!(counter.is_gauge);
+        assert!(counter.has_cnt_data);
+        assert_eq!(counter.cnt_data, 3);
+    }
+
+    #[test]
+    fn test_stats_counter_raw_value_conversion() {
+        let stat = StatisticsCounter::Experimental65531(vec![0, 0, 0, 7]);
+        let counter = BmpStatsCounter::from(&stat);
+        assert_eq!(counter.cnt_type, 65531);
+        assert!(!counter.is_gauge);
+        assert!(counter.has_cnt_data);
+        assert_eq!(counter.cnt_data, 7);
+        assert_eq!(counter.raw_value_len, 4);
+
+        let stat = StatisticsCounter::Unknown(100, vec![0, 0, 0, 0, 0, 0, 1, 0]);
+        let counter = BmpStatsCounter::from(&stat);
+        assert_eq!(counter.cnt_type, 100);
+        assert!(counter.has_cnt_data);
+        assert_eq!(counter.cnt_data, 256);
+
+        // Values that are neither 4 nor 8 bytes long are only available raw
+        let stat = StatisticsCounter::Unknown(100, vec![1, 2, 3]);
+        let counter = BmpStatsCounter::from(&stat);
+        assert!(!counter.has_cnt_data);
+        assert_eq!(counter.cnt_data, 0);
+        assert_eq!(counter.raw_value, stat.get_raw_value().unwrap().as_ptr());
+        assert_eq!(counter.raw_value_len, 3);
+    }
+
+    #[test]
+    fn test_stats_interval() {
+        assert_eq!(stats_interval_secs(Some(1_000_000), Some(3_500_000)), 2.5);
+        assert_eq!(stats_interval_secs(None, Some(3_500_000)), 0.0);
+        assert_eq!(stats_interval_secs(Some(1_000_000), None), 0.0);
+        // Timestamps going backwards give no rate
+        assert_eq!(stats_interval_secs(Some(3_500_000), Some(1_000_000)), 0.0);
+    }
+
+    #[test]
+    fn test_stats_timestamp() {
+        assert_eq!(stats_timestamp_micros(Some(1_500_000), 10), Some(1_500_000));
+        // Routers may send a zero timestamp, the time of the caller is used then
+        assert_eq!(stats_timestamp_micros(Some(0), 10), Some(10_000_000));
+        assert_eq!(stats_timestamp_micros(None, 10), Some(10_000_000));
+        assert_eq!(stats_timestamp_micros(Some(0), 0), None);
+    }
+
+    #[test]
+    fn test_stats_delta() {
+        let before = BmpStatsCounter::from(&StatisticsCounter::NumberOfDuplicateWithdraws(
+            CounterU32::new(5),
+        ));
+        let after = BmpStatsCounter::from(&StatisticsCounter::NumberOfDuplicateWithdraws(
+            CounterU32::new(25),
+        ));
+
+        let delta = make_stats_delta(after, None, 10.0);
+        assert!(!delta.has_delta);
+        assert_eq!(
+            (delta.delta, delta.interval_secs, delta.rate),
+            (0, 0.0, 0.0)
+        );
+
+        let previous = peer_stats(Some(0), &[&before]);
+        let delta = make_stats_delta(after, Some(&previous), 10.0);
+        assert!(delta.has_delta);
+        assert!(!delta.is_reset);
+        assert_eq!(
+            (delta.delta, delta.interval_secs, delta.rate),
+            (20, 10.0, 2.0)
+        );
+
+        // Without interval there is a delta but no rate
+        let delta = make_stats_delta(after, Some(&previous), 0.0);
+        assert!(delta.has_delta);
+        assert_eq!((delta.delta, delta.rate), (20, 0.0));
+
+        // Gauges can decrease
+        let before = BmpStatsCounter::from(&StatisticsCounter::NumberOfRoutesInLocRib(
+            GaugeU64::new(100),
+        ));
+        let after = BmpStatsCounter::from(&StatisticsCounter::NumberOfRoutesInLocRib(
+            GaugeU64::new(40),
+        ));
+        let delta = make_stats_delta(after, Some(&peer_stats(Some(0), &[&before])), 6.0);
+        assert!(delta.has_delta);
+        assert!(!delta.is_reset);
+        assert_eq!((delta.delta, delta.rate), (-60, -10.0));
+    }
+
+    fn duplicate_withdraws(value: u32) -> BmpStatsCounter {
+        BmpStatsCounter::from(&StatisticsCounter::NumberOfDuplicateWithdraws(
+            CounterU32::new(value),
+        ))
+    }
+
+    #[test]
+    fn test_stats_delta_wraparound() {
+        let before = duplicate_withdraws(u32::MAX - 9);
+        let after = duplicate_withdraws(5);
+        let delta = make_stats_delta(after, Some(&peer_stats(Some(0), &[&before])), 1.0);
+        assert!(delta.has_delta);
+        assert!(!delta.is_reset);
+        assert_eq!((delta.delta, delta.rate), (15, 15.0));
+
+        // Without interval, only the values tell it is a wraparound
+        let delta = make_stats_delta(after, Some(&peer_stats(None, &[&before])), 0.0);
+        assert!(!delta.is_reset);
+        assert_eq!(delta.delta, 15);
+
+        // 4 bytes experimental counters are 32-bit too
+        let before = StatisticsCounter::Experimental65531(u32::MAX.to_be_bytes().to_vec());
+        let after = StatisticsCounter::Experimental65531(vec![0, 0, 0, 1]);
+        let before = BmpStatsCounter::from(&before);
+        let delta = make_stats_delta(
+            BmpStatsCounter::from(&after),
+            Some(&peer_stats(Some(0), &[&before])),
+            1.0,
+        );
+        assert!(!delta.is_reset);
+        assert_eq!(delta.delta, 2);
+    }
+
+    #[test]
+    fn test_stats_delta_counter32_reset() {
+        // The router or the session restarted
+        let delta = make_stats_delta(
+            duplicate_withdraws(3),
+            Some(&peer_stats(Some(0), &[&duplicate_withdraws(1000)])),
+            10.0,
+        );
+        assert!(delta.has_delta);
+        assert!(delta.is_reset);
+        assert_eq!((delta.delta, delta.rate), (3, 0.3));
+
+        // Close to u32::MAX, but the new value is too large for a wraparound
+        let before = duplicate_withdraws(u32::MAX - 9);
+        let delta = make_stats_delta(
+            duplicate_withdraws(u32::MAX / 2),
+            Some(&peer_stats(Some(0), &[&before])),
+            10.0,
+        );
+        assert!(delta.is_reset);
+        assert_eq!(delta.delta, (u32::MAX / 2) as i64);
+
+        // A wraparound would be faster than the counter can plausibly increase
+        let after = duplicate_withdraws(1 << 20);
+        let delta = make_stats_delta(after, Some(&peer_stats(Some(0), &[&before])), 0.5);
+        assert!(delta.is_reset);
+        assert_eq!(delta.delta, 1 << 20);
+        let delta = make_stats_delta(after, Some(&peer_stats(Some(0), &[&before])), 10.0);
+        assert!(!delta.is_reset);
+        assert_eq!(delta.delta, (1 << 20) + 10);
+    }
+
+    #[test]
+    fn test_stats_delta_reset() {
+        let before = StatisticsCounter::Unknown(100, 1000u64.to_be_bytes().to_vec());
+        let after = StatisticsCounter::Unknown(100, 30u64.to_be_bytes().to_vec());
+        let before = BmpStatsCounter::from(&before);
+        let delta = make_stats_delta(
+            BmpStatsCounter::from(&after),
+            Some(&peer_stats(Some(0), &[&before])),
+            10.0,
+        );
+        assert!(delta.has_delta);
+        assert!(delta.is_reset);
+        assert_eq!((delta.delta, delta.rate), (30, 3.0));
+    }
+}
@@ -0,0 +1,508 @@
+use std::ffi::c_char;
+use std::slice;
+
+use c_str_macro::c_str;
+use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
+
+use crate::capi::bmp::parse::{
+    netgauze_bmp_parse_error_str, netgauze_bmp_parse_packet_with_options,
+    netgauze_bmp_parse_result_free, BmpParseError, BmpParseOptions, ParsedBmp,
+    BMP_COMMON_HEADER_LENGTH, BMP_MAX_MESSAGE_LENGTH, BMP_VERSION_4,
+};
+use crate::coption::COption;
+use crate::cresult::CResult;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::opaque::Opaque;
+use crate::{free_rust_raw_box, make_rust_raw_box_pointer};
+
+/// BMP version of RFC 7854
+const BMP_VERSION: u8 = 3;
+
+/// Maximum message length used when 0 is given to [netgauze_bmp_stream_decoder_new]
+pub const BMP_STREAM_DEFAULT_MAX_MESSAGE_LENGTH: u32 = BMP_MAX_MESSAGE_LENGTH;
+
+#[repr(C)]
+#[derive(Debug)]
+pub enum BmpStreamError {
+    /// The stream does not start with a valid BMP common header anymore
+    FramingLost { version: u8, length: u32 },
+    /// The router speaks a BMP version that is not supported
+    UnsupportedVersion { version: u8 },
+    /// The common header announces a message longer than the configured maximum
+    MessageTooLarge { length: u32, max_length: u32 },
+    /// The message was framed correctly but could not be parsed. It has been dropped.
+    ParseError(BmpParseError),
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_stream_error_str(error: BmpStreamError) -> *const c_char {
+    match error {
+        BmpStreamError::FramingLost { .. } => c_str! {
+            "BmpStreamError::FramingLost"
+        }
+        .as_ptr(),
+        BmpStreamError::UnsupportedVersion { .. } => c_str! {
+            "BmpStreamError::UnsupportedVersion"
+        }
+        .as_ptr(),
+        BmpStreamError::MessageTooLarge { .. } => c_str! {
+            "BmpStreamError::MessageTooLarge"
+        }
+        .as_ptr(),
+        BmpStreamError::ParseError(err) => netgauze_bmp_parse_error_str(err),
+    }
+}
+
+/// Messages decoded from the bytes given to a [BmpStreamDecoder].
+///
+/// This structure must be freed using [netgauze_bmp_stream_output_free]
+#[repr(C)]
+#[derive(Debug)]
+pub struct BmpStreamOutput {
+    pub messages: OwnedSlice<ParsedBmp>,
+    /// Error that stopped the decoding. The BMP session should be closed
+    /// unless it is a [BmpStreamError::ParseError].
+    /// When resynchronization is enabled, only [BmpStreamError::UnsupportedVersion]
+    /// and [BmpStreamError::MessageTooLarge] are reported.
+    pub error: COption<BmpStreamError>,
+    /// Bytes dropped to resynchronize on the next plausible message
+    pub skipped_bytes: u32,
+}
+
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_stream_output_free(output: BmpStreamOutput) {
+    output.messages.rust_free();
+    if let COption::Some(BmpStreamError::ParseError(err)) = output.error {
+        netgauze_bmp_parse_result_free(CResult::Err(err));
+    }
+}
+
+/// Decoder for a BMP TCP stream.
+///
+/// It is fed with raw TCP segments of any size, keeps incomplete messages until
+/// the next segment and parses the complete ones with [netgauze_bmp_parse_packet_with_options],
+/// using its own [BmpParsingContext] and the [BmpParseOptions] of the session.
+/// The framing follows NetGauze's `BmpCodec`: messages are delimited by the length of
+/// their common header, which is checked before waiting for the rest of the message.
+///
+/// `BmpCodec` itself is not used: its `decode` frames and parses in one step with its own
+/// private [BmpParsingContext], so it can neither rewrite BMP v4 messages, return a
+/// [ParsedBmp] nor apply the [BmpParseOptions]. It also waits for any announced length
+/// and cannot resynchronize after a corrupt header.
+#[derive(Debug)]
+pub struct BmpStreamDecoder {
+    parsing_context: BmpParsingContext,
+    options: BmpParseOptions,
+    buffer: Vec<u8>,
+    max_message_length: u32,
+    resync: bool,
+}
+
+impl BmpStreamDecoder {
+    pub fn new(max_message_length: u32) -> Self {
+        Self {
+            parsing_context: BmpParsingContext::default(),
+            options: BmpParseOptions::default(),
+            buffer: Vec::new(),
+            max_message_length: match max_message_length {
+                0 => BMP_STREAM_DEFAULT_MAX_MESSAGE_LENGTH,
+                max_message_length => max_message_length.min(BMP_MAX_MESSAGE_LENGTH),
+            },
+            resync: false,
+        }
+    }
+
+    /// When enabled, corrupt or unparsable messages are skipped and the decoder
+    /// scans forward for the next plausible common header instead of failing.
+    /// Unsupported versions and messages too large are still reported.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.resync = resync;
+    }
+
+    /// Options used to parse each complete message.
+    /// [BmpParseOptions::now] is replaced by the time given to [BmpStreamDecoder::feed].
+    pub fn set_options(&mut self, options: BmpParseOptions) {
+        self.options = options;
+    }
+
+    pub fn parsing_context_mut(&mut self) -> &mut BmpParsingContext {
+        &mut self.parsing_context
+    }
+
+    /// Number of bytes waiting for the rest of their message
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Check the common header at the start of `bytes`.
+    /// Returns the message length, or [None] if the header is incomplete.
+    fn check_common_header(&self, bytes: &[u8]) -> Result<Option<u32>, BmpStreamError> {
+        let version = match bytes.first() {
+            Some(version) => *version,
+            None => return Ok(None),
+        };
+
+        // BMP versions 1 and 2 predate RFC 7854
+        if matches!(version, 1 | 2) {
+            return Err(BmpStreamError::UnsupportedVersion { version });
+        }
+
+        if bytes.len() < BMP_COMMON_HEADER_LENGTH {
+            return if is_supported_version(version) {
+                Ok(None)
+            } else {
+                Err(BmpStreamError::FramingLost { version, length: 0 })
+            };
+        }
+
+        let length = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        if !is_supported_version(version) || (length as usize) < BMP_COMMON_HEADER_LENGTH {
+            return Err(BmpStreamError::FramingLost { version, length });
+        }
+
+        if length > self.max_message_length {
+            return Err(BmpStreamError::MessageTooLarge {
+                length,
+                max_length: self.max_message_length,
+            });
+        }
+
+        Ok(Some(length))
+    }
+
+    /// Feed bytes received from the router at time `now`, in seconds,
+    /// and parse all the complete messages
+    pub fn feed(&mut self, bytes: &[u8], now: u64) -> BmpStreamOutput {
+        self.buffer.extend_from_slice(bytes);
+        self.options.now = now;
+
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        let mut skipped_bytes = 0;
+
+        let error = loop {
+            let pending = &self.buffer[offset..];
+            let length = match self.check_common_header(pending) {
+                Ok(Some(length)) if pending.len() >= length as usize => length,
+                Ok(_) => break None,
+                Err(BmpStreamError::FramingLost { .. }) if self.resync => {
+                    // Look for the next header after the current corrupt one
+                    let skip = find_bmp_header(&pending[1..], self.max_message_length)
+                        .map(|position| position + 1)
+                        .unwrap_or(pending.len());
+                    offset += skip;
+                    skipped_bytes += skip;
+                    continue;
+                }
+                Err(err) => break Some(err),
+            };
+
+            let result = unsafe {
+                netgauze_bmp_parse_packet_with_options(
+                    pending.as_ptr() as *const c_char,
+                    length,
+                    Opaque::mut_from_mut(&mut self.parsing_context),
+                    self.options.clone(),
+                )
+            };
+
+            offset += length as usize;
+
+            match result {
+                CResult::Ok(parsed) => messages.push(parsed),
+                CResult::Err(err) if self.resync => {
+                    netgauze_bmp_parse_result_free(CResult::Err(err));
+                    skipped_bytes += length as usize;
+                }
+                CResult::Err(err) => break Some(BmpStreamError::ParseError(err)),
+            }
+        };
+
+        self.buffer.drain(..offset);
+
+        BmpStreamOutput {
+            messages: OwnedSlice::from_vec(messages),
+            error: error.into(),
+            skipped_bytes: skipped_bytes as u32,
+        }
+    }
+}
+
+/// BMP v3 is decoded by NetGauze, BMP v4 is rewritten as v3 first
+fn is_supported_version(version: u8) -> bool {
+    version == BMP_VERSION || version == BMP_VERSION_4
+}
+
+/// Check if `bytes` could start with a BMP common header:
+/// version 3 or 4, sane length and known type.
+/// A header truncated by the end of `bytes` is plausible if its available fields are.
+fn is_plausible_bmp_header(bytes: &[u8], max_message_length: u32) -> bool {
+    if !bytes.first().copied().is_some_and(is_supported_version) {
+        return false;
+    }
+
+    if let Some(length) = bytes.get(1..5) {
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]);
+        if (length as usize) < BMP_COMMON_HEADER_LENGTH || length > max_message_length {
+            return false;
+        }
+    }
+
+    // Route Monitoring, Statistics Report, Peer Down, Peer Up, Initiation, Termination,
+    // Route Mirroring and Experimental
+    match bytes.get(5) {
+        Some(message_type) => matches!(message_type, 0..=6 | 251..=254),
+        None => true,
+    }
+}
+
+/// Find the position of the first plausible BMP common header in `bytes`
+pub fn find_bmp_header(bytes: &[u8], max_message_length: u32) -> Option<usize> {
+    (0..bytes.len())
+        .find(|position| is_plausible_bmp_header(&bytes[*position..], max_message_length))
+}
+
+free_rust_raw_box!(Opaque<BmpStreamDecoder>, Opaque_BmpStreamDecoder);
+
+/// Create a [BmpStreamDecoder] for a new BMP session.
+/// A `max_message_length` of 0 uses [BMP_STREAM_DEFAULT_MAX_MESSAGE_LENGTH],
+/// larger values are capped at [BMP_MAX_MESSAGE_LENGTH].
+///
+/// The decoder must be freed using [netgauze_free_Opaque_BmpStreamDecoder]
+#[no_mangle]
+pub extern "C" fn netgauze_bmp_stream_decoder_new(
+    max_message_length: u32,
+) -> *mut Opaque<BmpStreamDecoder> {
+    make_rust_raw_box_pointer(Opaque::from(BmpStreamDecoder::new(max_message_length)))
+}
+
+/// Feed bytes received on a BMP session at time `now`, in seconds, to a [BmpStreamDecoder]
+///
+/// # Safety
+/// `bmp_stream_decoder` should be not null and point to valid data
+/// `buffer` should be not null and point to valid data of length `buf_len`
+///
+/// This function does not consume the `bmp_stream_decoder` and `buffer` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stream_decoder_feed(
+    bmp_stream_decoder: *mut Opaque<BmpStreamDecoder>,
+    buffer: *const c_char,
+    buf_len: u32,
+    now: u64,
+) -> BmpStreamOutput {
+    let bmp_stream_decoder = unsafe { bmp_stream_decoder.as_mut().unwrap().as_mut() };
+    let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, buf_len as usize) };
+
+    bmp_stream_decoder.feed(bytes, now)
+}
+
+/// Enable or disable the resynchronization of a [BmpStreamDecoder] after a corrupt message
+///
+/// # Safety
+/// `bmp_stream_decoder` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_stream_decoder` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stream_decoder_set_resync(
+    bmp_stream_decoder: *mut Opaque<BmpStreamDecoder>,
+    resync: bool,
+) {
+    let bmp_stream_decoder = unsafe { bmp_stream_decoder.as_mut().unwrap().as_mut() };
+
+    bmp_stream_decoder.set_resync(resync);
+}
+
+/// Set the [BmpParseOptions] used by a [BmpStreamDecoder] to parse each complete message
+///
+/// # Safety
+/// `bmp_stream_decoder` should be not null and point to valid data
+/// `options.peer_activity` should be null or point to valid data while the decoder is fed
+/// `options.stats_store` should be null or point to valid data while the decoder is fed
+/// `options.peer_table` should be null or point to valid data while the decoder is fed
+///
+/// This function does not consume the `bmp_stream_decoder`, `options.peer_activity`,
+/// `options.stats_store` and `options.peer_table` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stream_decoder_set_options(
+    bmp_stream_decoder: *mut Opaque<BmpStreamDecoder>,
+    options: BmpParseOptions,
+) {
+    let bmp_stream_decoder = unsafe { bmp_stream_decoder.as_mut().unwrap().as_mut() };
+
+    bmp_stream_decoder.set_options(options);
+}
+
+/// Find the offset of the first plausible BMP common header in a buffer.
+/// A `max_message_length` of 0 uses [BMP_STREAM_DEFAULT_MAX_MESSAGE_LENGTH].
+///
+/// # Safety
+/// `buffer` should be not null and point to valid data of length `buf_len`
+///
+/// This function does not consume the `buffer` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_find_next_header(
+    buffer: *const c_char,
+    buf_len: u32,
+    max_message_length: u32,
+) -> COption<u32> {
+    let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, buf_len as usize) };
+    let max_message_length = match max_message_length {
+        0 => BMP_STREAM_DEFAULT_MAX_MESSAGE_LENGTH,
+        max_message_length => max_message_length,
+    };
+
+    find_bmp_header(bytes, max_message_length)
+        .map(|position| position as u32)
+        .into()
+}
+
+/// Get the number of bytes a [BmpStreamDecoder] keeps until their message is complete
+///
+/// # Safety
+/// `bmp_stream_decoder` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_stream_decoder` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stream_decoder_buffered_bytes(
+    bmp_stream_decoder: *const Opaque<BmpStreamDecoder>,
+) -> usize {
+    let bmp_stream_decoder = unsafe { bmp_stream_decoder.as_ref().unwrap().as_ref() };
+
+    bmp_stream_decoder.buffered_bytes()
+}
+
+/// Get a mutable pointer to the [BmpParsingContext] used by a [BmpStreamDecoder]
+///
+/// # Safety
+/// `bmp_stream_decoder` should be not null and point to valid data
+///
+/// This function does not consume the `bmp_stream_decoder` pointer
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_bmp_stream_decoder_get_parsing_context(
+    bmp_stream_decoder: *mut Opaque<BmpStreamDecoder>,
+) -> *mut Opaque<BmpParsingContext> {
+    let bmp_stream_decoder = unsafe { bmp_stream_decoder.as_mut().unwrap().as_mut() };
+
+    Opaque::mut_from_mut(bmp_stream_decoder.parsing_context_mut())
+}
+
+#[cfg(test)]
+mod test {
+    use netgauze_bmp_pkt::BmpMessageValue;
+
+    use crate::capi::bmp::stream::{
+        netgauze_bmp_stream_output_free, BmpStreamDecoder, BmpStreamError,
+    };
+    use crate::coption::COption;
+
+    /// BMP Initiation with a sysDescr and a sysName TLV
+    fn initiation(sys_name: &[u8]) -> Vec<u8> {
+        let mut message = vec![3, 0, 0, 0, 0, 4];
+        message.extend_from_slice(&[0, 1, 0, 2, b'p', b'm']);
+        message.extend_from_slice(&[0, 2]);
+        message.extend_from_slice(&(sys_name.len() as u16).to_be_bytes());
+        message.extend_from_slice(sys_name);
+
+        let len = message.len() as u32;
+        message[1..5].copy_from_slice(&len.to_be_bytes());
+        message
+    }
+
+    /// Feed `bytes` and return the decoded Initiation messages, formatted for comparison
+    fn feed(decoder: &mut BmpStreamDecoder, bytes: &[u8]) -> Vec<String> {
+        let output = decoder.feed(bytes, 0);
+        assert!(matches!(output.error, COption::None));
+
+        let initiations = unsafe { output.messages.as_slice() }
+            .iter()
+            .map(|parsed| match unsafe { (*parsed.message).as_ref() } {
+                BmpMessageValue::Initiation(initiation) => format!("{:?}", initiation),
+                value => panic!("unexpected message {value:?}"),
+            })
+            .collect();
+
+        netgauze_bmp_stream_output_free(output);
+        initiations
+    }
+
+    #[test]
+    fn test_stream_resync() {
+        let mut decoder = BmpStreamDecoder::new(0);
+        decoder.set_resync(true);
+
+        let mut bytes = vec![0xde, 0xad, 0xbe, 0xef];
+        bytes.extend_from_slice(&initiation(b"r1"));
+
+        let output = decoder.feed(&bytes, 0);
+        assert!(matches!(output.error, COption::None));
+        assert_eq!(unsafe { output.messages.as_slice() }.len(), 1);
+        assert_eq!(output.skipped_bytes, 4);
+        netgauze_bmp_stream_output_free(output);
+        assert_eq!(decoder.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_stream_resync_reports_errors() {
+        let mut decoder = BmpStreamDecoder::new(64);
+        decoder.set_resync(true);
+
+        let output = decoder.feed(&[3, 0, 0, 0, 65, 4], 0);
+        assert!(matches!(
+            output.error,
+            COption::Some(BmpStreamError::MessageTooLarge {
+                length: 65,
+                max_length: 64
+            })
+        ));
+        assert_eq!(output.skipped_bytes, 0);
+        netgauze_bmp_stream_output_free(output);
+
+        let mut decoder = BmpStreamDecoder::new(0);
+        decoder.set_resync(true);
+
+        let output = decoder.feed(&[2, 0, 0, 0, 6, 4], 0);
+        assert!(matches!(
+            output.error,
+            COption::Some(BmpStreamError::UnsupportedVersion { version: 2 })
+        ));
+        netgauze_bmp_stream_output_free(output);
+    }
+
+    #[test]
+    fn test_stream_split_message() {
+        let mut decoder = BmpStreamDecoder::new(0);
+        let message = initiation(b"r1");
+
+        for (index, byte) in message[..message.len() - 1].iter().enumerate() {
+            assert!(feed(&mut decoder, &[*byte]).is_empty());
+            assert_eq!(decoder.buffered_bytes(), index + 1);
+        }
+
+        let messages = feed(&mut decoder, &message[message.len() - 1..]);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("r1"));
+        assert_eq!(decoder.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_stream_merged_messages() {
+        let mut decoder = BmpStreamDecoder::new(0);
+        let third = initiation(b"r3");
+
+        // Two whole messages and the start of a third one in the same segment
+        let mut bytes = initiation(b"r1");
+        bytes.extend_from_slice(&initiation(b"r2"));
+        bytes.extend_from_slice(&third[..8]);
+
+        let messages = feed(&mut decoder, &bytes);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("r1"));
+        assert!(messages[1].contains("r2"));
+        assert_eq!(decoder.buffered_bytes(), 8);
+
+        let messages = feed(&mut decoder, &third[8..]);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("r3"));
+        assert_eq!(decoder.buffered_bytes(), 0);
+    }
+}
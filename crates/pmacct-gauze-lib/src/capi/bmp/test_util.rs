@@ -0,0 +1,46 @@
+//! Raw BMP messages shared by the tests of the BMP C API
+
+/// Address and BGP identifier of the peer of [peer_message]
+pub(crate) const PEER_ADDRESS: [u8; 4] = [192, 0, 2, 1];
+
+/// BMP message of version `version` and type `message_type` followed by `body`
+pub(crate) fn bmp_message(version: u8, message_type: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![version];
+    message.extend_from_slice(&((body.len() + 6) as u32).to_be_bytes());
+    message.push(message_type);
+    message.extend_from_slice(body);
+    message
+}
+
+/// Per-peer header of the IPv4 peer `address` in AS 65000, using `address` as BGP identifier
+pub(crate) fn peer_header(peer_type: u8, flags: u8, rd: u64, address: [u8; 4]) -> Vec<u8> {
+    let mut peer_header = vec![peer_type, flags];
+    peer_header.extend_from_slice(&rd.to_be_bytes());
+    peer_header.extend_from_slice(&[0; 12]);
+    peer_header.extend_from_slice(&address);
+    peer_header.extend_from_slice(&65000u32.to_be_bytes());
+    peer_header.extend_from_slice(&address);
+    peer_header.extend_from_slice(&[0; 8]);
+    peer_header
+}
+
+/// BMP v3 message of type `message_type` with the per-peer header of peer 192.0.2.1
+/// in AS 65000 followed by `body`
+pub(crate) fn peer_message(
+    message_type: u8,
+    peer_type: u8,
+    flags: u8,
+    rd: u64,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut message_body = peer_header(peer_type, flags, rd, PEER_ADDRESS);
+    message_body.extend_from_slice(body);
+    bmp_message(3, message_type, &message_body)
+}
+
+/// BGP UPDATE with no withdrawn routes, no attributes and no NLRI (IPv4 End-of-RIB)
+pub(crate) fn end_of_rib() -> Vec<u8> {
+    let mut update = vec![0xff; 16];
+    update.extend_from_slice(&[0, 23, 2, 0, 0, 0, 0]);
+    update
+}
@@ -1,5 +1,7 @@
 pub mod bgp;
 pub mod bmp;
+pub mod parse_error;
+pub mod rib;
 
 #[no_mangle]
 pub extern "C" fn nonce10() {}
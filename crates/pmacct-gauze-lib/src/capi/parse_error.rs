@@ -0,0 +1,275 @@
+use nom::Needed;
+
+use crate::coption::COption;
+
+/// Length of the BGP message header (marker, length, type)
+const BGP_HEADER_LENGTH: usize = 19;
+
+/// Part of the input a parsing error was found in
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// BGP header: marker, length or message type
+    BgpMessageHeader,
+    BgpOpenMessage,
+    BgpUpdateMessage,
+    BgpNotificationMessage,
+    BgpRouteRefreshMessage,
+    /// BMP common header: version, length or message type
+    BmpMessageHeader,
+    /// BMP message body: per-peer header, TLVs or BGP PDUs
+    BmpMessage,
+}
+
+/// Machine-readable description of a parsing error
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ParseErrorDetails {
+    pub kind: ParseErrorKind,
+    /// Offset of the error from the start of the input
+    pub offset: usize,
+    /// Type of the message, if the header could be read
+    pub message_type: COption<u8>,
+    /// BGP path attribute type or BMP TLV type containing the error, if known
+    pub attribute_type: COption<u16>,
+    /// NOTIFICATION code to send to a BGP peer, 0 if no NOTIFICATION should be sent
+    pub notification_code: u8,
+    pub notification_subcode: u8,
+}
+
+impl ParseErrorDetails {
+    pub fn new(kind: ParseErrorKind, offset: usize, message_type: Option<u8>) -> Self {
+        Self {
+            kind,
+            offset,
+            message_type: message_type.into(),
+            attribute_type: COption::None,
+            notification_code: 0,
+            notification_subcode: 0,
+        }
+    }
+
+    pub fn with_attribute_type(mut self, attribute_type: Option<u16>) -> Self {
+        self.attribute_type = attribute_type.into();
+        self
+    }
+
+    pub fn with_notification(mut self, code: u8, subcode: u8) -> Self {
+        self.notification_code = code;
+        self.notification_subcode = subcode;
+        self
+    }
+}
+
+/// Find the type of the path attribute containing `offset` in a raw BGP UPDATE message
+/// starting at the beginning of `update`
+pub(crate) fn find_update_attribute_type(update: &[u8], offset: usize) -> Option<u8> {
+    let read_u16 = |at: usize| -> Option<usize> {
+        let bytes = update.get(at..at + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+
+    let withdrawn_length = read_u16(BGP_HEADER_LENGTH)?;
+    let attributes_length_at = BGP_HEADER_LENGTH + 2 + withdrawn_length;
+    let attributes_length = read_u16(attributes_length_at)?;
+
+    let mut cursor = attributes_length_at + 2;
+    let attributes_end = cursor + attributes_length;
+    while cursor < attributes_end {
+        let flags = *update.get(cursor)?;
+        let attribute_type = *update.get(cursor + 1)?;
+
+        // Extended Length bit
+        let (value_length, header_length) = if flags & 0x10 != 0 {
+            (read_u16(cursor + 2)?, 4)
+        } else {
+            (*update.get(cursor + 2)? as usize, 3)
+        };
+
+        let attribute_end = cursor + header_length + value_length;
+        if (cursor..attribute_end).contains(&offset) {
+            return Some(attribute_type);
+        }
+
+        cursor = attribute_end;
+    }
+
+    None
+}
+
+/// Recommended UPDATE Message Error subcode (RFC 4271 section 6.3) for an attribute type
+pub(crate) fn update_error_subcode(attribute_type: Option<u8>) -> u8 {
+    match attribute_type {
+        // ORIGIN: Invalid ORIGIN Attribute
+        Some(1) => 6,
+        // AS_PATH: Malformed AS_PATH
+        Some(2) => 11,
+        // NEXT_HOP: Invalid NEXT_HOP Attribute
+        Some(3) => 8,
+        // Other attributes: Optional Attribute Error
+        Some(_) => 9,
+        // Unspecific
+        None => 0,
+    }
+}
+
+/// Find the type of the BMP TLV (2 bytes type, 2 bytes length) containing `offset`
+/// in TLVs starting at `start` and ending at the end of `message`
+pub(crate) fn find_tlv_type(message: &[u8], start: usize, offset: usize) -> Option<u16> {
+    let mut cursor = start;
+    while cursor < message.len() {
+        let header = message.get(cursor..cursor + 4)?;
+        let tlv_type = u16::from_be_bytes([header[0], header[1]]);
+        let tlv_length = u16::from_be_bytes([header[2], header[3]]) as usize;
+
+        let tlv_end = cursor + 4 + tlv_length;
+        if (cursor..tlv_end).contains(&offset) {
+            return Some(tlv_type);
+        }
+
+        cursor = tlv_end;
+    }
+
+    None
+}
+
+/// Number of bytes missing from `input` to contain a whole message, based on its header.
+///
+/// `message_length` reads the message length from a complete header.
+/// A length shorter than the header is not reported here but by the message parser.
+/// A length above `max_length` cannot belong to a valid message and is returned as an error
+/// instead of asking the caller to buffer it.
+pub(crate) fn missing_bytes(
+    input: &[u8],
+    header_length: usize,
+    max_length: usize,
+    message_length: impl Fn(&[u8]) -> usize,
+) -> Result<Option<u32>, usize> {
+    if input.len() < header_length {
+        return Ok(Some((header_length - input.len()) as u32));
+    }
+
+    let message_length = message_length(input);
+    if message_length > max_length {
+        return Err(message_length);
+    }
+
+    Ok((message_length > input.len()).then(|| (message_length - input.len()) as u32))
+}
+
+/// Number of missing bytes reported by nom, 0 if unknown
+pub(crate) fn needed_bytes(needed: &Needed) -> u32 {
+    match needed {
+        Needed::Unknown => 0,
+        Needed::Size(size) => size.get() as u32,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::capi::parse_error::{
+        find_tlv_type, find_update_attribute_type, missing_bytes, update_error_subcode,
+    };
+
+    /// BGP UPDATE with no withdrawn routes, ORIGIN, an extended length AS_PATH and one NLRI
+    fn update() -> Vec<u8> {
+        let mut update = vec![0xff; 16];
+        update.extend_from_slice(&[0, 0, 2]);
+        // Withdrawn Routes Length
+        update.extend_from_slice(&[0, 0]);
+        // Total Path Attribute Length
+        update.extend_from_slice(&[0, 15]);
+        // ORIGIN IGP, at offset 23
+        update.extend_from_slice(&[0x40, 1, 1, 0]);
+        // AS_PATH with one AS_SEQUENCE of one ASN, extended length, at offset 27
+        update.extend_from_slice(&[0x50, 2, 0, 4, 2, 1, 0xfd, 0xe8]);
+        // MED, truncated: announced with 4 bytes but only 1 in the attributes, at offset 35
+        update.extend_from_slice(&[0x80, 4, 4]);
+        // NLRI 10.0.0.0/8
+        update.extend_from_slice(&[8, 10]);
+
+        let len = update.len() as u16;
+        update[16..18].copy_from_slice(&len.to_be_bytes());
+        update
+    }
+
+    #[test]
+    fn test_find_update_attribute_type() {
+        let update = update();
+
+        assert_eq!(find_update_attribute_type(&update, 10), None);
+        assert_eq!(find_update_attribute_type(&update, 23), Some(1));
+        assert_eq!(find_update_attribute_type(&update, 26), Some(1));
+        assert_eq!(find_update_attribute_type(&update, 27), Some(2));
+        assert_eq!(find_update_attribute_type(&update, 34), Some(2));
+        assert_eq!(find_update_attribute_type(&update, 36), Some(4));
+        // The MED claims to go past the end of the attributes
+        assert_eq!(find_update_attribute_type(&update, 41), Some(4));
+        assert_eq!(find_update_attribute_type(&update, 42), None);
+
+        // Truncated before the path attributes
+        assert_eq!(find_update_attribute_type(&update[..20], 25), None);
+    }
+
+    #[test]
+    fn test_update_error_subcode() {
+        assert_eq!(update_error_subcode(Some(1)), 6);
+        assert_eq!(update_error_subcode(Some(2)), 11);
+        assert_eq!(update_error_subcode(Some(3)), 8);
+        assert_eq!(update_error_subcode(Some(32)), 9);
+        assert_eq!(update_error_subcode(None), 0);
+    }
+
+    #[test]
+    fn test_find_tlv_type() {
+        // BMP Initiation: sysDescr "ab", sysName "r1"
+        let mut message = vec![3, 0, 0, 0, 0, 4];
+        message.extend_from_slice(&[0, 1, 0, 2, b'a', b'b']);
+        message.extend_from_slice(&[0, 2, 0, 2, b'r', b'1']);
+
+        assert_eq!(find_tlv_type(&message, 6, 3), None);
+        assert_eq!(find_tlv_type(&message, 6, 6), Some(1));
+        assert_eq!(find_tlv_type(&message, 6, 11), Some(1));
+        assert_eq!(find_tlv_type(&message, 6, 12), Some(2));
+        assert_eq!(find_tlv_type(&message, 6, 17), Some(2));
+        assert_eq!(find_tlv_type(&message, 6, 18), None);
+
+        // Truncated TLV header
+        assert_eq!(find_tlv_type(&message[..14], 6, 13), None);
+    }
+
+    #[test]
+    fn test_missing_bytes() {
+        let length = |header: &[u8]| {
+            u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize
+        };
+        let header = |len: u32| {
+            let mut header = vec![3];
+            header.extend_from_slice(&len.to_be_bytes());
+            header.push(4);
+            header
+        };
+
+        // Incomplete header
+        assert_eq!(missing_bytes(&[3, 0], 6, 1024, length), Ok(Some(4)));
+
+        // Complete header, missing body
+        assert_eq!(missing_bytes(&header(10), 6, 1024, length), Ok(Some(4)));
+
+        // Whole message
+        let mut message = header(10);
+        message.extend_from_slice(&[0; 4]);
+        assert_eq!(missing_bytes(&message, 6, 1024, length), Ok(None));
+
+        // Lengths above the maximum are rejected instead of buffered
+        assert_eq!(
+            missing_bytes(&header(1024), 6, 1024, length),
+            Ok(Some(1018))
+        );
+        assert_eq!(missing_bytes(&header(1025), 6, 1024, length), Err(1025));
+        assert_eq!(
+            missing_bytes(&header(u32::MAX), 6, 1024, length),
+            Err(u32::MAX as usize)
+        );
+    }
+}
@@ -0,0 +1,753 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use netgauze_bgp_pkt::nlri::RouteDistinguisher;
+use netgauze_bgp_pkt::update::BgpUpdateMessage;
+use netgauze_bgp_pkt::BgpMessage;
+use netgauze_bmp_pkt::BmpMessageValue;
+
+use pmacct_gauze_bindings::{afi_t, host_addr, path_id_t, rd_t, safi_t, AFI_IP, AFI_IP6};
+
+use crate::capi::bgp::WrongBgpMessageTypeError;
+use crate::capi::bmp::WrongBmpMessageTypeError;
+use crate::capi::rib::{
+    update_nlri, RibEntry, RibPathKey, RibPrefixKey, RibRoute, RibUpdateSummary,
+};
+use crate::coption::COption;
+use crate::cresult::CResult;
+use crate::extensions::bmp_message::{BmpRibType, ExtendBmpPeerHeader};
+use crate::extensions::rd::RdOriginType;
+use crate::opaque::Opaque;
+use crate::{free_rust_raw_box, make_default};
+
+const NO_NODE: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+struct PrefixTrieNode<V> {
+    /// Prefix of the node, left-aligned and without the bits after `len`
+    address: u128,
+    len: u8,
+    children: [u32; 2],
+    value: Option<V>,
+}
+
+impl<V> PrefixTrieNode<V> {
+    fn new(address: u128, len: u8) -> Self {
+        Self {
+            address,
+            len,
+            children: [NO_NODE; 2],
+            value: None,
+        }
+    }
+}
+
+/// Path-compressed binary trie of prefixes whose nodes are stored in an arena.
+///
+/// There is a node for each prefix with a value and for each prefix where two branches split,
+/// so there are less than twice as many nodes as prefixes. The root is the empty prefix.
+///
+/// Addresses are left-aligned in a [u128], so the same trie works for IPv4 and IPv6.
+#[derive(Debug, Clone)]
+struct PrefixTrie<V> {
+    nodes: Vec<PrefixTrieNode<V>>,
+    /// Indexes of the unused nodes in `nodes`
+    free: Vec<u32>,
+}
+
+impl<V> Default for PrefixTrie<V> {
+    fn default() -> Self {
+        Self {
+            nodes: vec![PrefixTrieNode::new(0, 0)],
+            free: Vec::new(),
+        }
+    }
+}
+
+fn bit(address: u128, index: u8) -> usize {
+    ((address >> (127 - index)) & 1) as usize
+}
+
+fn mask(address: u128, len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        address & (u128::MAX << (128 - len as u32))
+    }
+}
+
+/// Length of the longest common prefix of two addresses, at most `max_len`
+fn common_len(a: u128, b: u128, max_len: u8) -> u8 {
+    ((a ^ b).leading_zeros() as u8).min(max_len)
+}
+
+impl<V> PrefixTrie<V> {
+    fn allocate(&mut self, address: u128, len: u8) -> u32 {
+        let node = PrefixTrieNode::new(mask(address, len), len);
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index as usize] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                (self.nodes.len() - 1) as u32
+            }
+        }
+    }
+
+    fn release(&mut self, index: u32) {
+        self.nodes[index as usize] = PrefixTrieNode::new(0, 0);
+        self.free.push(index);
+    }
+
+    fn get_or_insert_with(
+        &mut self,
+        address: u128,
+        len: u8,
+        default: impl FnOnce() -> V,
+    ) -> &mut V {
+        let address = mask(address, len);
+
+        // The prefix of `node` always contains the inserted prefix
+        let mut node = 0;
+        while self.nodes[node as usize].len != len {
+            let branch = bit(address, self.nodes[node as usize].len);
+            let child = self.nodes[node as usize].children[branch];
+
+            let next = if child == NO_NODE {
+                self.allocate(address, len)
+            } else {
+                let (child_address, child_len) = {
+                    let child = &self.nodes[child as usize];
+                    (child.address, child.len)
+                };
+                let common = common_len(address, child_address, len.min(child_len));
+
+                if common == child_len {
+                    node = child;
+                    continue;
+                } else if common == len {
+                    // The inserted prefix contains the child
+                    let next = self.allocate(address, len);
+                    self.nodes[next as usize].children[bit(child_address, len)] = child;
+                    next
+                } else {
+                    // The inserted prefix and the child split after their common prefix
+                    let next = self.allocate(address, common);
+                    self.nodes[next as usize].children[bit(child_address, common)] = child;
+                    next
+                }
+            };
+
+            self.nodes[node as usize].children[branch] = next;
+            node = next;
+        }
+
+        self.nodes[node as usize].value.get_or_insert_with(default)
+    }
+
+    /// Nodes from the root to the node of a prefix
+    fn find_path(&self, address: u128, len: u8) -> Option<Vec<u32>> {
+        let address = mask(address, len);
+
+        let mut path = vec![0];
+        let mut node = 0;
+        while self.nodes[node as usize].len != len {
+            node = self.nodes[node as usize].children[bit(address, self.nodes[node as usize].len)];
+            if node == NO_NODE {
+                return None;
+            }
+
+            let node_ref = &self.nodes[node as usize];
+            if node_ref.len > len || node_ref.address != mask(address, node_ref.len) {
+                return None;
+            }
+            path.push(node);
+        }
+
+        Some(path)
+    }
+
+    fn get_mut(&mut self, address: u128, len: u8) -> Option<&mut V> {
+        let node = *self.find_path(address, len)?.last()?;
+        self.nodes[node as usize].value.as_mut()
+    }
+
+    /// Remove the value of a prefix and the nodes that are no longer needed
+    fn remove(&mut self, address: u128, len: u8) -> Option<V> {
+        let mut path = self.find_path(address, len)?;
+        let value = self.nodes[*path.last()? as usize].value.take();
+
+        // A node without value is only needed where two branches split, never remove the root
+        while path.len() > 1 {
+            let node = path.pop()?;
+            let parent = *path.last()?;
+            let node_ref = &self.nodes[node as usize];
+            if node_ref.value.is_some() {
+                break;
+            }
+
+            let replacement = match node_ref.children {
+                [NO_NODE, NO_NODE] => NO_NODE,
+                [child, NO_NODE] | [NO_NODE, child] => child,
+                _ => break,
+            };
+            let branch = bit(node_ref.address, self.nodes[parent as usize].len);
+            self.nodes[parent as usize].children[branch] = replacement;
+            self.release(node);
+
+            // Only a removed leaf can leave its parent with a single child
+            if replacement != NO_NODE {
+                break;
+            }
+        }
+
+        value
+    }
+
+    /// Longest prefix containing `address` whose value matches `filter`, with its length
+    fn longest_match(
+        &self,
+        address: u128,
+        max_len: u8,
+        filter: impl Fn(&V) -> bool,
+    ) -> Option<(u8, &V)> {
+        let mut result = None;
+        let mut node = 0;
+        loop {
+            let node_ref = &self.nodes[node as usize];
+            if node_ref.len > max_len || node_ref.address != mask(address, node_ref.len) {
+                break;
+            }
+            if let Some(value) = node_ref.value.as_ref().filter(|value| filter(value)) {
+                result = Some((node_ref.len, value));
+            }
+            if node_ref.len == max_len {
+                break;
+            }
+
+            node = node_ref.children[bit(address, node_ref.len)];
+            if node == NO_NODE {
+                break;
+            }
+        }
+
+        result
+    }
+}
+
+/// A path to a prefix in a [RibLookupTable]
+#[derive(Debug, Clone)]
+struct LookupPath {
+    /// RIB of the monitored router, None for routes received on a BGP session
+    rib_type: Option<BmpRibType>,
+    safi: safi_t,
+    path_id: Option<path_id_t>,
+    /// Origin of the RD of the route, BMP or BGP
+    rd_origin: RdOriginType,
+    route: RibRoute,
+}
+
+impl LookupPath {
+    fn key(&self) -> (Option<BmpRibType>, safi_t, Option<path_id_t>) {
+        (self.rib_type, self.safi, self.path_id)
+    }
+}
+
+/// Paths to a prefix in a [RibLookupTable], by RIB, SAFI and path id
+type LookupPaths = Vec<LookupPath>;
+
+/// Preference of the paths of a RIB in [RibLookupTable] lookups, lowest first.
+/// Loc-RIB has the best paths of the router, then come the ones it accepted.
+fn rib_type_preference(rib_type: Option<BmpRibType>) -> u8 {
+    match rib_type {
+        None | Some(BmpRibType::LocalRib) => 0,
+        Some(BmpRibType::AdjRibInPostPolicy) => 1,
+        Some(BmpRibType::AdjRibInPrePolicy) => 2,
+        Some(BmpRibType::AdjRibOutPostPolicy) => 3,
+        Some(BmpRibType::AdjRibOutPrePolicy) => 4,
+    }
+}
+
+/// Prefixes of a VRF
+#[derive(Debug, Clone, Default)]
+struct LookupVrf {
+    ipv4: PrefixTrie<LookupPaths>,
+    ipv6: PrefixTrie<LookupPaths>,
+}
+
+impl LookupVrf {
+    fn trie(&mut self, prefix: &IpNet) -> (&mut PrefixTrie<LookupPaths>, u128) {
+        match prefix {
+            IpNet::V4(net) => (&mut self.ipv4, (u32::from(net.network()) as u128) << 96),
+            IpNet::V6(net) => (&mut self.ipv6, u128::from(net.network())),
+        }
+    }
+}
+
+/// Prefixes announced by a peer, per VRF, for longest-prefix-match lookups
+#[derive(Debug, Clone, Default)]
+pub struct RibLookupTable {
+    vrfs: HashMap<Option<RouteDistinguisher>, LookupVrf>,
+}
+
+impl RibLookupTable {
+    /// Apply the announcements and withdrawals of a BGP UPDATE message
+    /// received in the `rib_type` RIB, whose RDs come from `rd_origin`
+    pub fn apply_update(
+        &mut self,
+        rib_type: Option<BmpRibType>,
+        rd_origin: RdOriginType,
+        update: &BgpUpdateMessage,
+    ) -> RibUpdateSummary {
+        let mut summary = RibUpdateSummary {
+            end_of_rib: update.end_of_rib().is_some(),
+            ..Default::default()
+        };
+
+        let (announced, withdrawn) = update_nlri(update);
+
+        for nlri in withdrawn {
+            let RibPrefixKey {
+                safi, rd, prefix, ..
+            } = nlri.prefix_key;
+            let key = (rib_type, safi, nlri.path_id);
+
+            let vrf = match self.vrfs.get_mut(&rd) {
+                Some(vrf) => vrf,
+                None => {
+                    summary.withdrawn_unknown += 1;
+                    continue;
+                }
+            };
+
+            let (trie, address) = vrf.trie(&prefix);
+            let removed = match trie.get_mut(address, prefix.prefix_len()) {
+                None => false,
+                Some(paths) => {
+                    let len = paths.len();
+                    paths.retain(|path| path.key() != key);
+                    let removed = paths.len() != len;
+                    if paths.is_empty() {
+                        trie.remove(address, prefix.prefix_len());
+                    }
+                    removed
+                }
+            };
+
+            if removed {
+                summary.withdrawn += 1;
+            } else {
+                summary.withdrawn_unknown += 1;
+            }
+        }
+
+        for (nlri, attributes) in announced {
+            let RibPrefixKey {
+                safi, rd, prefix, ..
+            } = nlri.prefix_key;
+            let path = LookupPath {
+                rib_type,
+                safi,
+                path_id: nlri.path_id,
+                rd_origin,
+                route: RibRoute {
+                    attributes,
+                    label: nlri.label,
+                },
+            };
+
+            let (trie, address) = self.vrfs.entry(rd).or_default().trie(&prefix);
+            let paths = trie.get_or_insert_with(address, prefix.prefix_len(), Vec::new);
+
+            match paths
+                .iter_mut()
+                .find(|previous| previous.key() == path.key())
+            {
+                Some(previous) => {
+                    *previous = path;
+                    summary.implicitly_withdrawn += 1;
+                }
+                None => {
+                    paths.push(path);
+                    summary.announced += 1;
+                }
+            }
+        }
+
+        summary
+    }
+
+    /// Longest prefix containing `address` in the VRF `rd` with a path of `safi`,
+    /// and its preferred path of `safi`: the one of the most preferred RIB
+    /// (see [rib_type_preference]) with the lowest path id
+    fn lookup(
+        &self,
+        address: &IpAddr,
+        safi: safi_t,
+        rd: Option<RouteDistinguisher>,
+    ) -> Option<(IpNet, &LookupPath)> {
+        let vrf = self.vrfs.get(&rd)?;
+        let has_safi = |paths: &LookupPaths| paths.iter().any(|path| path.safi == safi);
+
+        let (prefix, paths) = match address {
+            IpAddr::V4(address) => {
+                let bits = (u32::from(*address) as u128) << 96;
+                let (len, paths) = vrf.ipv4.longest_match(bits, 32, has_safi)?;
+                (IpNet::V4(Ipv4Net::new(*address, len).ok()?.trunc()), paths)
+            }
+            IpAddr::V6(address) => {
+                let (len, paths) = vrf
+                    .ipv6
+                    .longest_match(u128::from(*address), 128, has_safi)?;
+                (IpNet::V6(Ipv6Net::new(*address, len).ok()?.trunc()), paths)
+            }
+        };
+
+        let path = paths
+            .iter()
+            .filter(|path| path.safi == safi)
+            .min_by_key(|path| (rib_type_preference(path.rib_type), path.path_id))?;
+
+        Some((prefix, path))
+    }
+}
+
+free_rust_raw_box!(Opaque<RibLookupTable>, Opaque_RibLookupTable);
+make_default!(Opaque<RibLookupTable>, Opaque_RibLookupTable);
+
+pub type RibLookupTableUpdateResult = CResult<RibUpdateSummary, WrongBgpMessageTypeError>;
+
+/// Apply a BGP UPDATE received on a BGP session to the [RibLookupTable] of its peer.
+/// For BMP, use [netgauze_rib_lookup_table_update_bmp]
+///
+/// # Safety
+/// `table` should be not null and point to valid data
+/// `bgp_msg` should be not null and point to valid data
+///
+/// This function does not consume the `table` and `bgp_msg` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_rib_lookup_table_update(
+    table: *mut Opaque<RibLookupTable>,
+    bgp_msg: *const Opaque<BgpMessage>,
+) -> RibLookupTableUpdateResult {
+    let table = unsafe { table.as_mut().unwrap().as_mut() };
+    let bgp_msg = unsafe { bgp_msg.as_ref().unwrap().as_ref() };
+
+    let update = match bgp_msg {
+        BgpMessage::Update(update) => update,
+        _ => return WrongBgpMessageTypeError(bgp_msg.get_type().into()).into(),
+    };
+
+    CResult::Ok(table.apply_update(None, RdOriginType::BGP, update))
+}
+
+pub type RibLookupTableBmpUpdateResult = CResult<RibUpdateSummary, WrongBmpMessageTypeError>;
+
+/// Apply the BGP UPDATE of a BMP Route Monitoring Message to the [RibLookupTable] of its peer,
+/// in the RIB of its per-peer header
+///
+/// # Safety
+/// `table` should be not null and point to valid data
+/// `bmp_message_value_opaque` should be not null and point to valid data
+///
+/// This function does not consume the `table` and `bmp_message_value_opaque` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_rib_lookup_table_update_bmp(
+    table: *mut Opaque<RibLookupTable>,
+    bmp_message_value_opaque: *const Opaque<BmpMessageValue>,
+) -> RibLookupTableBmpUpdateResult {
+    let table = unsafe { table.as_mut().unwrap().as_mut() };
+    let bmp_value = unsafe { bmp_message_value_opaque.as_ref().unwrap().as_ref() };
+
+    let bmp_rm = match bmp_value {
+        BmpMessageValue::RouteMonitoring(rm) => rm,
+        _ => return WrongBmpMessageTypeError(bmp_value.get_type().into()).into(),
+    };
+
+    let update = match bmp_rm.update_message() {
+        BgpMessage::Update(update) => update,
+        // NetGauze only accepts Route Monitoring Messages with a BGP UPDATE
+        _ => return CResult::Ok(RibUpdateSummary::default()),
+    };
+
+    CResult::Ok(table.apply_update(bmp_rm.peer_header().rib_type(), RdOriginType::BMP, update))
+}
+
+/// Find the longest prefix containing `addr` with a route of `safi` in the VRF `rd`
+/// of the [RibLookupTable]. `rd` may be null to look up routes without a Route Distinguisher.
+///
+/// With multiple paths to the prefix, the one of the most preferred RIB is returned:
+/// Loc-RIB, then post-policy and pre-policy Adj-RIB-In, then post-policy and pre-policy
+/// Adj-RIB-Out. Among the paths of a RIB, the one with the lowest path id is returned.
+/// The attributes of the returned [RibEntry] are borrowed from the [RibLookupTable]
+/// and are only valid until the [RibLookupTable] is modified.
+///
+/// # Safety
+/// `table` should be not null and point to valid data
+/// `addr` should be not null and point to valid data
+/// `rd` should be null or point to valid data
+///
+/// This function does not consume the `table`, `addr` and `rd` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_rib_lookup(
+    table: *const Opaque<RibLookupTable>,
+    addr: *const host_addr,
+    safi: safi_t,
+    rd: *const rd_t,
+) -> COption<RibEntry> {
+    let table = unsafe { table.as_ref().unwrap().as_ref() };
+    let addr = unsafe { addr.as_ref().unwrap() };
+
+    let rd = match unsafe { rd.as_ref() }.map(|rd| RouteDistinguisher::try_from(*rd)) {
+        None => None,
+        Some(Ok(rd)) => Some(rd),
+        Some(Err(_)) => return COption::None,
+    };
+
+    let address = match IpAddr::try_from(addr) {
+        Ok(address) => address,
+        Err(_) => return COption::None,
+    };
+
+    let (prefix, path) = match table.lookup(&address, safi, rd) {
+        Some(result) => result,
+        None => return COption::None,
+    };
+
+    let afi = match prefix {
+        IpNet::V4(_) => AFI_IP,
+        IpNet::V6(_) => AFI_IP6,
+    };
+    let prefix_key = RibPrefixKey {
+        afi: afi as afi_t,
+        safi,
+        rd,
+        prefix,
+    };
+    let path_key = RibPathKey {
+        rib_type: path.rib_type,
+        path_id: path.path_id,
+    };
+
+    COption::Some(RibEntry::new(
+        &prefix_key,
+        &path_key,
+        &path.route,
+        path.rd_origin,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+    use netgauze_iana::address_family::AddressType;
+
+    use pmacct_gauze_bindings::{safi_t, SAFI_UNICAST};
+
+    use crate::capi::rib::lookup::{mask, PrefixTrie, RibLookupTable};
+    use crate::capi::rib::test::{parse_update, update, ATTRIBUTES};
+    use crate::extensions::bmp_message::BmpRibType;
+    use crate::extensions::rd::RdOriginType;
+
+    fn v4(address: [u8; 4]) -> u128 {
+        (u32::from_be_bytes(address) as u128) << 96
+    }
+
+    fn used_nodes<V>(trie: &PrefixTrie<V>) -> usize {
+        trie.nodes.len() - trie.free.len()
+    }
+
+    #[test]
+    fn test_longest_match() {
+        let mut trie = PrefixTrie::default();
+        *trie.get_or_insert_with(v4([10, 0, 0, 0]), 8, || 0) = 8;
+        *trie.get_or_insert_with(v4([10, 1, 0, 0]), 16, || 0) = 16;
+        *trie.get_or_insert_with(v4([10, 1, 2, 0]), 24, || 0) = 24;
+
+        assert_eq!(
+            trie.longest_match(v4([10, 1, 2, 3]), 32, |_| true),
+            Some((24, &24))
+        );
+        assert_eq!(
+            trie.longest_match(v4([10, 1, 3, 3]), 32, |_| true),
+            Some((16, &16))
+        );
+        assert_eq!(
+            trie.longest_match(v4([10, 2, 2, 3]), 32, |_| true),
+            Some((8, &8))
+        );
+        assert_eq!(trie.longest_match(v4([11, 1, 2, 3]), 32, |_| true), None);
+
+        // Prefixes whose value is filtered out are skipped
+        assert_eq!(
+            trie.longest_match(v4([10, 1, 2, 3]), 32, |value| *value != 24),
+            Some((16, &16))
+        );
+    }
+
+    #[test]
+    fn test_path_compression() {
+        let mut trie = PrefixTrie::default();
+        *trie.get_or_insert_with(v4([10, 1, 0, 0]), 16, || 0) = 16;
+        // Root and 10.1.0.0/16 only
+        assert_eq!(used_nodes(&trie), 2);
+
+        // 10.0.0.0/8 is inserted between the root and 10.1.0.0/16
+        *trie.get_or_insert_with(v4([10, 0, 0, 0]), 8, || 0) = 8;
+        assert_eq!(used_nodes(&trie), 3);
+
+        // 10.1.0.0/16 and 10.2.0.0/16 split after 10.0.0.0/14
+        *trie.get_or_insert_with(v4([10, 2, 0, 0]), 16, || 0) = 2;
+        assert_eq!(used_nodes(&trie), 5);
+        assert_eq!(trie.get_mut(v4([10, 0, 0, 0]), 14), None);
+        assert_eq!(
+            trie.longest_match(v4([10, 3, 0, 1]), 32, |_| true),
+            Some((8, &8))
+        );
+
+        // The split node is removed with 10.1.0.0/16 and reused
+        assert_eq!(trie.remove(v4([10, 1, 0, 0]), 16), Some(16));
+        assert_eq!(used_nodes(&trie), 3);
+        assert_eq!(
+            trie.longest_match(v4([10, 1, 2, 3]), 32, |_| true),
+            Some((8, &8))
+        );
+        assert_eq!(
+            trie.longest_match(v4([10, 2, 2, 3]), 32, |_| true),
+            Some((16, &2))
+        );
+
+        let allocated = trie.nodes.len();
+        *trie.get_or_insert_with(v4([10, 1, 0, 0]), 16, || 0) = 16;
+        assert_eq!(trie.nodes.len(), allocated);
+
+        assert_eq!(trie.remove(v4([10, 0, 0, 0]), 8), Some(8));
+        assert_eq!(trie.remove(v4([192, 168, 0, 0]), 16), None);
+        assert_eq!(trie.remove(v4([10, 0, 0, 0]), 8), None);
+        assert_eq!(
+            trie.longest_match(v4([10, 1, 2, 3]), 32, |_| true),
+            Some((16, &16))
+        );
+    }
+
+    #[test]
+    fn test_trie_matches_linear_search() {
+        // Deterministic pseudo-random prefixes
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut trie = PrefixTrie::default();
+        let mut prefixes = Vec::new();
+        for _ in 0..2000 {
+            // Few distinct high bits so that prefixes overlap
+            let address = mask(((next() & 0xff00_0fff) as u128) << 96, 32);
+            let len = (next() % 33) as u8;
+            let prefix = (mask(address, len), len);
+            if next() % 4 == 0 {
+                let expected = prefixes.iter().position(|known| *known == prefix);
+                let removed = trie.remove(prefix.0, prefix.1);
+                assert_eq!(removed, expected.map(|index| prefixes.swap_remove(index)));
+            } else if !prefixes.contains(&prefix) {
+                trie.get_or_insert_with(prefix.0, prefix.1, || prefix);
+                prefixes.push(prefix);
+            }
+        }
+
+        for _ in 0..2000 {
+            let address = ((next() & 0xff00_0fff) as u128) << 96;
+            let expected = prefixes
+                .iter()
+                .filter(|(prefix, len)| mask(address, *len) == *prefix)
+                .max_by_key(|(_, len)| *len);
+            let found = trie.longest_match(address, 32, |_| true);
+            assert_eq!(found.map(|(_, value)| value), expected);
+        }
+        assert!(used_nodes(&trie) < 2 * prefixes.len() + 1);
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let mut table = RibLookupTable::default();
+        let mut context = BgpParsingContext::default();
+        let unicast = SAFI_UNICAST as safi_t;
+        let labeled_unicast: safi_t = 4;
+
+        // 10.0.0.0/8 and 10.1.0.0/16 in Loc-RIB
+        let message = update(&[], &ATTRIBUTES, &[8, 10, 16, 10, 1]);
+        let update_message = parse_update(&message, &mut context);
+        let rib_type = Some(BmpRibType::LocalRib);
+        table.apply_update(rib_type, RdOriginType::BMP, &update_message);
+
+        // 10.1.2.0/24 with a label in Loc-RIB
+        let mut attributes = ATTRIBUTES[..7].to_vec();
+        attributes.extend_from_slice(&[0x80, 14, 16, 0, 1, 4, 4, 192, 0, 2, 1, 0]);
+        attributes.extend_from_slice(&[48, 0, 1, 1, 10, 1, 2]);
+        let message = update(&[], &attributes, &[]);
+        let update_message = parse_update(&message, &mut context);
+        table.apply_update(rib_type, RdOriginType::BMP, &update_message);
+
+        // 10.1.0.0/16 in the pre-policy Adj-RIB-In, with path ids 2 and 1
+        let mut add_path_context = BgpParsingContext::default();
+        add_path_context
+            .add_path_mut()
+            .insert(AddressType::Ipv4Unicast, true);
+        let message = update(
+            &[],
+            &ATTRIBUTES,
+            &[0, 0, 0, 2, 16, 10, 1, 0, 0, 0, 1, 16, 10, 1],
+        );
+        let update_message = parse_update(&message, &mut add_path_context);
+        let summary = table.apply_update(
+            Some(BmpRibType::AdjRibInPrePolicy),
+            RdOriginType::BMP,
+            &update_message,
+        );
+        assert_eq!((summary.announced, summary.implicitly_withdrawn), (2, 0));
+
+        let lookup = |table: &RibLookupTable, address: [u8; 4], safi| {
+            table
+                .lookup(&IpAddr::from(address), safi, None)
+                .map(|(prefix, path)| (prefix.to_string(), path.rib_type, path.path_id))
+        };
+
+        // The labeled route is only found for its SAFI
+        assert_eq!(
+            lookup(&table, [10, 1, 2, 3], unicast),
+            Some(("10.1.0.0/16".to_string(), rib_type, None))
+        );
+        assert_eq!(
+            lookup(&table, [10, 1, 2, 3], labeled_unicast),
+            Some(("10.1.2.0/24".to_string(), rib_type, None))
+        );
+        assert_eq!(lookup(&table, [10, 2, 2, 3], labeled_unicast), None);
+        assert_eq!(
+            lookup(&table, [10, 2, 2, 3], unicast),
+            Some(("10.0.0.0/8".to_string(), rib_type, None))
+        );
+        assert_eq!(lookup(&table, [11, 1, 2, 3], unicast), None);
+
+        // Loc-RIB is preferred, then the lowest path id
+        let message = update(&[16, 10, 1], &[], &[]);
+        let update_message = parse_update(&message, &mut context);
+        let summary = table.apply_update(rib_type, RdOriginType::BMP, &update_message);
+        assert_eq!(summary.withdrawn, 1);
+        assert_eq!(
+            lookup(&table, [10, 1, 3, 3], unicast),
+            Some((
+                "10.1.0.0/16".to_string(),
+                Some(BmpRibType::AdjRibInPrePolicy),
+                Some(1)
+            ))
+        );
+    }
+}
@@ -0,0 +1,726 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use ipnet::IpNet;
+use netgauze_bgp_pkt::nlri::RouteDistinguisher;
+use netgauze_bgp_pkt::path_attribute::{PathAttribute, PathAttributeValue};
+use netgauze_bgp_pkt::update::BgpUpdateMessage;
+use pmacct_gauze_bindings::convert::{TryConvertFrom, TryConvertInto};
+use pmacct_gauze_bindings::{
+    afi_t, bgp_attr, bgp_attr_extra, bgp_peer, path_id_t, prefix, rd_t, safi_t, DefaultZeroed,
+    AFI_IP, SAFI_UNICAST,
+};
+
+use crate::capi::bgp::update::{
+    fill_attr_mp_next_hop, make_prefix, mp_reach_nlri, mp_unreach_nlri, process_attributes, MpNlri,
+    ProcessPacket,
+};
+use crate::coption::COption;
+use crate::cslice::{OwnedSlice, RustFree};
+use crate::extensions::bmp_message::BmpRibType;
+use crate::extensions::rd::{ExtendRdT, RdOriginType};
+use crate::free_cslice_t;
+use crate::log::{pmacct_log, LogPriority};
+use crate::opaque::Opaque;
+
+pub mod lookup;
+
+/// Path attributes shared by the routes of a BGP UPDATE message
+#[derive(Debug, Clone)]
+pub struct RibAttributes {
+    /// Next hop of the MP_REACH_NLRI attribute, the NEXT_HOP attribute is in `path_attributes`
+    mp_next_hop: Option<IpAddr>,
+    /// Path attributes without MP_REACH_NLRI and MP_UNREACH_NLRI
+    path_attributes: Vec<PathAttribute>,
+}
+
+/// Destination of a route in a [Rib]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RibPrefixKey {
+    pub afi: afi_t,
+    pub safi: safi_t,
+    pub rd: Option<RouteDistinguisher>,
+    pub prefix: IpNet,
+}
+
+/// Path to a [RibPrefixKey] in a [Rib]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct RibPathKey {
+    /// RIB of the monitored router, None for routes received on a BGP session
+    pub rib_type: Option<BmpRibType>,
+    pub path_id: Option<path_id_t>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RibRoute {
+    pub attributes: Arc<RibAttributes>,
+    /// Bottom of stack MPLS label, zeroes if the route has none
+    pub label: [u8; 3],
+}
+
+/// A route announced or withdrawn by a BGP UPDATE message
+#[derive(Debug, Clone)]
+pub(crate) struct RibNlri {
+    pub(crate) prefix_key: RibPrefixKey,
+    pub(crate) path_id: Option<path_id_t>,
+    pub(crate) label: [u8; 3],
+}
+
+impl RibNlri {
+    fn new(afi: afi_t, safi: safi_t, nlri: MpNlri) -> Self {
+        Self {
+            prefix_key: RibPrefixKey {
+                afi,
+                safi,
+                rd: nlri.rd,
+                prefix: nlri.prefix,
+            },
+            path_id: nlri.path_id,
+            label: nlri.label,
+        }
+    }
+}
+
+/// Routes announced and withdrawn by a BGP UPDATE message
+pub(crate) fn update_nlri(
+    update: &BgpUpdateMessage,
+) -> (Vec<(RibNlri, Arc<RibAttributes>)>, Vec<RibNlri>) {
+    let path_attributes: Vec<PathAttribute> = update
+        .path_attributes()
+        .iter()
+        .filter(|attribute| {
+            !matches!(
+                attribute.value(),
+                PathAttributeValue::MpReach(_) | PathAttributeValue::MpUnreach(_)
+            )
+        })
+        .cloned()
+        .collect();
+
+    let (afi, safi) = (AFI_IP as afi_t, SAFI_UNICAST as safi_t);
+
+    let mut announced = Vec::with_capacity(update.nlri().len());
+    if !update.nlri().is_empty() {
+        let attributes = Arc::new(RibAttributes {
+            mp_next_hop: None,
+            path_attributes: path_attributes.clone(),
+        });
+        announced.extend(update.nlri().iter().map(|nlri| {
+            let nlri = MpNlri::new(IpNet::from(nlri.network().address()), nlri.path_id());
+            (RibNlri::new(afi, safi, nlri), attributes.clone())
+        }));
+    }
+
+    let mut withdrawn: Vec<RibNlri> = update
+        .withdraw_routes()
+        .iter()
+        .map(|withdraw| {
+            let nlri = MpNlri::new(
+                IpNet::from(withdraw.network().address()),
+                withdraw.path_id(),
+            );
+            RibNlri::new(afi, safi, nlri)
+        })
+        .collect();
+
+    // Only the address types pmacct supports, like [crate::capi::bgp::update::process_mp_reach]
+    for attribute in update.path_attributes() {
+        match attribute.value() {
+            PathAttributeValue::MpReach(mp_reach) => {
+                let (afi, safi) = match (
+                    mp_reach.afi().try_convert_to(),
+                    mp_reach.safi().try_convert_to(),
+                ) {
+                    (Ok(afi), Ok(safi)) => (afi, safi),
+                    _ => continue,
+                };
+
+                if let Some((next_hop, nlris)) = mp_reach_nlri(mp_reach) {
+                    let attributes = Arc::new(RibAttributes {
+                        mp_next_hop: Some(next_hop),
+                        path_attributes: path_attributes.clone(),
+                    });
+                    announced.extend(
+                        nlris
+                            .into_iter()
+                            .map(|nlri| (RibNlri::new(afi, safi, nlri), attributes.clone())),
+                    );
+                }
+            }
+            PathAttributeValue::MpUnreach(mp_unreach) => {
+                let (afi, safi) = match (
+                    mp_unreach.afi().try_convert_to(),
+                    mp_unreach.safi().try_convert_to(),
+                ) {
+                    (Ok(afi), Ok(safi)) => (afi, safi),
+                    _ => continue,
+                };
+
+                if let Some(nlris) = mp_unreach_nlri(mp_unreach) {
+                    withdrawn.extend(nlris.into_iter().map(|nlri| RibNlri::new(afi, safi, nlri)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (announced, withdrawn)
+}
+
+/// Table of a [RibPeer]: RIB of the monitored router and AFI/SAFI
+type RibTableKey = (Option<BmpRibType>, afi_t, safi_t);
+
+/// A path to a prefix of a [RibPeer]
+#[derive(Debug, Clone)]
+struct RibPath {
+    key: RibPathKey,
+    route: RibRoute,
+    /// Number of End-of-RIB received for the table of the route when it was last announced
+    epoch: u64,
+}
+
+/// Routes of a peer in a [Rib]
+#[derive(Debug, Clone, Default)]
+pub struct RibPeer {
+    /// Paths to each prefix, usually only one or a few
+    routes: HashMap<RibPrefixKey, Vec<RibPath>>,
+    /// Number of End-of-RIB received for each table
+    end_of_rib: HashMap<RibTableKey, u64>,
+}
+
+impl RibPeer {
+    pub fn route_count(&self) -> usize {
+        self.routes.values().map(Vec::len).sum()
+    }
+
+    pub fn end_of_rib_count(&self) -> usize {
+        self.end_of_rib.len()
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = (&RibPrefixKey, &RibPathKey, &RibRoute)> {
+        self.routes.iter().flat_map(|(prefix_key, paths)| {
+            paths
+                .iter()
+                .map(move |path| (prefix_key, &path.key, &path.route))
+        })
+    }
+
+    pub fn paths(
+        &self,
+        prefix_key: &RibPrefixKey,
+    ) -> impl Iterator<Item = (&RibPathKey, &RibRoute)> {
+        self.routes
+            .get(prefix_key)
+            .into_iter()
+            .flatten()
+            .map(|path| (&path.key, &path.route))
+    }
+
+    fn epoch(&self, table: &RibTableKey) -> u64 {
+        self.end_of_rib.get(table).copied().unwrap_or(0)
+    }
+
+    fn remove_path(
+        &mut self,
+        prefix_key: &RibPrefixKey,
+        path_key: &RibPathKey,
+    ) -> Option<RibRoute> {
+        let paths = self.routes.get_mut(prefix_key)?;
+        let index = paths.iter().position(|path| path.key == *path_key)?;
+        let removed = paths.swap_remove(index);
+        if paths.is_empty() {
+            self.routes.remove(prefix_key);
+        }
+
+        Some(removed.route)
+    }
+
+    /// Remove the paths of `table` that were not announced since its previous End-of-RIB
+    fn remove_stale_paths(&mut self, table: &RibTableKey) -> usize {
+        let epoch = self.epoch(table);
+        let (rib_type, afi, safi) = *table;
+
+        let mut removed = 0;
+        self.routes.retain(|prefix_key, paths| {
+            if prefix_key.afi != afi || prefix_key.safi != safi {
+                return true;
+            }
+
+            let len = paths.len();
+            paths.retain(|path| path.key.rib_type != rib_type || path.epoch == epoch);
+            removed += len - paths.len();
+            !paths.is_empty()
+        });
+
+        removed
+    }
+}
+
+/// Changes made to a [Rib] by a BGP UPDATE message
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RibUpdateSummary {
+    /// New routes
+    pub announced: usize,
+    /// Routes replaced by a new announcement of the same path
+    pub implicitly_withdrawn: usize,
+    pub withdrawn: usize,
+    /// Withdrawn routes that were not in the RIB
+    pub withdrawn_unknown: usize,
+    /// The UPDATE message is an End-of-RIB marker
+    pub end_of_rib: bool,
+    /// Routes removed by the End-of-RIB because they were not announced again
+    /// since the previous End-of-RIB of the same table
+    pub stale_withdrawn: usize,
+}
+
+/// Routes received from peers, identified by `P`
+#[derive(Debug, Clone)]
+pub struct Rib<P> {
+    peers: HashMap<P, RibPeer>,
+}
+
+impl<P> Default for Rib<P> {
+    fn default() -> Self {
+        Self {
+            peers: HashMap::default(),
+        }
+    }
+}
+
+impl<P: Hash + Eq> Rib<P> {
+    /// Apply the announcements, withdrawals and End-of-RIB of a BGP UPDATE message
+    /// received from `peer` in its `rib_type` RIB.
+    ///
+    /// End-of-RIB ends a full announcement of a table, after a Peer Up or a new session:
+    /// the routes of the table that were not announced again since the previous End-of-RIB
+    /// of the table are stale and removed.
+    pub fn apply_update(
+        &mut self,
+        peer: P,
+        rib_type: Option<BmpRibType>,
+        update: &BgpUpdateMessage,
+    ) -> RibUpdateSummary {
+        let mut summary = RibUpdateSummary::default();
+        let rib_peer = self.peers.entry(peer).or_default();
+
+        let (announced, withdrawn) = update_nlri(update);
+
+        for nlri in withdrawn {
+            let path_key = RibPathKey {
+                rib_type,
+                path_id: nlri.path_id,
+            };
+
+            if rib_peer.remove_path(&nlri.prefix_key, &path_key).is_some() {
+                summary.withdrawn += 1;
+            } else {
+                summary.withdrawn_unknown += 1;
+            }
+        }
+
+        for (nlri, attributes) in announced {
+            let path = RibPath {
+                key: RibPathKey {
+                    rib_type,
+                    path_id: nlri.path_id,
+                },
+                route: RibRoute {
+                    attributes,
+                    label: nlri.label,
+                },
+                epoch: rib_peer.epoch(&(rib_type, nlri.prefix_key.afi, nlri.prefix_key.safi)),
+            };
+
+            let paths = rib_peer.routes.entry(nlri.prefix_key).or_default();
+            match paths.iter_mut().find(|previous| previous.key == path.key) {
+                Some(previous) => {
+                    *previous = path;
+                    summary.implicitly_withdrawn += 1;
+                }
+                None => {
+                    paths.push(path);
+                    summary.announced += 1;
+                }
+            }
+        }
+
+        if let Some(address_type) = update.end_of_rib() {
+            match (
+                afi_t::try_convert_from(address_type.address_family()),
+                safi_t::try_convert_from(address_type.subsequent_address_family()),
+            ) {
+                (Ok(afi), Ok(safi)) => {
+                    let table = (rib_type, afi, safi);
+                    summary.stale_withdrawn = rib_peer.remove_stale_paths(&table);
+                    *rib_peer.end_of_rib.entry(table).or_default() += 1;
+                    summary.end_of_rib = true;
+                }
+                _ => pmacct_log(
+                    LogPriority::Warning,
+                    &format!(
+                        "[pmacct-gauze] warn! could not convert EoR afi/safi {}/{} to pmacct\n",
+                        address_type.address_family(),
+                        address_type.subsequent_address_family()
+                    ),
+                ),
+            }
+        }
+
+        summary
+    }
+
+    pub fn get_peer(&self, peer: &P) -> Option<&RibPeer> {
+        self.peers.get(peer)
+    }
+
+    pub fn remove_peer(&mut self, peer: &P) -> Option<RibPeer> {
+        self.peers.remove(peer)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = (&P, &RibPeer)> {
+        self.peers.iter()
+    }
+}
+
+/// A route of a [Rib] in pmacct types
+///
+/// `attributes` is borrowed from the [Rib] and is only valid until the [Rib] is modified
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct RibEntry {
+    /// BMP RIB type (BMP_RIB_*) of the route, None for routes received on a BGP session
+    pub rib_type: COption<u8>,
+    pub afi: afi_t,
+    pub safi: safi_t,
+    pub prefix: prefix,
+    pub rd: COption<rd_t>,
+    /// 0 if the route has no path id
+    pub path_id: path_id_t,
+    pub label: [u8; 3],
+    pub attributes: *const Opaque<RibAttributes>,
+}
+
+impl RibEntry {
+    pub fn new(
+        prefix_key: &RibPrefixKey,
+        path_key: &RibPathKey,
+        route: &RibRoute,
+        rd_origin: RdOriginType,
+    ) -> Self {
+        Self {
+            rib_type: path_key.rib_type.map(u8::from).into(),
+            afi: prefix_key.afi,
+            safi: prefix_key.safi,
+            prefix: make_prefix(&prefix_key.prefix),
+            rd: prefix_key.rd.map(|rd| make_rd(rd, rd_origin)).into(),
+            path_id: path_key.path_id.unwrap_or(0),
+            label: route.label,
+            attributes: Opaque::const_from_ref(route.attributes.as_ref()),
+        }
+    }
+}
+
+fn make_rd(rd: RouteDistinguisher, rd_origin: RdOriginType) -> rd_t {
+    let mut rd = rd_t::from(rd);
+    rd.set_pmacct_rd_origin(rd_origin);
+    rd
+}
+
+/// Make the [RibPrefixKey] of a query from pmacct types. `rd` may be null if the route has no RD.
+/// Returns [None] if the prefix or the RD is invalid
+///
+/// # Safety
+/// `prefix` should be not null and point to valid data
+/// `rd` should be null or point to valid data
+pub(crate) unsafe fn make_prefix_key(
+    afi: afi_t,
+    safi: safi_t,
+    prefix: *const prefix,
+    rd: *const rd_t,
+) -> Option<RibPrefixKey> {
+    let prefix = unsafe { prefix.as_ref().unwrap() };
+    let rd = unsafe { rd.as_ref() };
+
+    Some(RibPrefixKey {
+        afi,
+        safi,
+        rd: rd
+            .map(|rd| RouteDistinguisher::try_from(*rd))
+            .transpose()
+            .ok()?,
+        prefix: IpNet::try_from(prefix).ok()?,
+    })
+}
+
+/// Path attributes of a [RibEntry] converted to pmacct
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct RibBgpAttributes {
+    pub attr: bgp_attr,
+    pub attr_extra: bgp_attr_extra,
+}
+
+/// Convert the [RibAttributes] of a [RibEntry] to pmacct attributes, interned for `peer`
+///
+/// The `path_id`, `label` and `rd` of `attr_extra` are not set, they are in the [RibEntry]
+///
+/// # Safety
+/// `peer` should be not null and point to valid data
+/// `attributes` should be not null and point to valid data
+///
+/// This function does not consume the `peer` and `attributes` pointers
+#[no_mangle]
+pub unsafe extern "C" fn netgauze_rib_attributes_get_bgp_attr(
+    peer: *mut bgp_peer,
+    attributes: *const Opaque<RibAttributes>,
+) -> RibBgpAttributes {
+    let attributes = unsafe { attributes.as_ref().unwrap().as_ref() };
+
+    let (_, _, mut attr, attr_extra) = process_attributes(peer, &attributes.path_attributes);
+    if let Some(next_hop) = &attributes.mp_next_hop {
+        fill_attr_mp_next_hop(&mut attr, next_hop);
+    }
+
+    RibBgpAttributes { attr, attr_extra }
+}
+
+free_cslice_t!(RibEntry);
+
+/// Routes of a [RibPeer] as [RibEntry]
+pub(crate) fn make_rib_entries(rib_peer: &RibPeer, rd_origin: RdOriginType) -> Vec<RibEntry> {
+    rib_peer
+        .routes()
+        .map(|(prefix_key, path_key, route)| RibEntry::new(prefix_key, path_key, route, rd_origin))
+        .collect()
+}
+
+/// Synthetic withdrawals of all the routes of a [RibPeer], with the RIB they were in
+pub(crate) fn make_withdraw_packets(
+    rib_peer: &RibPeer,
+    rd_origin: RdOriginType,
+) -> Vec<(Option<BmpRibType>, ProcessPacket)> {
+    rib_peer
+        .routes()
+        .map(|(prefix_key, path_key, route)| {
+            let mut attr_extra = bgp_attr_extra::default_zeroed();
+            attr_extra.path_id = path_key.path_id.unwrap_or(0);
+            attr_extra.label = route.label;
+            if let Some(rd) = prefix_key.rd {
+                attr_extra.rd = make_rd(rd, rd_origin);
+            }
+
+            let packet = ProcessPacket::withdraw(
+                prefix_key.afi,
+                prefix_key.safi,
+                make_prefix(&prefix_key.prefix),
+                attr_extra,
+            );
+
+            (path_key.rib_type, packet)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use ipnet::IpNet;
+    use netgauze_bgp_pkt::update::BgpUpdateMessage;
+    use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+    use netgauze_bgp_pkt::BgpMessage;
+    use netgauze_iana::address_family::AddressType;
+    use netgauze_parse_utils::{ReadablePduWithOneInput, Span};
+
+    use crate::capi::rib::{Rib, RibUpdateSummary};
+    use crate::extensions::bmp_message::BmpRibType;
+
+    /// ORIGIN IGP, empty AS_PATH and NEXT_HOP 192.0.2.1
+    pub(crate) const ATTRIBUTES: [u8; 14] = [0x40, 1, 1, 0, 0x40, 2, 0, 0x40, 3, 4, 192, 0, 2, 1];
+
+    /// BGP UPDATE message with the given withdrawn routes, path attributes and NLRI
+    pub(crate) fn update(withdrawn: &[u8], attributes: &[u8], nlri: &[u8]) -> Vec<u8> {
+        let mut message = vec![0xff; 16];
+        message.extend_from_slice(&[0, 0, 2]);
+        message.extend_from_slice(&(withdrawn.len() as u16).to_be_bytes());
+        message.extend_from_slice(withdrawn);
+        message.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        message.extend_from_slice(attributes);
+        message.extend_from_slice(nlri);
+
+        let len = message.len() as u16;
+        message[16..18].copy_from_slice(&len.to_be_bytes());
+        message
+    }
+
+    pub(crate) fn parse_update(
+        message: &[u8],
+        context: &mut BgpParsingContext,
+    ) -> BgpUpdateMessage {
+        match BgpMessage::from_wire(Span::new(message), context) {
+            Ok((_, BgpMessage::Update(update))) => update,
+            result => panic!("unexpected parsing result {result:?}"),
+        }
+    }
+
+    /// Apply a BGP UPDATE message from peer 1 to `rib`
+    fn apply(
+        rib: &mut Rib<u32>,
+        rib_type: Option<BmpRibType>,
+        context: &mut BgpParsingContext,
+        message: &[u8],
+    ) -> RibUpdateSummary {
+        rib.apply_update(1, rib_type, &parse_update(message, context))
+    }
+
+    /// Prefixes of the routes of peer 1 in `rib`
+    fn prefixes(rib: &Rib<u32>) -> Vec<String> {
+        let mut prefixes: Vec<String> = rib
+            .get_peer(&1)
+            .map(|rib_peer| {
+                rib_peer
+                    .routes()
+                    .map(|(prefix_key, _, _)| prefix_key.prefix.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        prefixes.sort();
+        prefixes
+    }
+
+    fn summary(summary: RibUpdateSummary) -> (usize, usize, usize, usize, bool, usize) {
+        (
+            summary.announced,
+            summary.implicitly_withdrawn,
+            summary.withdrawn,
+            summary.withdrawn_unknown,
+            summary.end_of_rib,
+            summary.stale_withdrawn,
+        )
+    }
+
+    #[test]
+    fn test_rib_announce_withdraw() {
+        let mut rib = Rib::default();
+        let mut context = BgpParsingContext::default();
+
+        // 10.0.0.0/8 and 192.168.0.0/16
+        let message = update(&[], &ATTRIBUTES, &[8, 10, 16, 192, 168]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (2, 0, 0, 0, false, 0));
+        assert_eq!(prefixes(&rib), vec!["10.0.0.0/8", "192.168.0.0/16"]);
+
+        // Implicit withdraw of 192.168.0.0/16 with a MED
+        let mut attributes = ATTRIBUTES.to_vec();
+        attributes.extend_from_slice(&[0x80, 4, 4, 0, 0, 0, 10]);
+        let message = update(&[], &attributes, &[16, 192, 168]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (0, 1, 0, 0, false, 0));
+        let rib_peer = rib.get_peer(&1).unwrap();
+        assert_eq!(rib_peer.route_count(), 2);
+        let (_, _, route) = rib_peer
+            .routes()
+            .find(|(prefix_key, _, _)| prefix_key.prefix.prefix_len() == 16)
+            .unwrap();
+        assert_eq!(route.attributes.path_attributes.len(), 4);
+
+        // Withdraw of 10.0.0.0/8 and of 172.16.0.0/12 that is unknown
+        let message = update(&[8, 10, 12, 172, 16], &[], &[]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (0, 0, 1, 1, false, 0));
+        assert_eq!(prefixes(&rib), vec!["192.168.0.0/16"]);
+    }
+
+    #[test]
+    fn test_rib_end_of_rib() {
+        let mut rib = Rib::default();
+        let mut context = BgpParsingContext::default();
+        let rib_type = Some(BmpRibType::AdjRibInPrePolicy);
+
+        let message = update(&[], &ATTRIBUTES, &[8, 10, 16, 192, 168]);
+        apply(&mut rib, rib_type, &mut context, &message);
+
+        // The first End-of-RIB keeps the routes announced before it
+        let end_of_rib = update(&[], &[], &[]);
+        let result = apply(&mut rib, rib_type, &mut context, &end_of_rib);
+        assert_eq!(summary(result), (0, 0, 0, 0, true, 0));
+        assert_eq!(rib.get_peer(&1).unwrap().end_of_rib_count(), 1);
+        assert_eq!(prefixes(&rib), vec!["10.0.0.0/8", "192.168.0.0/16"]);
+
+        // Another RIB of the peer is not affected by the End-of-RIB
+        let message = update(&[], &ATTRIBUTES, &[12, 172, 16]);
+        apply(&mut rib, Some(BmpRibType::LocalRib), &mut context, &message);
+
+        // The table is announced again without 192.168.0.0/16, which is stale
+        let message = update(&[], &ATTRIBUTES, &[8, 10]);
+        let result = apply(&mut rib, rib_type, &mut context, &message);
+        assert_eq!(summary(result), (0, 1, 0, 0, false, 0));
+        let result = apply(&mut rib, rib_type, &mut context, &end_of_rib);
+        assert_eq!(summary(result), (0, 0, 0, 0, true, 1));
+        assert_eq!(rib.get_peer(&1).unwrap().end_of_rib_count(), 1);
+        assert_eq!(prefixes(&rib), vec!["10.0.0.0/8", "172.16.0.0/12"]);
+    }
+
+    #[test]
+    fn test_rib_path_id() {
+        let mut rib = Rib::default();
+        let mut context = BgpParsingContext::default();
+        context
+            .add_path_mut()
+            .insert(AddressType::Ipv4Unicast, true);
+
+        // 10.0.0.0/8 with path ids 1 and 2
+        let message = update(&[], &ATTRIBUTES, &[0, 0, 0, 1, 8, 10, 0, 0, 0, 2, 8, 10]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (2, 0, 0, 0, false, 0));
+
+        let message = update(&[0, 0, 0, 1, 8, 10], &[], &[]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (0, 0, 1, 0, false, 0));
+
+        let rib_peer = rib.get_peer(&1).unwrap();
+        let (prefix_key, _, _) = rib_peer.routes().next().unwrap();
+        let path_ids: Vec<_> = rib_peer
+            .paths(prefix_key)
+            .map(|(path_key, _)| path_key.path_id)
+            .collect();
+        assert_eq!(path_ids, vec![Some(2)]);
+    }
+
+    #[test]
+    fn test_rib_route_distinguisher() {
+        let mut rib = Rib::default();
+        let mut context = BgpParsingContext::default();
+
+        // MP_REACH_NLRI of IPv4 MPLS VPN with next hop 0:192.0.2.1
+        // and 10.0.0.0/8 with label 16 in RD 65000:1 and 65000:2
+        let mut mp_reach = vec![0x80, 14, 43, 0, 1, 128, 12, 0, 0, 0, 0, 0, 0, 0, 0];
+        mp_reach.extend_from_slice(&[192, 0, 2, 1, 0]);
+        for rd in [1, 2] {
+            mp_reach.extend_from_slice(&[96, 0, 1, 1, 0, 0, 0xfd, 0xe8, 0, 0, 0, rd, 10]);
+        }
+        let mut attributes = ATTRIBUTES[..7].to_vec();
+        attributes.extend_from_slice(&mp_reach);
+
+        let message = update(&[], &attributes, &[]);
+        let result = apply(&mut rib, None, &mut context, &message);
+        assert_eq!(summary(result), (2, 0, 0, 0, false, 0));
+
+        let rib_peer = rib.get_peer(&1).unwrap();
+        let rds: HashSet<_> = rib_peer
+            .routes()
+            .map(|(prefix_key, _, route)| {
+                assert_eq!(prefix_key.prefix, "10.0.0.0/8".parse::<IpNet>().unwrap());
+                assert_eq!(route.label, [0, 1, 1]);
+                prefix_key.rd.map(u64::from)
+            })
+            .collect();
+        assert_eq!(
+            rds,
+            HashSet::from([Some(0xfde8_0000_0001), Some(0xfde8_0000_0002)])
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::extensions::add_path::AddPathCapabilityValue::{Both, ReceiveOnly, SendOnly, Unset};
-use netgauze_bgp_pkt::capabilities::AddPathAddressFamily;
+use netgauze_bgp_pkt::capabilities::{AddPathAddressFamily, BgpCapability};
+use netgauze_bgp_pkt::open::BgpOpenMessage;
 use netgauze_iana::address_family::AddressType;
 use pmacct_gauze_bindings::convert::TryConvertInto;
 use pmacct_gauze_bindings::{afi_t, cap_per_af, safi_t};
@@ -91,3 +92,29 @@ impl AddPathCapability for cap_per_af {
         Ok(result)
     }
 }
+
+impl AddPathCapability for BgpOpenMessage {
+    fn get_receive_map(&self) -> Result<HashMap<AddressType, bool>, (afi_t, safi_t)> {
+        Ok(add_path_address_families(self)
+            .filter(|add_path_address_family| add_path_address_family.receive())
+            .map(|add_path_address_family| (add_path_address_family.address_type(), true))
+            .collect())
+    }
+
+    fn get_send_map(&self) -> Result<HashMap<AddressType, bool>, (afi_t, safi_t)> {
+        Ok(add_path_address_families(self)
+            .filter(|add_path_address_family| add_path_address_family.send())
+            .map(|add_path_address_family| (add_path_address_family.address_type(), true))
+            .collect())
+    }
+}
+
+fn add_path_address_families(open: &BgpOpenMessage) -> impl Iterator<Item = &AddPathAddressFamily> {
+    open.capabilities()
+        .into_iter()
+        .filter_map(|capability| match capability {
+            BgpCapability::AddPath(add_path) => Some(add_path.address_families().iter()),
+            _ => None,
+        })
+        .flatten()
+}
@@ -1,3 +1,6 @@
+use std::ffi::CStr;
+
+use c_str_macro::c_str;
 use netgauze_bgp_pkt::iana::{
     BgpErrorNotificationCode, CeaseErrorSubCode, FiniteStateMachineErrorSubCode,
     MessageHeaderErrorSubCode, OpenMessageErrorSubCode, RouteRefreshMessageErrorSubCode,
@@ -8,10 +11,58 @@ use netgauze_bgp_pkt::notification::{
     MessageHeaderError, OpenMessageError, RouteRefreshError, UpdateMessageError,
 };
 
+/// Maximum length of a Shutdown Communication message (RFC 9003)
+pub const SHUTDOWN_COMMUNICATION_MAX_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BgpNotificationBuildError {
+    UndefinedCode(u8),
+    UndefinedSubCode { code: u8, subcode: u8 },
+}
+
 pub trait ExtendBgpNotificationMessage {
     fn code(&self) -> BgpErrorNotificationCode;
     fn raw_subcode(&self) -> u8;
     fn value_ptr(&self) -> &[u8];
+
+    /// Human-readable name of the error code
+    fn code_name(&self) -> &'static CStr;
+
+    /// Human-readable name of the error subcode
+    fn subcode_name(&self) -> &'static CStr;
+
+    /// Build a notification from its raw code, subcode and data
+    fn from_raw(code: u8, subcode: u8, value: Vec<u8>) -> Result<Self, BgpNotificationBuildError>
+    where
+        Self: Sized;
+}
+
+/// Encode a Shutdown Communication (RFC 8203 / RFC 9003) as the data of a Cease
+/// Administrative Shutdown or Administrative Reset notification.
+///
+/// Returns the length of the message if it is too long to be encoded
+pub fn encode_shutdown_communication(message: &str) -> Result<Vec<u8>, usize> {
+    let len = message.len();
+    if len > SHUTDOWN_COMMUNICATION_MAX_LENGTH {
+        return Err(len);
+    }
+
+    let mut value = Vec::with_capacity(len + 1);
+    value.push(len as u8);
+    value.extend_from_slice(message.as_bytes());
+
+    Ok(value)
+}
+
+/// Decode the Shutdown Communication (RFC 8203 / RFC 9003) carried in the data
+/// of a Cease Administrative Shutdown or Administrative Reset notification.
+///
+/// Returns [None] if the data is not a valid length-prefixed UTF-8 message
+pub fn decode_shutdown_communication(value: &[u8]) -> Option<&str> {
+    let (len, message) = value.split_first()?;
+    let message = message.get(..*len as usize)?;
+
+    std::str::from_utf8(message).ok()
 }
 
 impl ExtendBgpNotificationMessage for BgpNotificationMessage {
@@ -226,4 +277,355 @@ impl ExtendBgpNotificationMessage for BgpNotificationMessage {
             },
         }
     }
+
+    fn code_name(&self) -> &'static CStr {
+        match self {
+            BgpNotificationMessage::MessageHeaderError(_) => c_str!("Message Header Error"),
+            BgpNotificationMessage::OpenMessageError(_) => c_str!("OPEN Message Error"),
+            BgpNotificationMessage::UpdateMessageError(_) => c_str!("UPDATE Message Error"),
+            BgpNotificationMessage::HoldTimerExpiredError(_) => c_str!("Hold Timer Expired"),
+            BgpNotificationMessage::FiniteStateMachineError(_) => {
+                c_str!("Finite State Machine Error")
+            }
+            BgpNotificationMessage::CeaseError(_) => c_str!("Cease"),
+            BgpNotificationMessage::RouteRefreshError(_) => c_str!("ROUTE-REFRESH Message Error"),
+        }
+    }
+
+    fn subcode_name(&self) -> &'static CStr {
+        match self {
+            BgpNotificationMessage::MessageHeaderError(error) => match error {
+                MessageHeaderError::Unspecific { .. } => c_str!("Unspecific"),
+                MessageHeaderError::ConnectionNotSynchronized { .. } => {
+                    c_str!("Connection Not Synchronized")
+                }
+                MessageHeaderError::BadMessageLength { .. } => c_str!("Bad Message Length"),
+                MessageHeaderError::BadMessageType { .. } => c_str!("Bad Message Type"),
+            },
+            BgpNotificationMessage::OpenMessageError(error) => match error {
+                OpenMessageError::Unspecific { .. } => c_str!("Unspecific"),
+                OpenMessageError::UnsupportedVersionNumber { .. } => {
+                    c_str!("Unsupported Version Number")
+                }
+                OpenMessageError::BadPeerAs { .. } => c_str!("Bad Peer AS"),
+                OpenMessageError::BadBgpIdentifier { .. } => c_str!("Bad BGP Identifier"),
+                OpenMessageError::UnsupportedOptionalParameter { .. } => {
+                    c_str!("Unsupported Optional Parameter")
+                }
+                OpenMessageError::UnacceptableHoldTime { .. } => c_str!("Unacceptable Hold Time"),
+                OpenMessageError::UnsupportedCapability { .. } => {
+                    c_str!("Unsupported Capability")
+                }
+                OpenMessageError::RoleMismatch { .. } => c_str!("Role Mismatch"),
+            },
+            BgpNotificationMessage::UpdateMessageError(error) => match error {
+                UpdateMessageError::Unspecific { .. } => c_str!("Unspecific"),
+                UpdateMessageError::MalformedAttributeList { .. } => {
+                    c_str!("Malformed Attribute List")
+                }
+                UpdateMessageError::UnrecognizedWellKnownAttribute { .. } => {
+                    c_str!("Unrecognized Well-known Attribute")
+                }
+                UpdateMessageError::MissingWellKnownAttribute { .. } => {
+                    c_str!("Missing Well-known Attribute")
+                }
+                UpdateMessageError::AttributeFlagsError { .. } => c_str!("Attribute Flags Error"),
+                UpdateMessageError::AttributeLengthError { .. } => {
+                    c_str!("Attribute Length Error")
+                }
+                UpdateMessageError::InvalidOriginAttribute { .. } => {
+                    c_str!("Invalid ORIGIN Attribute")
+                }
+                UpdateMessageError::InvalidNextHopAttribute { .. } => {
+                    c_str!("Invalid NEXT_HOP Attribute")
+                }
+                UpdateMessageError::OptionalAttributeError { .. } => {
+                    c_str!("Optional Attribute Error")
+                }
+                UpdateMessageError::InvalidNetworkField { .. } => c_str!("Invalid Network Field"),
+                UpdateMessageError::MalformedAsPath { .. } => c_str!("Malformed AS_PATH"),
+            },
+            BgpNotificationMessage::HoldTimerExpiredError(error) => match error {
+                HoldTimerExpiredError::Unspecific { .. } => c_str!("Unspecific"),
+            },
+            BgpNotificationMessage::FiniteStateMachineError(error) => match error {
+                FiniteStateMachineError::Unspecific { .. } => c_str!("Unspecified Error"),
+                FiniteStateMachineError::ReceiveUnexpectedMessageInOpenSentState { .. } => {
+                    c_str!("Receive Unexpected Message in OpenSent State")
+                }
+                FiniteStateMachineError::ReceiveUnexpectedMessageInOpenConfirmState { .. } => {
+                    c_str!("Receive Unexpected Message in OpenConfirm State")
+                }
+                FiniteStateMachineError::ReceiveUnexpectedMessageInEstablishedState { .. } => {
+                    c_str!("Receive Unexpected Message in Established State")
+                }
+            },
+            BgpNotificationMessage::CeaseError(error) => match error {
+                CeaseError::MaximumNumberOfPrefixesReached { .. } => {
+                    c_str!("Maximum Number of Prefixes Reached")
+                }
+                CeaseError::AdministrativeShutdown { .. } => c_str!("Administrative Shutdown"),
+                CeaseError::PeerDeConfigured { .. } => c_str!("Peer De-configured"),
+                CeaseError::AdministrativeReset { .. } => c_str!("Administrative Reset"),
+                CeaseError::ConnectionRejected { .. } => c_str!("Connection Rejected"),
+                CeaseError::OtherConfigurationChange { .. } => {
+                    c_str!("Other Configuration Change")
+                }
+                CeaseError::ConnectionCollisionResolution { .. } => {
+                    c_str!("Connection Collision Resolution")
+                }
+                CeaseError::OutOfResources { .. } => c_str!("Out of Resources"),
+                CeaseError::HardReset { .. } => c_str!("Hard Reset"),
+                CeaseError::BfdDown { .. } => c_str!("BFD Down"),
+            },
+            BgpNotificationMessage::RouteRefreshError(error) => match error {
+                RouteRefreshError::InvalidMessageLength { .. } => {
+                    c_str!("Invalid Message Length")
+                }
+            },
+        }
+    }
+
+    fn from_raw(code: u8, subcode: u8, value: Vec<u8>) -> Result<Self, BgpNotificationBuildError> {
+        let undefined_subcode = Err(BgpNotificationBuildError::UndefinedSubCode { code, subcode });
+
+        let notification = match code {
+            x if x == BgpErrorNotificationCode::MessageHeaderError as u8 => {
+                BgpNotificationMessage::MessageHeaderError(match subcode {
+                    x if x == MessageHeaderErrorSubCode::Unspecific as u8 => {
+                        MessageHeaderError::Unspecific { value }
+                    }
+                    x if x == MessageHeaderErrorSubCode::ConnectionNotSynchronized as u8 => {
+                        MessageHeaderError::ConnectionNotSynchronized { value }
+                    }
+                    x if x == MessageHeaderErrorSubCode::BadMessageLength as u8 => {
+                        MessageHeaderError::BadMessageLength { value }
+                    }
+                    x if x == MessageHeaderErrorSubCode::BadMessageType as u8 => {
+                        MessageHeaderError::BadMessageType { value }
+                    }
+                    _ => return undefined_subcode,
+                })
+            }
+            x if x == BgpErrorNotificationCode::OpenMessageError as u8 => {
+                BgpNotificationMessage::OpenMessageError(match subcode {
+                    x if x == OpenMessageErrorSubCode::Unspecific as u8 => {
+                        OpenMessageError::Unspecific { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::UnsupportedVersionNumber as u8 => {
+                        OpenMessageError::UnsupportedVersionNumber { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::BadPeerAs as u8 => {
+                        OpenMessageError::BadPeerAs { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::BadBgpIdentifier as u8 => {
+                        OpenMessageError::BadBgpIdentifier { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::UnsupportedOptionalParameter as u8 => {
+                        OpenMessageError::UnsupportedOptionalParameter { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::UnacceptableHoldTime as u8 => {
+                        OpenMessageError::UnacceptableHoldTime { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::UnsupportedCapability as u8 => {
+                        OpenMessageError::UnsupportedCapability { value }
+                    }
+                    x if x == OpenMessageErrorSubCode::RoleMismatch as u8 => {
+                        OpenMessageError::RoleMismatch { value }
+                    }
+                    _ => return undefined_subcode,
+                })
+            }
+            x if x == BgpErrorNotificationCode::UpdateMessageError as u8 => {
+                BgpNotificationMessage::UpdateMessageError(match subcode {
+                    x if x == UpdateMessageErrorSubCode::Unspecific as u8 => {
+                        UpdateMessageError::Unspecific { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::MalformedAttributeList as u8 => {
+                        UpdateMessageError::MalformedAttributeList { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::UnrecognizedWellKnownAttribute as u8 => {
+                        UpdateMessageError::UnrecognizedWellKnownAttribute { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::MissingWellKnownAttribute as u8 => {
+                        UpdateMessageError::MissingWellKnownAttribute { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::AttributeFlagsError as u8 => {
+                        UpdateMessageError::AttributeFlagsError { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::AttributeLengthError as u8 => {
+                        UpdateMessageError::AttributeLengthError { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::InvalidOriginAttribute as u8 => {
+                        UpdateMessageError::InvalidOriginAttribute { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::InvalidNextHopAttribute as u8 => {
+                        UpdateMessageError::InvalidNextHopAttribute { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::OptionalAttributeError as u8 => {
+                        UpdateMessageError::OptionalAttributeError { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::InvalidNetworkField as u8 => {
+                        UpdateMessageError::InvalidNetworkField { value }
+                    }
+                    x if x == UpdateMessageErrorSubCode::MalformedAsPath as u8 => {
+                        UpdateMessageError::MalformedAsPath { value }
+                    }
+                    _ => return undefined_subcode,
+                })
+            }
+            x if x == BgpErrorNotificationCode::HoldTimerExpired as u8 => {
+                BgpNotificationMessage::HoldTimerExpiredError(HoldTimerExpiredError::Unspecific {
+                    sub_code: subcode,
+                    value,
+                })
+            }
+            x if x == BgpErrorNotificationCode::FiniteStateMachineError as u8 => {
+                BgpNotificationMessage::FiniteStateMachineError(match subcode {
+                    x if x == FiniteStateMachineErrorSubCode::UnspecifiedError as u8 => {
+                        FiniteStateMachineError::Unspecific { value }
+                    }
+                    x if x
+                        == FiniteStateMachineErrorSubCode::ReceiveUnexpectedMessageInOpenSentState
+                            as u8 =>
+                    {
+                        FiniteStateMachineError::ReceiveUnexpectedMessageInOpenSentState { value }
+                    }
+                    x if x
+                        == FiniteStateMachineErrorSubCode::ReceiveUnexpectedMessageInOpenConfirmState
+                            as u8 =>
+                    {
+                        FiniteStateMachineError::ReceiveUnexpectedMessageInOpenConfirmState {
+                            value,
+                        }
+                    }
+                    x if x
+                        == FiniteStateMachineErrorSubCode::ReceiveUnexpectedMessageInEstablishedState
+                            as u8 =>
+                    {
+                        FiniteStateMachineError::ReceiveUnexpectedMessageInEstablishedState {
+                            value,
+                        }
+                    }
+                    _ => return undefined_subcode,
+                })
+            }
+            x if x == BgpErrorNotificationCode::Cease as u8 => {
+                BgpNotificationMessage::CeaseError(match subcode {
+                    x if x == CeaseErrorSubCode::MaximumNumberOfPrefixesReached as u8 => {
+                        CeaseError::MaximumNumberOfPrefixesReached { value }
+                    }
+                    x if x == CeaseErrorSubCode::AdministrativeShutdown as u8 => {
+                        CeaseError::AdministrativeShutdown { value }
+                    }
+                    x if x == CeaseErrorSubCode::PeerDeConfigured as u8 => {
+                        CeaseError::PeerDeConfigured { value }
+                    }
+                    x if x == CeaseErrorSubCode::AdministrativeReset as u8 => {
+                        CeaseError::AdministrativeReset { value }
+                    }
+                    x if x == CeaseErrorSubCode::ConnectionRejected as u8 => {
+                        CeaseError::ConnectionRejected { value }
+                    }
+                    x if x == CeaseErrorSubCode::OtherConfigurationChange as u8 => {
+                        CeaseError::OtherConfigurationChange { value }
+                    }
+                    x if x == CeaseErrorSubCode::ConnectionCollisionResolution as u8 => {
+                        CeaseError::ConnectionCollisionResolution { value }
+                    }
+                    x if x == CeaseErrorSubCode::OutOfResources as u8 => {
+                        CeaseError::OutOfResources { value }
+                    }
+                    x if x == CeaseErrorSubCode::HardReset as u8 => CeaseError::HardReset { value },
+                    x if x == CeaseErrorSubCode::BfdDown as u8 => CeaseError::BfdDown { value },
+                    _ => return undefined_subcode,
+                })
+            }
+            x if x == BgpErrorNotificationCode::RouteRefreshMessageError as u8 => {
+                BgpNotificationMessage::RouteRefreshError(match subcode {
+                    x if x == RouteRefreshMessageErrorSubCode::InvalidMessageLength as u8 => {
+                        RouteRefreshError::InvalidMessageLength { value }
+                    }
+                    _ => return undefined_subcode,
+                })
+            }
+            _ => return Err(BgpNotificationBuildError::UndefinedCode(code)),
+        };
+
+        Ok(notification)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use netgauze_bgp_pkt::notification::{BgpNotificationMessage, CeaseError};
+
+    use crate::extensions::bgp_notification::{
+        decode_shutdown_communication, encode_shutdown_communication, BgpNotificationBuildError,
+        ExtendBgpNotificationMessage, SHUTDOWN_COMMUNICATION_MAX_LENGTH,
+    };
+
+    #[test]
+    fn test_shutdown_communication_round_trip() {
+        let encoded = encode_shutdown_communication("maintenance").unwrap();
+        assert_eq!(encoded[0], 11);
+        assert_eq!(&encoded[1..], b"maintenance");
+        assert_eq!(decode_shutdown_communication(&encoded), Some("maintenance"));
+
+        let encoded = encode_shutdown_communication("").unwrap();
+        assert_eq!(encoded, vec![0]);
+        assert_eq!(decode_shutdown_communication(&encoded), Some(""));
+
+        let longest = "é".repeat(SHUTDOWN_COMMUNICATION_MAX_LENGTH / 2) + "x";
+        let encoded = encode_shutdown_communication(&longest).unwrap();
+        assert_eq!(encoded.len(), SHUTDOWN_COMMUNICATION_MAX_LENGTH + 1);
+        assert_eq!(
+            decode_shutdown_communication(&encoded),
+            Some(longest.as_str())
+        );
+
+        let too_long = "x".repeat(SHUTDOWN_COMMUNICATION_MAX_LENGTH + 1);
+        assert_eq!(
+            encode_shutdown_communication(&too_long),
+            Err(SHUTDOWN_COMMUNICATION_MAX_LENGTH + 1)
+        );
+    }
+
+    #[test]
+    fn test_from_raw_round_trip() {
+        for (code, subcode) in [
+            (1, 2),
+            (2, 7),
+            (3, 11),
+            (4, 0),
+            (5, 3),
+            (6, 2),
+            (6, 4),
+            (7, 1),
+        ] {
+            let notification =
+                BgpNotificationMessage::from_raw(code, subcode, vec![1, 2, 3]).unwrap();
+            assert_eq!(notification.code() as u8, code);
+            assert_eq!(notification.raw_subcode(), subcode);
+            assert_eq!(notification.value_ptr(), &[1, 2, 3]);
+        }
+
+        assert!(matches!(
+            BgpNotificationMessage::from_raw(6, 2, vec![]),
+            Ok(BgpNotificationMessage::CeaseError(
+                CeaseError::AdministrativeShutdown { .. }
+            ))
+        ));
+        assert_eq!(
+            BgpNotificationMessage::from_raw(0, 0, vec![]).err(),
+            Some(BgpNotificationBuildError::UndefinedCode(0))
+        );
+        assert_eq!(
+            BgpNotificationMessage::from_raw(6, 200, vec![]).err(),
+            Some(BgpNotificationBuildError::UndefinedSubCode {
+                code: 6,
+                subcode: 200
+            })
+        );
+    }
 }
@@ -89,6 +89,7 @@ pub trait ExtendBmpPeerHeader {
     fn rib_type(&self) -> Option<BmpRibType>;
 }
 
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub enum BmpRibType {
     AdjRibInPrePolicy,
     AdjRibInPostPolicy,
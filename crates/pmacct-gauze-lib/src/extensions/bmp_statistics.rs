@@ -17,6 +17,12 @@ impl Display for StatHasNoNumericalValue {
 pub trait ExtendBmpStatistics {
     fn get_afi_safi(&self) -> Result<Option<(afi_t, safi_t)>, AddressType>;
     fn get_value_as_u64(&self) -> Result<u64, StatHasNoNumericalValue>;
+    fn get_address_type(&self) -> Option<AddressType>;
+    /// Raw value of experimental and unknown stats, which NetGauze does not decode
+    fn get_raw_value(&self) -> Option<&[u8]>;
+    /// Whether the stat is a gauge (number of routes, types 7-10 and 14-17)
+    /// rather than a monotonically increasing counter
+    fn is_gauge(&self) -> bool;
 }
 
 impl ExtendBmpStatistics for StatisticsCounter {
@@ -117,4 +123,55 @@ impl ExtendBmpStatistics for StatisticsCounter {
             | StatisticsCounter::Unknown(_, _) => Err(StatHasNoNumericalValue),
         }
     }
+
+    fn get_address_type(&self) -> Option<AddressType> {
+        match self {
+            StatisticsCounter::NumberOfRoutesInPerAfiSafiAdjRibIn(address_type, _)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiLocRib(address_type, _)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiPrePolicyAdjRibOut(address_type, _)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiPostPolicyAdjRibOut(address_type, _) => {
+                Some(*address_type)
+            }
+            _ => None,
+        }
+    }
+
+    fn is_gauge(&self) -> bool {
+        match self {
+            StatisticsCounter::NumberOfRoutesInAdjRibIn(_)
+            | StatisticsCounter::NumberOfRoutesInLocRib(_)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiAdjRibIn(_, _)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiLocRib(_, _)
+            | StatisticsCounter::NumberOfRoutesInPrePolicyAdjRibOut(_)
+            | StatisticsCounter::NumberOfRoutesInPostPolicyAdjRibOut(_)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiPrePolicyAdjRibOut(_, _)
+            | StatisticsCounter::NumberOfRoutesInPerAfiSafiPostPolicyAdjRibOut(_, _) => true,
+            StatisticsCounter::NumberOfPrefixesRejectedByInboundPolicy(_)
+            | StatisticsCounter::NumberOfDuplicatePrefixAdvertisements(_)
+            | StatisticsCounter::NumberOfDuplicateWithdraws(_)
+            | StatisticsCounter::NumberOfUpdatesInvalidatedDueToClusterListLoop(_)
+            | StatisticsCounter::NumberOfUpdatesInvalidatedDueToAsPathLoop(_)
+            | StatisticsCounter::NumberOfUpdatesInvalidatedDueToOriginatorId(_)
+            | StatisticsCounter::NumberOfUpdatesInvalidatedDueToAsConfederationLoop(_)
+            | StatisticsCounter::NumberOfUpdatesSubjectedToTreatAsWithdraw(_)
+            | StatisticsCounter::NumberOfPrefixesSubjectedToTreatAsWithdraw(_)
+            | StatisticsCounter::NumberOfDuplicateUpdateMessagesReceived(_)
+            | StatisticsCounter::Experimental65531(_)
+            | StatisticsCounter::Experimental65532(_)
+            | StatisticsCounter::Experimental65533(_)
+            | StatisticsCounter::Experimental65534(_)
+            | StatisticsCounter::Unknown(_, _) => false,
+        }
+    }
+
+    fn get_raw_value(&self) -> Option<&[u8]> {
+        match self {
+            StatisticsCounter::Experimental65531(value)
+            | StatisticsCounter::Experimental65532(value)
+            | StatisticsCounter::Experimental65533(value)
+            | StatisticsCounter::Experimental65534(value)
+            | StatisticsCounter::Unknown(_, value) => Some(value),
+            _ => None,
+        }
+    }
 }
@@ -1,6 +1,91 @@
+use std::collections::HashMap;
+
+use netgauze_bgp_pkt::capabilities::BgpCapability;
+use netgauze_bgp_pkt::open::BgpOpenMessage;
 use netgauze_bgp_pkt::wire::deserializer::BgpParsingContext;
+use netgauze_bgp_pkt::BgpMessage;
 use netgauze_bmp_pkt::wire::deserializer::BmpParsingContext;
-use netgauze_bmp_pkt::PeerKey;
+use netgauze_bmp_pkt::{PeerKey, PeerUpNotificationMessage};
+
+use crate::extensions::add_path::AddPathCapability;
+
+/// Build the [BgpParsingContext] to parse the messages received on a session
+/// where `open_tx` was sent to the peer and `open_rx` was received from the peer.
+///
+/// - ASN4 is used if both sides advertised the Four-Octet AS capability
+/// - Add-Path is used for an address type if we can receive and the peer can send
+/// - Multiple labels use our advertised count if the peer also advertised the capability
+pub fn bgp_parsing_context_from_opens(
+    open_tx: &BgpOpenMessage,
+    open_rx: &BgpOpenMessage,
+) -> BgpParsingContext {
+    let mut parsing_context = BgpParsingContext::default();
+
+    let has_asn4 = |open: &BgpOpenMessage| {
+        open.capabilities()
+            .into_iter()
+            .any(|capability| matches!(capability, BgpCapability::FourOctetAs(_)))
+    };
+    parsing_context.set_asn4(has_asn4(open_tx) && has_asn4(open_rx));
+
+    // Infallible for [BgpOpenMessage]
+    let receive_map = open_tx.get_receive_map().unwrap_or_default();
+    let send_map = open_rx.get_send_map().unwrap_or_default();
+    let add_path = parsing_context.add_path_mut();
+    add_path.clear();
+    for (address_type, receive) in receive_map {
+        if receive && send_map.get(&address_type).copied().unwrap_or(false) {
+            add_path.insert(address_type, true);
+        }
+    }
+
+    let multiple_labels = |open: &BgpOpenMessage| {
+        open.capabilities()
+            .into_iter()
+            .filter_map(|capability| match capability {
+                BgpCapability::MultipleLabels(labels) => Some(labels.iter()),
+                _ => None,
+            })
+            .flatten()
+            .map(|label| (label.address_type(), label.count()))
+            .collect::<HashMap<_, _>>()
+    };
+    let tx_labels = multiple_labels(open_tx);
+    let rx_labels = multiple_labels(open_rx);
+    let negotiated_labels = parsing_context.multiple_labels_mut();
+    negotiated_labels.clear();
+    for (address_type, count) in tx_labels {
+        if rx_labels.contains_key(&address_type) {
+            negotiated_labels.insert(address_type, count);
+        }
+    }
+
+    parsing_context
+}
+
+/// Whether Extended Messages (RFC 8654) are used on a session,
+/// i.e. both sides advertised the Extended Message capability
+pub fn extended_message_from_opens(open_tx: &BgpOpenMessage, open_rx: &BgpOpenMessage) -> bool {
+    let has_extended_message = |open: &BgpOpenMessage| {
+        open.capabilities()
+            .into_iter()
+            .any(|capability| matches!(capability, BgpCapability::ExtendedMessage))
+    };
+    has_extended_message(open_tx) && has_extended_message(open_rx)
+}
+
+/// Build the [BgpParsingContext] of a BMP monitored peer from the OPEN messages
+/// exchanged by the monitored router and its peer in a Peer Up Notification
+pub fn bgp_parsing_context_from_peer_up(
+    peer_up: &PeerUpNotificationMessage,
+) -> Option<BgpParsingContext> {
+    match (peer_up.sent_message(), peer_up.received_message()) {
+        (BgpMessage::Open(open_tx), BgpMessage::Open(open_rx)) => {
+            Some(bgp_parsing_context_from_opens(open_tx, open_rx))
+        }
+        _ => None,
+    }
+}
 
 pub trait ExtendBmpParsingContext {
     fn peer_count(&self) -> usize;
@@ -8,6 +93,8 @@ pub trait ExtendBmpParsingContext {
 
     fn add_default_peer(&mut self, peer_key: PeerKey);
 
+    fn add_peer_from_peer_up(&mut self, peer_up: &PeerUpNotificationMessage);
+
     fn delete_peer(&mut self, peer_key: &PeerKey);
 
     fn get_peer(&mut self, peer_key: &PeerKey) -> Option<&mut BgpParsingContext>;
@@ -25,6 +112,14 @@ impl ExtendBmpParsingContext for BmpParsingContext {
         self.add_peer(peer_key, BgpParsingContext::default())
     }
 
+    fn add_peer_from_peer_up(&mut self, peer_up: &PeerUpNotificationMessage) {
+        let peer_key = PeerKey::from_peer_header(peer_up.peer_header());
+        match bgp_parsing_context_from_peer_up(peer_up) {
+            Some(parsing_context) => self.add_peer(peer_key, parsing_context),
+            None => self.add_default_peer(peer_key),
+        }
+    }
+
     fn delete_peer(&mut self, peer_key: &PeerKey) {
         self.remove(peer_key);
     }
@@ -1,9 +1,20 @@
 use netgauze_bmp_pkt::iana::PeerTerminationCode;
 use netgauze_bmp_pkt::{InitiationInformation, TerminationInformation};
+use netgauze_parse_utils::WritablePdu;
 use std::ffi::c_void;
 
 pub trait TlvExtension {
     fn get_value_ptr(&self) -> *mut c_void;
+
+    fn get_raw_type(&self) -> u16;
+
+    /// Length of the value, without the type and length fields
+    fn get_value_len(&self) -> u16;
+
+    /// Private Enterprise Number of the TLV, 0 for IANA TLVs
+    fn get_pen(&self) -> u32 {
+        0
+    }
 }
 
 impl TlvExtension for InitiationInformation {
@@ -22,6 +33,14 @@ impl TlvExtension for InitiationInformation {
 
         ptr as *mut c_void
     }
+
+    fn get_raw_type(&self) -> u16 {
+        self.get_type().into()
+    }
+
+    fn get_value_len(&self) -> u16 {
+        (self.len() - Self::BASE_LENGTH) as u16
+    }
 }
 
 impl TlvExtension for TerminationInformation {
@@ -39,4 +58,12 @@ impl TlvExtension for TerminationInformation {
 
         ptr as *mut c_void
     }
+
+    fn get_raw_type(&self) -> u16 {
+        self.get_type().into()
+    }
+
+    fn get_value_len(&self) -> u16 {
+        (self.len() - Self::BASE_LENGTH) as u16
+    }
 }
@@ -16,6 +16,7 @@ pub trait ExtendRouteDistinguisher {
 }
 
 #[repr(u32)]
+#[derive(Debug, Clone, Copy)]
 pub enum RdOriginType {
     Unknown = RD_ORIGIN_UNKNOWN,
     Mask = RD_ORIGIN_MASK,
@@ -37,7 +38,8 @@ impl ExtendRouteDistinguisher for RouteDistinguisher {
         let mut new_rd: rd_t = self.into();
         new_rd.set_pmacct_rd_origin(origin);
 
-        new_rd.into()
+        // Only Leaf A-D routes have no pmacct RD type, they keep no origin
+        new_rd.try_into().unwrap_or(self)
     }
 }
 